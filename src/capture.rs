@@ -0,0 +1,258 @@
+// THE FILE MADE FOR EVERYTHING AROUND THE CAPTURE ITSELF:
+// CAPTURE FILES, THE METADATA OF EVERY CAUGHT PACKET AND ITS TIME
+
+pub mod pcap;
+pub mod pcapng;
+pub mod rotation;
+pub mod source;
+
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter};
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use pcap::{PcapReader, PcapWriter};
+use pcapng::{PcapngReader, PcapngWriter};
+
+use crate::print_error;
+use crate::layers::{self, CheckedPacket};
+use crate::rules::RuleOutput;
+
+
+
+// LINK TYPES AS THEY ARE WRITTEN IN THE CAPTURE FILES
+// (SEE THE tcpdump.org LINK-LAYER HEADER TYPES LIST)
+
+pub const LINKTYPE_NULL       : u32 = 0;
+pub const LINKTYPE_ETHERNET   : u32 = 1;
+pub const LINKTYPE_RAW        : u32 = 101;
+pub const LINKTYPE_LOOP       : u32 = 108;
+pub const LINKTYPE_LINUX_SLL  : u32 = 113;
+pub const LINKTYPE_IPV4       : u32 = 228;
+pub const LINKTYPE_IPV6       : u32 = 229;
+pub const LINKTYPE_LINUX_SLL2 : u32 = 276;
+
+// SOME OLD FILES STILL HAVE THE RAW IP WRITTEN AS THE PLATFORM DLT VALUE
+
+pub const DLT_RAW             : u32 = 12;
+pub const DLT_RAW_OPENBSD     : u32 = 14;
+
+
+
+// THE METADATA OF THE PACKET, WHICH IS PRINTED IN THE HEADER
+// OF EVERY PACKET BEFORE THE LAYERS THEMSELVES
+
+#[derive(Debug)]
+pub struct PacketInfo<'a> {
+    pub id           : usize,
+    pub interface    : &'a str,
+    pub timestamp    : Duration,
+    pub link_type    : u32,
+    pub original_len : u32,
+    pub comment      : Option<&'a str>,
+}
+
+// THE PACKET AS IT IS STORED INSIDE THE CAPTURE FILE
+
+#[derive(Debug)]
+pub struct Record {
+    pub timestamp    : Duration,
+    pub original_len : u32,
+    pub data         : Vec<u8>,
+    pub interface    : usize,
+    pub link_type    : u32,
+    pub comment      : Option<String>,
+}
+
+
+
+// ANY OF THE SUPPORTED CAPTURE FILES
+// THE FORMAT IS RECOGNISED BY THE FIRST FOUR BYTES OF THE FILE
+
+pub enum CaptureFile {
+    Pcap(PcapReader<BufReader<File>>),
+    Pcapng(PcapngReader<BufReader<File>>),
+}
+
+impl CaptureFile {
+    pub fn open(path : &str) -> io::Result<CaptureFile> {
+        let mut reader = BufReader::new(File::open(path)?);
+
+        let is_pcapng = reader.fill_buf()?
+            .starts_with(&pcapng::SECTION_HEADER_BLOCK.to_le_bytes());
+
+        if is_pcapng {
+            return Ok(CaptureFile::Pcapng(PcapngReader::new(reader)?));
+        }
+
+        Ok(CaptureFile::Pcap(PcapReader::new(reader)?))
+    }
+
+    pub fn next_record(&mut self) -> io::Result<Option<Record>> {
+        match self {
+            CaptureFile::Pcap(reader)   => reader.next_record(),
+            CaptureFile::Pcapng(reader) => reader.next_record(),
+        }
+    }
+
+    // PCAPNG MAY HAVE MANY LINK TYPES, ONLY THE ONES OF THE INTERFACES READ SO FAR ARE KNOWN
+
+    pub fn link_types(&self) -> Vec<u32> {
+        match self {
+            CaptureFile::Pcap(reader)   => vec![reader.link_type],
+            CaptureFile::Pcapng(reader) => reader.interfaces.iter()
+                                                 .map(|interface| interface.link_type).collect(),
+        }
+    }
+
+    // THE NAME OF THE INTERFACE IS KNOWN ONLY IF PCAPNG HAS IT WRITTEN DOWN
+
+    pub fn interface_name(&self, index : usize) -> Option<String> {
+        match self {
+            CaptureFile::Pcap(_) => None,
+            CaptureFile::Pcapng(reader) => reader.interfaces.get(index)
+                                                 .and_then(|interface| interface.name.clone()),
+        }
+    }
+}
+
+
+
+// ANY FILE THE MATCHED PACKETS CAN BE SAVED TO
+
+pub trait CaptureWriter : Send {
+    fn write_packet(&mut self, info : &PacketInfo, data : &[u8]) -> io::Result<()>;
+    fn flush(&mut self) -> io::Result<()>;
+}
+
+// THE FORMAT IS CHOSEN BY THE EXTENSION, PCAPNG FOR ".pcapng", CLASSIC PCAP OTHERWISE
+
+pub fn is_pcapng(path : &str) -> bool {
+    Path::new(path).extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("pcapng"))
+}
+
+// THE LINK TYPE IS NEEDED ONLY BY THE CLASSIC PCAP, WHICH WRITES IT IN ITS HEADER
+
+pub fn create_writer(path : &str, link_type : u32) -> io::Result<Box<dyn CaptureWriter>> {
+    let file = BufWriter::new(File::create(path)?);
+
+    if is_pcapng(path) {
+        return Ok(Box::new(PcapngWriter::new(file)?));
+    }
+
+    Ok(Box::new(PcapWriter::new(file, link_type)?))
+}
+
+
+
+// WHERE THE MATCHED PACKETS GO: THE TERMINAL, THE FILE OR BOTH
+// THE WRITER IS SHARED BY ALL THE INTERFACE THREADS, SO IT IS LOCKED
+// FOR THE WHOLE PACKET TO NEVER MIX TWO RECORDS TOGETHER
+
+pub struct Output {
+    pub print  : bool,
+    pub writer : Option<Mutex<Box<dyn CaptureWriter>>>,
+
+    // WHAT THE RULES DO WITH THE PACKETS THEY MATCHED
+    pub rules  : RuleOutput,
+}
+
+impl Output {
+    // PRINTING AND SAVING THE PACKET, WHICH PASSED THE FILTERS
+
+    pub fn emit(&self, info : &PacketInfo, packet : &CheckedPacket, data : &[u8]) {
+        if self.print {
+            layers::print_packet(info, &packet.layers);
+        }
+
+        self.rules.emit(info, &packet.layers, data, &packet.rules);
+
+        let Some(writer) = &self.writer else {
+            return;
+        };
+
+        let mut writer = writer.lock().unwrap_or_else(|e| e.into_inner());
+
+        if let Err(e) = writer.write_packet(info, data) {
+            print_error();
+            println!("CANNOT SAVE PACKET #{} FROM {}: {}", info.id, info.interface, e);
+        }
+    }
+
+    // CALLED ONCE THE CAPTURE IS OVER TO NOT LOSE THE BUFFERED PACKETS
+
+    pub fn finish(&self) {
+        self.rules.finish();
+
+        let Some(writer) = &self.writer else {
+            return;
+        };
+
+        let mut writer = writer.lock().unwrap_or_else(|e| e.into_inner());
+
+        if let Err(e) = writer.flush() {
+            print_error();
+            println!("CANNOT FINISH WRITING THE FILE: {}", e);
+        }
+    }
+}
+
+
+
+// THE TIME OF THE LIVE PACKETS IS TAKEN AT THE MOMENT OF RECEIVING
+
+pub fn now() -> Duration {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default()
+}
+
+// CONVERTING THE DAYS SINCE 1970-01-01 INTO THE CIVIL DATE
+// (HOWARD HINNANT'S ALGORITHM, SO NO CALENDAR DEPENDENCY IS NEEDED)
+
+fn civil_from_days(days : i64) -> (i64, u32, u32) {
+    let z   = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp  = (5 * doy + 2) / 153;
+    let day   = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year  = yoe + era * 400 + i64::from(month <= 2);
+
+    (year, month, day)
+}
+
+// SPLITTING THE TIMESTAMP INTO (YEAR, MONTH, DAY, HOUR, MINUTE, SECOND) IN UTC
+
+pub fn split_timestamp(timestamp : Duration) -> (i64, u32, u32, u64, u64, u64) {
+    let secs = timestamp.as_secs();
+    let (year, month, day) = civil_from_days((secs / 86_400) as i64);
+
+    (year, month, day, secs % 86_400 / 3600, secs % 3600 / 60, secs % 60)
+}
+
+pub fn format_timestamp(timestamp : Duration) -> String {
+    let (year, month, day, hour, minute, second) = split_timestamp(timestamp);
+
+    format!("{:04}-{:02}-{:02} {:02}:{:02}:{:02}.{:06} UTC", year, month, day,
+            hour, minute, second, timestamp.subsec_micros())
+}
+
+
+
+// TESTS
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_timestamp() {
+        assert_eq!(format_timestamp(Duration::from_secs(0)),
+                   "1970-01-01 00:00:00.000000 UTC");
+        assert_eq!(format_timestamp(Duration::new(1_709_210_096, 123_456_000)),
+                   "2024-02-29 12:34:56.123456 UTC");
+    }
+}
@@ -0,0 +1,239 @@
+// CLASSIC LIBPCAP FILE FORMAT
+// 24 BYTES OF THE GLOBAL HEADER, THEN 16 BYTES OF THE RECORD HEADER
+// BEFORE EVERY SINGLE PACKET
+
+use std::io::{self, Read, Write};
+use std::time::Duration;
+
+use crate::capture::{CaptureWriter, PacketInfo, Record};
+
+const MAGIC_MICROS : u32 = 0xa1b2_c3d4;
+const MAGIC_NANOS  : u32 = 0xa1b2_3c4d;
+
+// NO REAL PACKET IS BIGGER THAN THAT, THE BIGGER LENGTH
+// MEANS THE FILE IS BROKEN AND WE SHOULD NOT ALLOCATE IT
+
+const MAX_RECORD_LEN : u32 = 256 * 1024;
+
+// THE SNAPSHOT LENGTH WRITTEN INTO THE FILES WE CREATE
+
+pub const WRITER_SNAPLEN : u32 = 262_144;
+
+pub struct PcapReader<R : Read> {
+    reader    : R,
+    swapped   : bool,
+    nanos     : bool,
+    pub link_type : u32,
+    pub snaplen   : u32,
+}
+
+fn invalid(message : &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+impl<R : Read> PcapReader<R> {
+    pub fn new(mut reader : R) -> io::Result<PcapReader<R>> {
+        let mut header = [0u8; 24];
+        reader.read_exact(&mut header)?;
+
+        // THE MAGIC NUMBER TELLS BOTH THE BYTE ORDER OF THE WRITER
+        // AND THE RESOLUTION OF THE TIMESTAMPS
+
+        let magic = u32::from_le_bytes([header[0], header[1], header[2], header[3]]);
+
+        let (swapped, nanos) = match magic {
+            MAGIC_MICROS => (false, false),
+            MAGIC_NANOS  => (false, true),
+            _ if magic.swap_bytes() == MAGIC_MICROS => (true, false),
+            _ if magic.swap_bytes() == MAGIC_NANOS  => (true, true),
+            _ => return Err(invalid("NOT A PCAP FILE")),
+        };
+
+        let mut pcap = PcapReader { reader, swapped, nanos, link_type : 0, snaplen : 0 };
+
+        pcap.snaplen   = pcap.read_u32(&header[16..20]);
+        pcap.link_type = pcap.read_u32(&header[20..24]) & 0x0fff_ffff;
+
+        Ok(pcap)
+    }
+
+    fn read_u32(&self, bytes : &[u8]) -> u32 {
+        let value = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+
+        if self.swapped { value.swap_bytes() } else { value }
+    }
+
+    // RETURNS NONE WHEN THE FILE IS OVER
+
+    pub fn next_record(&mut self) -> io::Result<Option<Record>> {
+        let mut header = [0u8; 16];
+
+        match self.reader.read_exact(&mut header) {
+            Ok(()) => (),
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(e),
+        }
+
+        let seconds      = self.read_u32(&header[0..4]);
+        let fraction     = self.read_u32(&header[4..8]);
+        let captured_len = self.read_u32(&header[8..12]);
+        let original_len = self.read_u32(&header[12..16]);
+
+        if captured_len > MAX_RECORD_LEN {
+            return Err(invalid("PCAP RECORD IS TOO LONG"));
+        }
+
+        let mut data = vec![0u8; captured_len as usize];
+        self.reader.read_exact(&mut data)?;
+
+        let nanos = if self.nanos { fraction } else { fraction.saturating_mul(1000) };
+
+        Ok(Some(Record {
+            timestamp : Duration::new(u64::from(seconds), nanos),
+            original_len,
+            data,
+            interface : 0,
+            link_type : self.link_type,
+            comment   : None,
+        }))
+    }
+}
+
+
+
+// THE CLASSIC PCAP KNOWS ONLY ONE LINK TYPE FOR THE WHOLE FILE
+// SO THE GLOBAL HEADER IS WRITTEN RIGHT AWAY, EVEN THE FILE WITHOUT PACKETS CAN BE OPENED
+
+pub struct PcapWriter<W : Write> {
+    writer    : W,
+    link_type : u32,
+
+    // THE PACKETS OF ANOTHER LINK TYPE ARE REPORTED ONLY ONCE, THEN SKIPPED
+    mismatch  : bool,
+}
+
+impl<W : Write> PcapWriter<W> {
+    pub fn new(writer : W, link_type : u32) -> io::Result<PcapWriter<W>> {
+        let mut pcap = PcapWriter { writer, link_type, mismatch : false };
+        pcap.write_header()?;
+
+        Ok(pcap)
+    }
+
+    fn write_header(&mut self) -> io::Result<()> {
+        let mut header = Vec::with_capacity(24);
+
+        header.extend_from_slice(&MAGIC_MICROS.to_le_bytes());
+        header.extend_from_slice(&2u16.to_le_bytes());
+        header.extend_from_slice(&4u16.to_le_bytes());
+        header.extend_from_slice(&0i32.to_le_bytes());
+        header.extend_from_slice(&0u32.to_le_bytes());
+        header.extend_from_slice(&WRITER_SNAPLEN.to_le_bytes());
+        header.extend_from_slice(&self.link_type.to_le_bytes());
+
+        self.writer.write_all(&header)
+    }
+}
+
+impl<W : Write + Send> CaptureWriter for PcapWriter<W> {
+    fn write_packet(&mut self, info : &PacketInfo, data : &[u8]) -> io::Result<()> {
+        if info.link_type != self.link_type {
+            if self.mismatch {
+                return Ok(());
+            }
+
+            self.mismatch = true;
+            return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                       "PCAP CANNOT MIX LINK TYPES, USE PCAPNG INSTEAD"));
+        }
+
+        let data = &data[..data.len().min(WRITER_SNAPLEN as usize)];
+
+        let mut record = Vec::with_capacity(16 + data.len());
+
+        record.extend_from_slice(&(info.timestamp.as_secs() as u32).to_le_bytes());
+        record.extend_from_slice(&info.timestamp.subsec_micros().to_le_bytes());
+        record.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        record.extend_from_slice(&info.original_len.max(data.len() as u32).to_le_bytes());
+        record.extend_from_slice(data);
+
+        self.writer.write_all(&record)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+
+
+// TESTS
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_read_records() {
+        let mut file = vec![0xd4, 0xc3, 0xb2, 0xa1, 0x02, 0x00, 0x04, 0x00,
+                            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+                            0xff, 0xff, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00];
+
+        // ONE RECORD: 10 SECONDS, 5 MICROSECONDS, 4 OUT OF 60 BYTES CAPTURED
+        file.extend_from_slice(&[0x0a, 0x00, 0x00, 0x00, 0x05, 0x00, 0x00, 0x00,
+                                 0x04, 0x00, 0x00, 0x00, 0x3c, 0x00, 0x00, 0x00,
+                                 0xde, 0xad, 0xbe, 0xef]);
+
+        let mut reader = PcapReader::new(Cursor::new(file)).unwrap();
+        assert_eq!(reader.link_type, 1);
+        assert_eq!(reader.snaplen, 65535);
+
+        let record = reader.next_record().unwrap().unwrap();
+        assert_eq!(record.timestamp, Duration::new(10, 5000));
+        assert_eq!(record.original_len, 60);
+        assert_eq!(record.data, vec![0xde, 0xad, 0xbe, 0xef]);
+
+        assert!(reader.next_record().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_write_and_read_back() {
+        let info = PacketInfo { id : 0, interface : "eth0", timestamp : Duration::new(7, 1000),
+                                link_type : 1, original_len : 100, comment : None };
+
+        let mut writer = PcapWriter::new(Vec::new(), 1).unwrap();
+        writer.write_packet(&info, &[1, 2, 3]).unwrap();
+        writer.write_packet(&info, &[4, 5]).unwrap();
+
+        // THE OTHER LINK TYPE FAILS ONLY THE FIRST TIME, THE PACKET IS NOT WRITTEN
+        let raw = PacketInfo { link_type : 101, ..info };
+        assert!(writer.write_packet(&raw, &[6]).is_err());
+        assert!(writer.write_packet(&raw, &[6]).is_ok());
+
+        let mut reader = PcapReader::new(Cursor::new(writer.writer)).unwrap();
+        assert_eq!(reader.link_type, 1);
+
+        let record = reader.next_record().unwrap().unwrap();
+        assert_eq!(record.timestamp, Duration::new(7, 1000));
+        assert_eq!(record.original_len, 100);
+        assert_eq!(record.data, vec![1, 2, 3]);
+
+        assert_eq!(reader.next_record().unwrap().unwrap().data, vec![4, 5]);
+        assert!(reader.next_record().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_header_without_packets() {
+        let writer = PcapWriter::new(Vec::new(), 101).unwrap();
+
+        let mut reader = PcapReader::new(Cursor::new(writer.writer)).unwrap();
+        assert_eq!(reader.link_type, 101);
+        assert!(reader.next_record().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_not_pcap() {
+        assert!(PcapReader::new(Cursor::new(vec![0u8; 24])).is_err());
+    }
+}
@@ -0,0 +1,553 @@
+// PCAPNG FILE FORMAT
+// THE FILE IS A SEQUENCE OF BLOCKS: TYPE, TOTAL LENGTH, BODY, TOTAL LENGTH AGAIN
+// EVERY SECTION STARTS WITH THE SECTION HEADER BLOCK, WHICH GIVES THE BYTE ORDER
+// AND IS FOLLOWED BY THE INTERFACE DESCRIPTIONS AND THE PACKETS THEMSELVES
+
+use std::io::{self, Read, Write};
+use std::time::Duration;
+
+use crate::capture::{CaptureWriter, PacketInfo, Record};
+use crate::capture::pcap::WRITER_SNAPLEN;
+
+pub const SECTION_HEADER_BLOCK   : u32 = 0x0a0d_0d0a;
+pub const INTERFACE_BLOCK        : u32 = 0x0000_0001;
+pub const OBSOLETE_PACKET_BLOCK  : u32 = 0x0000_0002;
+pub const SIMPLE_PACKET_BLOCK    : u32 = 0x0000_0003;
+pub const ENHANCED_PACKET_BLOCK  : u32 = 0x0000_0006;
+
+pub const BYTE_ORDER_MAGIC : u32 = 0x1a2b_3c4d;
+
+// OPTION CODES WE ARE INTERESTED IN
+
+pub const OPT_END_OF_OPT : u16 = 0;
+pub const OPT_COMMENT    : u16 = 1;
+pub const IF_NAME        : u16 = 2;
+pub const IF_TSRESOL     : u16 = 9;
+pub const IF_TSOFFSET    : u16 = 14;
+
+// THE SAME LIMIT AS FOR THE CLASSIC PCAP, BIGGER BLOCKS MEAN BROKEN FILE
+
+const MAX_BLOCK_LEN : u32 = 16 * 1024 * 1024;
+
+
+
+// EVERYTHING THE INTERFACE DESCRIPTION BLOCK TELLS ABOUT THE INTERFACE
+
+#[derive(Clone, Debug)]
+pub struct InterfaceDescription {
+    pub name       : Option<String>,
+    pub link_type  : u32,
+    pub snaplen    : u32,
+
+    // HOW MANY TIMESTAMP UNITS ARE IN ONE SECOND (MICROSECONDS BY DEFAULT)
+    pub units_per_second : u64,
+    pub offset_seconds   : i64,
+}
+
+impl InterfaceDescription {
+    fn timestamp(&self, units : u64) -> Duration {
+        let seconds  = units / self.units_per_second;
+        let fraction = units % self.units_per_second;
+
+        let nanos = (u128::from(fraction) * 1_000_000_000
+                     / u128::from(self.units_per_second)) as u32;
+
+        let seconds = seconds.saturating_add_signed(self.offset_seconds);
+
+        Duration::new(seconds, nanos)
+    }
+}
+
+pub struct PcapngReader<R : Read> {
+    reader     : R,
+    swapped    : bool,
+    pub interfaces : Vec<InterfaceDescription>,
+
+    // THE FIRST BLOCK AFTER THE INTERFACES, READ WHILE LOOKING FOR THEM
+    pending    : Option<(u32, Vec<u8>)>,
+}
+
+fn invalid(message : &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+impl<R : Read> PcapngReader<R> {
+    pub fn new(reader : R) -> io::Result<PcapngReader<R>> {
+        let mut pcapng = PcapngReader { reader, swapped : false, interfaces : vec![], 
+                                        pending : None };
+
+        // THE FIRST BLOCK MUST BE THE SECTION HEADER
+
+        match pcapng.read_block()? {
+            Some((SECTION_HEADER_BLOCK, _)) => (),
+            _ => return Err(invalid("NOT A PCAPNG FILE")),
+        }
+
+        // THE INTERFACES RIGHT AFTER THE HEADER ARE READ AT ONCE,
+        // SO THEIR LINK TYPES ARE KNOWN BEFORE THE FIRST PACKET
+
+        loop {
+            match pcapng.read_block()? {
+                Some((INTERFACE_BLOCK, body)) => pcapng.add_interface(&body)?,
+                block => {
+                    pcapng.pending = block;
+                    return Ok(pcapng);
+                }
+            }
+        }
+    }
+
+    fn u16_at(&self, bytes : &[u8], at : usize) -> u16 {
+        let value = u16::from_le_bytes([bytes[at], bytes[at + 1]]);
+
+        if self.swapped { value.swap_bytes() } else { value }
+    }
+
+    fn u32_at(&self, bytes : &[u8], at : usize) -> u32 {
+        let value = u32::from_le_bytes([bytes[at], bytes[at + 1], bytes[at + 2], bytes[at + 3]]);
+
+        if self.swapped { value.swap_bytes() } else { value }
+    }
+
+    // READING THE WHOLE BLOCK, RETURNING ITS TYPE AND BODY
+    // THE SECTION HEADER IS HANDLED RIGHT HERE AS IT CHANGES THE BYTE ORDER
+
+    fn read_block(&mut self) -> io::Result<Option<(u32, Vec<u8>)>> {
+        let mut header = [0u8; 8];
+
+        match self.reader.read_exact(&mut header) {
+            Ok(()) => (),
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(e),
+        }
+
+        let block_type = self.u32_at(&header, 0);
+
+        if block_type == SECTION_HEADER_BLOCK {
+            let mut magic = [0u8; 4];
+            self.reader.read_exact(&mut magic)?;
+
+            self.swapped = match u32::from_le_bytes(magic) {
+                BYTE_ORDER_MAGIC => false,
+                magic if magic.swap_bytes() == BYTE_ORDER_MAGIC => true,
+                _ => return Err(invalid("BROKEN PCAPNG SECTION HEADER")),
+            };
+
+            // NEW SECTION, NEW INTERFACES
+            self.interfaces.clear();
+
+            let total_len = self.u32_at(&header, 4);
+            if !(28..=MAX_BLOCK_LEN).contains(&total_len) || total_len % 4 != 0 {
+                return Err(invalid("BROKEN PCAPNG SECTION HEADER"));
+            }
+
+            let mut rest = vec![0u8; total_len as usize - 12];
+            self.reader.read_exact(&mut rest)?;
+
+            return Ok(Some((SECTION_HEADER_BLOCK, rest)));
+        }
+
+        let total_len = self.u32_at(&header, 4);
+        if !(12..=MAX_BLOCK_LEN).contains(&total_len) || total_len % 4 != 0 {
+            return Err(invalid("BROKEN PCAPNG BLOCK LENGTH"));
+        }
+
+        let mut body = vec![0u8; total_len as usize - 8];
+        self.reader.read_exact(&mut body)?;
+
+        // THE TRAILING COPY OF THE LENGTH IS NOT A PART OF THE BODY
+        body.truncate(total_len as usize - 12);
+
+        Ok(Some((block_type, body)))
+    }
+
+    // GOING THROUGH THE OPTIONS, CALLING THE CLOSURE FOR EVERY SINGLE ONE
+
+    fn for_each_option<F>(&self, options : &[u8], mut f : F)
+    where F : FnMut(u16, &[u8]) {
+        let mut position = 0;
+
+        while position + 4 <= options.len() {
+            let code = self.u16_at(options, position);
+            let len  = self.u16_at(options, position + 2) as usize;
+
+            if code == OPT_END_OF_OPT || position + 4 + len > options.len() {
+                break;
+            }
+
+            f(code, &options[position + 4..position + 4 + len]);
+
+            // THE VALUES ARE PADDED TO 32 BITS
+            position += 4 + len.div_ceil(4) * 4;
+        }
+    }
+
+    fn add_interface(&mut self, body : &[u8]) -> io::Result<()> {
+        if body.len() < 8 {
+            return Err(invalid("BROKEN PCAPNG INTERFACE DESCRIPTION"));
+        }
+
+        let mut interface = InterfaceDescription {
+            name             : None,
+            link_type        : u32::from(self.u16_at(body, 0)),
+            snaplen          : self.u32_at(body, 4),
+            units_per_second : 1_000_000,
+            offset_seconds   : 0,
+        };
+
+        let mut tsresol = None;
+        let mut tsoffset = None;
+
+        self.for_each_option(&body[8..], |code, value| {
+            match code {
+                IF_NAME => interface.name = Some(String::from_utf8_lossy(value)
+                                                    .trim_end_matches('\0').to_string()),
+                IF_TSRESOL if !value.is_empty() => tsresol = Some(value[0]),
+                IF_TSOFFSET if value.len() == 8 => tsoffset = Some([value[0], value[1],
+                                                    value[2], value[3], value[4], value[5],
+                                                    value[6], value[7]]),
+                _ => (),
+            }
+        });
+
+        // THE MOST SIGNIFICANT BIT TELLS IF THE RESOLUTION IS A POWER OF 2 OR 10
+
+        if let Some(resolution) = tsresol {
+            let exponent = u32::from(resolution & 0x7f);
+
+            interface.units_per_second = if resolution & 0x80 != 0 {
+                1u64.checked_shl(exponent).unwrap_or(0)
+            } else {
+                10u64.checked_pow(exponent).unwrap_or(0)
+            };
+
+            if interface.units_per_second == 0 {
+                return Err(invalid("UNSUPPORTED PCAPNG TIMESTAMP RESOLUTION"));
+            }
+        }
+
+        if let Some(offset) = tsoffset {
+            let offset = i64::from_le_bytes(offset);
+            interface.offset_seconds = if self.swapped { offset.swap_bytes() } else { offset };
+        }
+
+        self.interfaces.push(interface);
+
+        Ok(())
+    }
+
+    fn interface(&self, index : usize) -> io::Result<&InterfaceDescription> {
+        self.interfaces.get(index)
+            .ok_or_else(|| invalid("PCAPNG PACKET FROM AN UNKNOWN INTERFACE"))
+    }
+
+    fn enhanced_packet(&self, body : &[u8]) -> io::Result<Record> {
+        if body.len() < 20 {
+            return Err(invalid("BROKEN PCAPNG ENHANCED PACKET"));
+        }
+
+        let index        = self.u32_at(body, 0) as usize;
+        let units        = (u64::from(self.u32_at(body, 4)) << 32) | u64::from(self.u32_at(body, 8));
+        let captured_len = self.u32_at(body, 12) as usize;
+        let original_len = self.u32_at(body, 16);
+
+        if 20 + captured_len > body.len() {
+            return Err(invalid("BROKEN PCAPNG ENHANCED PACKET"));
+        }
+
+        let interface = self.interface(index)?;
+        let data = body[20..20 + captured_len].to_vec();
+
+        // THERE MIGHT BE SEVERAL COMMENTS FOR THE SAME PACKET
+
+        let mut comments : Vec<String> = vec![];
+        let options_start = (20 + captured_len.div_ceil(4) * 4).min(body.len());
+
+        self.for_each_option(&body[options_start..], |code, value| {
+            if code == OPT_COMMENT {
+                comments.push(String::from_utf8_lossy(value).to_string());
+            }
+        });
+
+        Ok(Record {
+            timestamp : interface.timestamp(units),
+            original_len,
+            data,
+            interface : index,
+            link_type : interface.link_type,
+            comment   : if comments.is_empty() { None } else { Some(comments.join("; ")) },
+        })
+    }
+
+    fn simple_packet(&self, body : &[u8]) -> io::Result<Record> {
+        if body.len() < 4 {
+            return Err(invalid("BROKEN PCAPNG SIMPLE PACKET"));
+        }
+
+        // THE SIMPLE PACKETS ALWAYS BELONG TO THE FIRST INTERFACE
+        // AND HAVE NO TIMESTAMP AT ALL
+
+        let interface    = self.interface(0)?;
+        let original_len = self.u32_at(body, 0);
+
+        let mut captured_len = (original_len as usize).min(body.len() - 4);
+        if interface.snaplen != 0 {
+            captured_len = captured_len.min(interface.snaplen as usize);
+        }
+
+        Ok(Record {
+            timestamp : Duration::ZERO,
+            original_len,
+            data      : body[4..4 + captured_len].to_vec(),
+            interface : 0,
+            link_type : interface.link_type,
+            comment   : None,
+        })
+    }
+
+    fn obsolete_packet(&self, body : &[u8]) -> io::Result<Record> {
+        if body.len() < 20 {
+            return Err(invalid("BROKEN PCAPNG PACKET"));
+        }
+
+        // THE SAME AS THE ENHANCED ONE, BUT THE INTERFACE ID IS 16 BITS
+        // AND FOLLOWED BY THE DROPS COUNTER
+
+        let mut enhanced = body.to_vec();
+        let index = u32::from(self.u16_at(body, 0));
+
+        let index = if self.swapped { index.swap_bytes() } else { index };
+        enhanced[0..4].copy_from_slice(&index.to_le_bytes());
+
+        self.enhanced_packet(&enhanced)
+    }
+
+    // RETURNS NONE WHEN THE FILE IS OVER
+
+    pub fn next_record(&mut self) -> io::Result<Option<Record>> {
+        loop {
+            let block = match self.pending.take() {
+                Some(block) => Some(block),
+                None => self.read_block()?,
+            };
+
+            let Some((block_type, body)) = block else {
+                return Ok(None);
+            };
+
+            match block_type {
+                INTERFACE_BLOCK        => self.add_interface(&body)?,
+                ENHANCED_PACKET_BLOCK  => return self.enhanced_packet(&body).map(Some),
+                SIMPLE_PACKET_BLOCK    => return self.simple_packet(&body).map(Some),
+                OBSOLETE_PACKET_BLOCK  => return self.obsolete_packet(&body).map(Some),
+
+                // STATISTICS, NAME RESOLUTION AND EVERYTHING ELSE IS SKIPPED
+                _ => (),
+            }
+        }
+    }
+}
+
+
+
+// WRITING THE PCAPNG: ONE SECTION, THE INTERFACE DESCRIPTIONS ARE ADDED
+// THE FIRST TIME THE PACKET FROM THE INTERFACE COMES, ALL THE TIMESTAMPS IN NANOSECONDS
+
+pub struct PcapngWriter<W : Write> {
+    writer     : W,
+    interfaces : Vec<(String, u32)>,
+}
+
+fn push_option(body : &mut Vec<u8>, code : u16, value : &[u8]) {
+    let value = &value[..value.len().min(u16::MAX as usize)];
+
+    body.extend_from_slice(&code.to_le_bytes());
+    body.extend_from_slice(&(value.len() as u16).to_le_bytes());
+    body.extend_from_slice(value);
+    body.resize(body.len().div_ceil(4) * 4, 0);
+}
+
+fn write_block<W : Write>(writer : &mut W, block_type : u32, body : &[u8]) -> io::Result<()> {
+    let total_len = (12 + body.len()) as u32;
+
+    let mut block = Vec::with_capacity(total_len as usize);
+    block.extend_from_slice(&block_type.to_le_bytes());
+    block.extend_from_slice(&total_len.to_le_bytes());
+    block.extend_from_slice(body);
+    block.extend_from_slice(&total_len.to_le_bytes());
+
+    writer.write_all(&block)
+}
+
+impl<W : Write> PcapngWriter<W> {
+    pub fn new(mut writer : W) -> io::Result<PcapngWriter<W>> {
+        let mut body = BYTE_ORDER_MAGIC.to_le_bytes().to_vec();
+        body.extend_from_slice(&1u16.to_le_bytes());
+        body.extend_from_slice(&0u16.to_le_bytes());
+
+        // THE LENGTH OF THE SECTION IS NOT KNOWN
+        body.extend_from_slice(&(-1i64).to_le_bytes());
+
+        write_block(&mut writer, SECTION_HEADER_BLOCK, &body)?;
+
+        Ok(PcapngWriter { writer, interfaces : vec![] })
+    }
+
+    fn interface_index(&mut self, name : &str, link_type : u32) -> io::Result<usize> {
+        if let Some(index) = self.interfaces.iter()
+                                 .position(|(n, l)| n == name && *l == link_type) {
+            return Ok(index);
+        }
+
+        let mut body = (link_type as u16).to_le_bytes().to_vec();
+        body.extend_from_slice(&0u16.to_le_bytes());
+        body.extend_from_slice(&WRITER_SNAPLEN.to_le_bytes());
+
+        push_option(&mut body, IF_NAME, name.as_bytes());
+        push_option(&mut body, IF_TSRESOL, &[9]);
+        push_option(&mut body, OPT_END_OF_OPT, &[]);
+
+        write_block(&mut self.writer, INTERFACE_BLOCK, &body)?;
+
+        self.interfaces.push((name.to_string(), link_type));
+
+        Ok(self.interfaces.len() - 1)
+    }
+}
+
+impl<W : Write + Send> CaptureWriter for PcapngWriter<W> {
+    fn write_packet(&mut self, info : &PacketInfo, data : &[u8]) -> io::Result<()> {
+        let index = self.interface_index(info.interface, info.link_type)?;
+
+        let data  = &data[..data.len().min(WRITER_SNAPLEN as usize)];
+        let units = info.timestamp.as_nanos() as u64;
+
+        let mut body = Vec::with_capacity(32 + data.len());
+        body.extend_from_slice(&(index as u32).to_le_bytes());
+        body.extend_from_slice(&((units >> 32) as u32).to_le_bytes());
+        body.extend_from_slice(&(units as u32).to_le_bytes());
+        body.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        body.extend_from_slice(&info.original_len.max(data.len() as u32).to_le_bytes());
+        body.extend_from_slice(data);
+        body.resize(body.len().div_ceil(4) * 4, 0);
+
+        if let Some(comment) = info.comment {
+            push_option(&mut body, OPT_COMMENT, comment.as_bytes());
+            push_option(&mut body, OPT_END_OF_OPT, &[]);
+        }
+
+        write_block(&mut self.writer, ENHANCED_PACKET_BLOCK, &body)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+
+
+// TESTS
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn block(block_type : u32, body : &[u8]) -> Vec<u8> {
+        let total_len = (12 + body.len()) as u32;
+
+        let mut block = block_type.to_le_bytes().to_vec();
+        block.extend_from_slice(&total_len.to_le_bytes());
+        block.extend_from_slice(body);
+        block.extend_from_slice(&total_len.to_le_bytes());
+        block
+    }
+
+    fn section_header() -> Vec<u8> {
+        let mut body = BYTE_ORDER_MAGIC.to_le_bytes().to_vec();
+        body.extend_from_slice(&[1, 0, 0, 0]);
+        body.extend_from_slice(&u64::MAX.to_le_bytes());
+        block(SECTION_HEADER_BLOCK, &body)
+    }
+
+    #[test]
+    fn test_interfaces_and_comments() {
+        let mut file = section_header();
+
+        // INTERFACE 0: ETHERNET, NAMED "eth0", DEFAULT RESOLUTION
+        file.extend(block(INTERFACE_BLOCK, &[0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+                                            0x02, 0x00, 0x04, 0x00, b'e', b't', b'h', b'0',
+                                            0x00, 0x00, 0x00, 0x00]));
+
+        // INTERFACE 1: RAW IP, NANOSECONDS
+        file.extend(block(INTERFACE_BLOCK, &[0x65, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+                                            0x09, 0x00, 0x01, 0x00, 0x09, 0x00, 0x00, 0x00,
+                                            0x00, 0x00, 0x00, 0x00]));
+
+        // PACKET ON INTERFACE 1, 1.5 SECONDS, 3 BYTES AND A COMMENT "hi"
+        file.extend(block(ENHANCED_PACKET_BLOCK, &[0x01, 0x00, 0x00, 0x00,
+                                                   0x00, 0x00, 0x00, 0x00,
+                                                   0x00, 0x2f, 0x68, 0x59,
+                                                   0x03, 0x00, 0x00, 0x00,
+                                                   0x03, 0x00, 0x00, 0x00,
+                                                   0xaa, 0xbb, 0xcc, 0x00,
+                                                   0x01, 0x00, 0x02, 0x00,
+                                                   b'h', b'i', 0x00, 0x00,
+                                                   0x00, 0x00, 0x00, 0x00]));
+
+        let mut reader = PcapngReader::new(Cursor::new(file)).unwrap();
+
+        // THE INTERFACES ARE KNOWN BEFORE THE FIRST PACKET IS READ
+        assert_eq!(reader.interfaces.iter().map(|interface| interface.link_type)
+                         .collect::<Vec<u32>>(), vec![1, 101]);
+
+        let record = reader.next_record().unwrap().unwrap();
+        assert_eq!(record.interface, 1);
+        assert_eq!(record.link_type, 101);
+        assert_eq!(record.timestamp, Duration::new(1, 500_000_000));
+        assert_eq!(record.data, vec![0xaa, 0xbb, 0xcc]);
+        assert_eq!(record.comment.as_deref(), Some("hi"));
+
+        assert_eq!(reader.interfaces[0].name.as_deref(), Some("eth0"));
+        assert!(reader.interfaces[1].name.is_none());
+
+        assert!(reader.next_record().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_write_and_read_back() {
+        let mut info = PacketInfo { id : 0, interface : "eth0", link_type : 1,
+                                    timestamp : Duration::new(3, 123_456_789),
+                                    original_len : 3, comment : Some("look") };
+
+        let mut writer = PcapngWriter::new(Vec::new()).unwrap();
+        writer.write_packet(&info, &[1, 2, 3]).unwrap();
+
+        info.interface = "tun0";
+        info.link_type = 101;
+        info.comment   = None;
+        writer.write_packet(&info, &[4]).unwrap();
+
+        let mut reader = PcapngReader::new(Cursor::new(writer.writer)).unwrap();
+
+        let record = reader.next_record().unwrap().unwrap();
+        assert_eq!(record.interface, 0);
+        assert_eq!(record.timestamp, Duration::new(3, 123_456_789));
+        assert_eq!(record.data, vec![1, 2, 3]);
+        assert_eq!(record.comment.as_deref(), Some("look"));
+
+        let record = reader.next_record().unwrap().unwrap();
+        assert_eq!(record.interface, 1);
+        assert_eq!(record.link_type, 101);
+        assert_eq!(reader.interfaces[1].name.as_deref(), Some("tun0"));
+    }
+
+    #[test]
+    fn test_unknown_interface() {
+        let mut file = section_header();
+        file.extend(block(ENHANCED_PACKET_BLOCK, &[0u8; 20]));
+
+        let mut reader = PcapngReader::new(Cursor::new(file)).unwrap();
+        assert!(reader.next_record().is_err());
+    }
+}
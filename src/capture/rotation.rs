@@ -0,0 +1,228 @@
+// RING BUFFER OF THE CAPTURE FILES
+// THE NEW FILE IS STARTED WHEN THE CURRENT ONE GETS TOO BIG OR TOO OLD,
+// THE OLDEST FILES ARE REMOVED TO NEVER HAVE MORE THAN THE GIVEN AMOUNT
+
+use std::collections::VecDeque;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use crate::print_error;
+use crate::capture::{self, CaptureWriter, PacketInfo};
+use crate::capture::pcap::PcapWriter;
+use crate::capture::pcapng::PcapngWriter;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Rotation {
+    pub max_size  : Option<u64>,
+    pub every     : Option<Duration>,
+    pub max_files : Option<usize>,
+}
+
+impl Rotation {
+    pub fn is_enabled(&self) -> bool {
+        self.max_size.is_some() || self.every.is_some() || self.max_files.is_some()
+    }
+}
+
+
+
+// THE FILE WRITER COUNTING HOW MANY BYTES WENT THROUGH IT
+
+struct CountingWriter<W : Write> {
+    inner : W,
+    count : Arc<AtomicU64>,
+}
+
+impl<W : Write> Write for CountingWriter<W> {
+    fn write(&mut self, buf : &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.count.fetch_add(written as u64, Ordering::Relaxed);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+
+
+pub struct RotatingWriter {
+    path      : PathBuf,
+    rotation  : Rotation,
+    pcapng    : bool,
+    link_type : u32,
+
+    // THE CURRENT FILE: ITS WRITER, SIZE AND THE TIME OF THE FIRST PACKET
+    current   : Option<Box<dyn CaptureWriter>>,
+    size      : Arc<AtomicU64>,
+    started   : Duration,
+
+    sequence  : usize,
+    files     : VecDeque<PathBuf>,
+
+    // THE OLD FILES WHICH CANNOT BE REMOVED ARE REPORTED ONLY ONCE
+    remove_failed : bool,
+}
+
+impl RotatingWriter {
+    pub fn new(path : &str, rotation : Rotation, link_type : u32) -> RotatingWriter {
+        let pcapng = capture::is_pcapng(path);
+
+        RotatingWriter {
+            path : PathBuf::from(path), rotation, pcapng, link_type,
+            current : None, size : Arc::new(AtomicU64::new(0)), started : Duration::ZERO,
+            sequence : 0, files : VecDeque::new(), remove_failed : false,
+        }
+    }
+
+    // "capture.pcapng" BECOMES "capture_00001_20240229123456.pcapng"
+
+    fn file_name(&self, start : Duration) -> PathBuf {
+        let (year, month, day, hour, minute, second) = capture::split_timestamp(start);
+
+        let stem = self.path.file_stem().map(|stem| stem.to_string_lossy().to_string())
+                                        .unwrap_or_default();
+
+        let mut name = format!("{}_{:05}_{:04}{:02}{:02}{:02}{:02}{:02}", stem, self.sequence,
+                               year, month, day, hour, minute, second);
+
+        if let Some(extension) = self.path.extension() {
+            name = format!("{}.{}", name, extension.to_string_lossy());
+        }
+
+        self.path.with_file_name(name)
+    }
+
+    fn needs_rotation(&self, timestamp : Duration) -> bool {
+        if self.current.is_none() {
+            return true;
+        }
+
+        let too_big = self.rotation.max_size
+            .is_some_and(|max| self.size.load(Ordering::Relaxed) >= max);
+
+        let too_old = self.rotation.every
+            .is_some_and(|every| timestamp.saturating_sub(self.started) >= every);
+
+        too_big || too_old
+    }
+
+    fn rotate(&mut self, timestamp : Duration) -> io::Result<()> {
+        if let Some(mut current) = self.current.take() {
+            current.flush()?;
+        }
+
+        self.sequence += 1;
+
+        let path = self.file_name(timestamp);
+        let size = Arc::new(AtomicU64::new(0));
+        let file = CountingWriter { inner : BufWriter::new(File::create(&path)?),
+                                    count : Arc::clone(&size) };
+
+        self.current = Some(if self.pcapng {
+            Box::new(PcapngWriter::new(file)?)
+        } else {
+            Box::new(PcapWriter::new(file, self.link_type)?)
+        });
+
+        self.size    = size;
+        self.started = timestamp;
+        self.files.push_back(path);
+
+        // REMOVING THE OLDEST FILES OF THE RING
+
+        // THE FILE ALREADY REMOVED BY HAND IS FINE, THE OTHER ERRORS ARE SHOWN ONLY ONCE,
+        // THE PACKET GOES INTO THE NEW FILE ANYWAY
+
+        if let Some(max_files) = self.rotation.max_files {
+            while self.files.len() > max_files.max(1) {
+                let Some(oldest) = self.files.pop_front() else { break };
+
+                match fs::remove_file(&oldest) {
+                    Ok(()) => (),
+                    Err(e) if e.kind() == io::ErrorKind::NotFound => (),
+                    Err(e) if !self.remove_failed => {
+                        self.remove_failed = true;
+
+                        print_error();
+                        println!("CANNOT REMOVE THE OLD FILE '{}': {}", oldest.display(), e);
+                    },
+                    Err(_) => (),
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl CaptureWriter for RotatingWriter {
+    fn write_packet(&mut self, info : &PacketInfo, data : &[u8]) -> io::Result<()> {
+        if self.needs_rotation(info.timestamp) {
+            self.rotate(info.timestamp)?;
+        }
+
+        match &mut self.current {
+            Some(current) => current.write_packet(info, data),
+            None => Ok(()),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match &mut self.current {
+            Some(current) => current.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+
+
+// TESTS
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rotation_by_time_and_count() {
+        let directory = std::env::temp_dir().join(format!("ohrest_rotation_{}",
+                                                           std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+
+        let path = directory.join("ring.pcap");
+        let rotation = Rotation { max_size : None, every : Some(Duration::from_secs(60)),
+                                  max_files : Some(2) };
+
+        let mut writer = RotatingWriter::new(path.to_str().unwrap(), rotation,
+                                             capture::LINKTYPE_ETHERNET);
+
+        let info = |minute : u64| PacketInfo { id : 0, interface : "eth0", link_type : 1,
+                                               timestamp : Duration::from_secs(minute * 60),
+                                               original_len : 1, comment : None };
+
+        for minute in 0..4u64 {
+            writer.write_packet(&info(minute), &[0]).unwrap();
+        }
+
+        // THE OLDEST FILE REMOVED BY HAND DOES NOT STOP THE NEXT ROTATION
+        fs::remove_file(directory.join("ring_00003_19700101000200.pcap")).unwrap();
+        writer.write_packet(&info(4), &[0]).unwrap();
+        writer.flush().unwrap();
+
+        let mut names : Vec<String> = fs::read_dir(&directory).unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+            .collect();
+        names.sort();
+
+        assert_eq!(names, vec!["ring_00004_19700101000300.pcap".to_string(),
+                               "ring_00005_19700101000400.pcap".to_string()]);
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
@@ -0,0 +1,263 @@
+// EVERYTHING THE PACKETS CAN COME FROM: THE LIVE INTERFACE, THE CAPTURE FILE
+// OR JUST THE FRAMES IN THE MEMORY (FOR THE TESTS WITHOUT ROOT AND REAL NICS)
+// THE CAPTURE LOOP DOES NOT CARE WHICH ONE IT IS WORKING WITH
+
+use std::io;
+use std::path::Path;
+use std::time::Duration;
+
+use pnet::datalink::{DataLinkReceiver, NetworkInterface};
+
+use crate::capture::{self, CaptureFile, Record};
+use crate::channel::{self, ChannelOptions};
+use crate::interfaces;
+use crate::statistics::KernelStats;
+use crate::filtering::{Filters, bpf};
+
+
+
+// THE SINGLE FRAME, BORROWED FROM THE SOURCE UNTIL THE NEXT ONE IS ASKED FOR
+
+#[derive(Debug)]
+pub struct Frame<'a> {
+    pub data         : &'a [u8],
+    pub interface    : &'a str,
+    pub timestamp    : Duration,
+    pub link_type    : u32,
+    pub original_len : u32,
+    pub comment      : Option<&'a str>,
+}
+
+pub trait PacketSource : Send {
+    // THE NAME USED FOR THE STATISTICS OF THE SOURCE
+    fn name(&self) -> &str;
+
+    // THE LINK TYPES OF THE FRAMES, AS FAR AS THEY ARE KNOWN BEFORE THE FIRST ONE IS READ
+    fn link_types(&self) -> Vec<u32>;
+
+    // NONE MEANS THERE WILL BE NO MORE PACKETS,
+    // THE TIMED OUT OR INTERRUPTED READING IS NOT AN ERROR, JUST NOTHING CAME YET
+    fn next_frame(&mut self) -> io::Result<Option<Frame<'_>>>;
+
+    // WHAT THE KERNEL RECEIVED AND DROPPED SINCE THE PREVIOUS CALL,
+    // ONLY THE LIVE SOURCES HAVE THE KERNEL BEHIND THEM
+    fn kernel_stats(&mut self) -> Option<KernelStats> {
+        None
+    }
+}
+
+pub fn is_idle(error : &io::Error) -> bool {
+    matches!(error.kind(), io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock |
+                           io::ErrorKind::Interrupted)
+}
+
+
+
+// THE LIVE INTERFACE
+
+pub struct LiveSource {
+    name      : String,
+    rx        : Box<dyn DataLinkReceiver>,
+    options   : ChannelOptions,
+    link_type : u32,
+
+    // OWNED BY THE RECEIVER, KEPT FOR ASKING THE KERNEL STATISTICS
+    // AND FOR READING THE FRAMES WITH THEIR VLAN TAGS AND THEIR LENGTH ON THE WIRE
+    socket    : Option<i32>,
+    buffer    : Vec<u8>,
+}
+
+impl LiveSource {
+    // THE FILTERS ARE GIVEN TO THE KERNEL AS MUCH AS IT CAN UNDERSTAND THEM,
+    // THE SNAPLEN TOO, SO THE REST OF THE FRAME IS NOT EVEN COPIED
+
+    pub fn open(interface : &NetworkInterface, options : &ChannelOptions, filters : &Filters)
+                                                        -> io::Result<LiveSource> {
+        let link_type = interfaces::get_link_type(interface);
+        let program   = bpf::compile(filters, link_type, options.snaplen);
+
+        let (rx, socket) = channel::open_channel(interface, options, program.as_deref())?;
+        let buffer = vec![0; options.to_config().read_buffer_size + channel::VLAN_TAG_LENGTH];
+
+        Ok(LiveSource {
+            name      : interface.name.clone(),
+            rx,
+            options   : *options,
+            link_type,
+            socket,
+            buffer,
+        })
+    }
+}
+
+impl PacketSource for LiveSource {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn link_types(&self) -> Vec<u32> {
+        vec![self.link_type]
+    }
+
+    fn next_frame(&mut self) -> io::Result<Option<Frame<'_>>> {
+        // THE LENGTH ON THE WIRE, THE FRAME ITSELF MAY BE CUT BY THE READ BUFFER
+        let (frame, original_len) = match self.socket {
+            Some(socket) => {
+                channel::receive(socket, &mut self.buffer, self.options.poll_timeout())?
+            },
+            _ => {
+                let frame = self.rx.next()?;
+                (frame, frame.len())
+            },
+        };
+
+        Ok(Some(Frame {
+            // ONLY THE BEGINNING OF THE FRAME IS KEPT IF THE SNAPLEN IS GIVEN
+            data         : self.options.cut(frame),
+            interface    : &self.name,
+            timestamp    : capture::now(),
+            link_type    : self.link_type,
+            original_len : original_len as u32,
+            comment      : None,
+        }))
+    }
+
+    fn kernel_stats(&mut self) -> Option<KernelStats> {
+        channel::kernel_stats(self.socket?).ok()
+    }
+}
+
+
+
+// THE CAPTURE FILE, THE FILE NAME PLAYS THE ROLE OF THE INTERFACE
+// UNLESS PCAPNG KNOWS THE REAL NAME
+
+pub struct FileSource {
+    file      : CaptureFile,
+    file_name : String,
+    record    : Option<Record>,
+    interface : String,
+
+    // NOTHING CAN BE READ AFTER THE BROKEN RECORD, THE FILE IS OVER
+    broken    : bool,
+}
+
+impl FileSource {
+    pub fn open(path : &str) -> io::Result<FileSource> {
+        let file_name = Path::new(path).file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_else(|| path.to_string());
+
+        Ok(FileSource { file : CaptureFile::open(path)?, file_name, record : None,
+                        interface : String::new(), broken : false })
+    }
+}
+
+impl PacketSource for FileSource {
+    fn name(&self) -> &str {
+        &self.file_name
+    }
+
+    fn link_types(&self) -> Vec<u32> {
+        self.file.link_types()
+    }
+
+    fn next_frame(&mut self) -> io::Result<Option<Frame<'_>>> {
+        if self.broken {
+            return Ok(None);
+        }
+
+        let record = match self.file.next_record() {
+            Ok(Some(record)) => record,
+            Ok(None) => return Ok(None),
+            Err(e) => {
+                self.broken = true;
+                return Err(e);
+            }
+        };
+
+        self.interface = match self.file.interface_name(record.interface) {
+            Some(name) => name,
+            None if record.interface == 0 => self.file_name.clone(),
+            None => format!("{}#{}", self.file_name, record.interface),
+        };
+
+        let record = self.record.insert(record);
+
+        Ok(Some(Frame {
+            data         : &record.data,
+            interface    : &self.interface,
+            timestamp    : record.timestamp,
+            link_type    : record.link_type,
+            original_len : record.original_len,
+            comment      : record.comment.as_deref(),
+        }))
+    }
+}
+
+
+
+// THE FRAMES PREPARED IN THE MEMORY, ONE MILLISECOND AFTER ANOTHER
+
+pub struct MemorySource {
+    name      : String,
+    link_type : u32,
+    frames    : Vec<Vec<u8>>,
+    position  : usize,
+}
+
+impl MemorySource {
+    pub fn new(name : &str, link_type : u32, frames : Vec<Vec<u8>>) -> MemorySource {
+        MemorySource { name : name.to_string(), link_type, frames, position : 0 }
+    }
+}
+
+impl PacketSource for MemorySource {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn link_types(&self) -> Vec<u32> {
+        vec![self.link_type]
+    }
+
+    fn next_frame(&mut self) -> io::Result<Option<Frame<'_>>> {
+        let Some(data) = self.frames.get(self.position) else {
+            return Ok(None);
+        };
+
+        self.position += 1;
+
+        Ok(Some(Frame {
+            data,
+            interface    : &self.name,
+            timestamp    : Duration::from_millis(self.position as u64),
+            link_type    : self.link_type,
+            original_len : data.len() as u32,
+            comment      : None,
+        }))
+    }
+}
+
+
+
+// TESTS
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_memory_source() {
+        let mut source = MemorySource::new("mem0", 1, vec![vec![1, 2], vec![3]]);
+
+        assert_eq!(source.next_frame().unwrap().unwrap().data, &[1, 2]);
+
+        let frame = source.next_frame().unwrap().unwrap();
+        assert_eq!(frame.data, &[3]);
+        assert_eq!(frame.interface, "mem0");
+        assert_eq!(frame.timestamp, Duration::from_millis(2));
+
+        assert!(source.next_frame().unwrap().is_none());
+    }
+}
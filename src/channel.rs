@@ -0,0 +1,382 @@
+// THE FILE MADE FOR OPENING THE LIVE CHANNELS ON THE INTERFACES
+// THE OPTIONS GIVEN BY THE USER ARE MAPPED ONTO THE PNET CONFIGURATION
+
+use std::io;
+use std::time::{Duration, Instant};
+
+use pnet::datalink::{self, Channel, DataLinkReceiver, FanoutOption, FanoutType,
+                     NetworkInterface};
+
+use crate::print_error;
+use crate::statistics::KernelStats;
+use crate::filtering::bpf::Instruction;
+
+// HOW OFTEN THE SILENT INTERFACE CHECKS IF THE CAPTURE SHOULD STOP
+// (UNLESS THE USER GAVE ITS OWN READ TIMEOUT)
+
+pub const POLL_INTERVAL : Duration = Duration::from_millis(200);
+
+// THE 802.1Q TAG (THE TPID AND THE TCI) WHICH GOES RIGHT AFTER THE MAC ADDRESSES
+
+pub const VLAN_TAG_LENGTH : usize = 4;
+
+const MAC_ADDRESSES_LENGTH : usize = 12;
+const ETHERTYPE_VLAN       : u16   = 0x8100;
+
+#[derive(Clone, Copy, Debug)]
+pub struct ChannelOptions {
+    pub promiscuous  : bool,
+    pub snaplen      : Option<usize>,
+    pub read_buffer  : Option<usize>,
+    pub write_buffer : Option<usize>,
+    pub read_timeout : Option<Duration>,
+    pub fanout       : Option<FanoutOption>,
+}
+
+impl Default for ChannelOptions {
+    fn default() -> ChannelOptions {
+        ChannelOptions {
+            promiscuous  : true,
+            snaplen      : None,
+            read_buffer  : None,
+            write_buffer : None,
+            read_timeout : None,
+            fanout       : None,
+        }
+    }
+}
+
+impl ChannelOptions {
+    pub fn to_config(self) -> datalink::Config {
+        let default = datalink::Config::default();
+
+        datalink::Config {
+            promiscuous       : self.promiscuous,
+            read_buffer_size  : self.read_buffer.unwrap_or(default.read_buffer_size),
+            write_buffer_size : self.write_buffer.unwrap_or(default.write_buffer_size),
+            read_timeout      : Some(self.poll_timeout()),
+            linux_fanout      : self.fanout,
+            ..default
+        }
+    }
+
+    // THE USER'S TIMEOUT CAN ONLY MAKE THE WAITING SHORTER, THE CAPTURE MUST STILL
+    // NOTICE IN TIME WHEN IT IS INTERRUPTED OR ITS DURATION IS OVER
+
+    pub fn poll_timeout(&self) -> Duration {
+        self.read_timeout.map_or(POLL_INTERVAL, |timeout| timeout.min(POLL_INTERVAL))
+    }
+
+    // THE PART OF THE FRAME KEPT FOR THE ANALYSIS AND THE FILES
+    // THE KERNEL FILTER CUTS THE FRAME ALREADY, BUT THE TAG PUT BACK MAKES IT LONGER AGAIN
+
+    pub fn cut<'a>(&self, packet : &'a [u8]) -> &'a [u8] {
+        match self.snaplen {
+            Some(snaplen) => &packet[..packet.len().min(snaplen)],
+            None => packet,
+        }
+    }
+}
+
+// "42" OR "42:lb", THE GROUP ID AND THE WAY THE PACKETS ARE SPREAD ACROSS THE GROUP
+
+pub fn fanout_from_str(value : &str) -> Option<FanoutOption> {
+    let (group, kind) = value.split_once(':').unwrap_or((value, "hash"));
+
+    let fanout_type = match kind {
+        "hash"     => FanoutType::HASH,
+        "lb"       => FanoutType::LB,
+        "cpu"      => FanoutType::CPU,
+        "rollover" => FanoutType::ROLLOVER,
+        "rnd"      => FanoutType::RND,
+        "qm"       => FanoutType::QM,
+        _ => return None,
+    };
+
+    Some(FanoutOption {
+        group_id : group.parse::<u16>().ok()?,
+        fanout_type,
+        defrag   : true,
+        rollover : false,
+    })
+}
+
+// ON LINUX THE SOCKET IS MADE HERE AND NOT BY PNET, SO IT CAN LATER BE ASKED
+// WHAT THE KERNEL DROPPED (PNET DOES NOT GIVE ITS SOCKET AWAY, BUT CLOSES IT)
+
+#[cfg(target_os = "linux")]
+fn open_socket() -> io::Result<Option<i32>> {
+    // NO PROTOCOL YET, OTHERWISE THE FRAMES OF EVERY INTERFACE WOULD BE QUEUED UNTIL THE BIND,
+    // THE RECEPTION STARTS WHEN PNET BINDS THE SOCKET TO THE INTERFACE WITH ETH_P_ALL
+    match unsafe { libc::socket(libc::AF_PACKET, libc::SOCK_RAW, 0) } {
+        -1 => Err(io::Error::last_os_error()),
+        socket => Ok(Some(socket)),
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn open_socket() -> io::Result<Option<i32>> {
+    Ok(None)
+}
+
+// THE NUMBERS SINCE THE PREVIOUS CALL, THE KERNEL RESETS THEM EVERY TIME
+
+#[cfg(target_os = "linux")]
+pub fn kernel_stats(socket : i32) -> io::Result<KernelStats> {
+    let mut stats  : libc::tpacket_stats = libc::tpacket_stats { tp_packets : 0, tp_drops : 0 };
+    let mut length : libc::socklen_t     = std::mem::size_of::<libc::tpacket_stats>() 
+                                                                        as libc::socklen_t;
+
+    let result = unsafe {
+        libc::getsockopt(socket, libc::SOL_PACKET, libc::PACKET_STATISTICS,
+                         &mut stats as *mut libc::tpacket_stats as *mut libc::c_void,
+                         &mut length)
+    };
+
+    if result == -1 {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok(KernelStats { received : u64::from(stats.tp_packets), 
+                     dropped  : u64::from(stats.tp_drops) })
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn kernel_stats(_socket : i32) -> io::Result<KernelStats> {
+    Err(io::Error::from(io::ErrorKind::Unsupported))
+}
+
+// THE KERNEL TAKES THE VLAN TAG OUT OF THE FRAME BEFORE THE SOCKET SEES IT,
+// WITH PACKET_AUXDATA IT TELLS THE TAG ASIDE OF EVERY FRAME
+
+#[cfg(target_os = "linux")]
+fn enable_auxdata(socket : i32) -> io::Result<()> {
+    let enable : libc::c_int = 1;
+
+    let result = unsafe {
+        libc::setsockopt(socket, libc::SOL_PACKET, libc::PACKET_AUXDATA,
+                         &enable as *const libc::c_int as *const libc::c_void,
+                         std::mem::size_of::<libc::c_int>() as libc::socklen_t)
+    };
+
+    match result {
+        -1 => Err(io::Error::last_os_error()),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn enable_auxdata(_socket : i32) -> io::Result<()> {
+    Err(io::Error::from(io::ErrorKind::Unsupported))
+}
+
+// THE FRAME IS READ VLAN_TAG_LENGTH BYTES INTO THE BUFFER, SO THE TAG THE KERNEL TOOK OUT
+// (THE TPID AND THE TCI) CAN BE PUT BACK AFTER THE MAC ADDRESSES, THE SAME AS libpcap DOES
+
+pub fn put_vlan_tag(buffer : &mut [u8], length : usize, tag : Option<(u16, u16)>) -> &[u8] {
+    let frame = VLAN_TAG_LENGTH..VLAN_TAG_LENGTH + length;
+
+    let Some((tpid, tci)) = tag.filter(|_| length >= MAC_ADDRESSES_LENGTH) else {
+        return &buffer[frame];
+    };
+
+    buffer.copy_within(VLAN_TAG_LENGTH..VLAN_TAG_LENGTH + MAC_ADDRESSES_LENGTH, 0);
+    buffer[MAC_ADDRESSES_LENGTH..MAC_ADDRESSES_LENGTH + 2].copy_from_slice(&tpid.to_be_bytes());
+    buffer[MAC_ADDRESSES_LENGTH + 2..MAC_ADDRESSES_LENGTH + 4].copy_from_slice(&tci.to_be_bytes());
+
+    &buffer[..length + VLAN_TAG_LENGTH]
+}
+
+// READING THE FRAME WITH ITS VLAN TAG, INSTEAD OF PNET WHICH ONLY SEES THE DATA,
+// THE WAITING IS THE SAME: NOTHING IN TIME IS THE TIMEOUT ERROR
+// THE FRAME LONGER THAN THE BUFFER IS CUT, BUT ITS LENGTH ON THE WIRE IS RETURNED ASIDE
+
+#[cfg(target_os = "linux")]
+pub fn receive(socket : i32, buffer : &mut [u8], timeout : Duration)
+                                                    -> io::Result<(&[u8], usize)> {
+    let mut poll = libc::pollfd { fd : socket, events : libc::POLLIN, revents : 0 };
+
+    // ONE POLL NEVER WAITS LONGER THAN THE INTERVAL (NOR LESS THAN A MILLISECOND, ZERO WOULD SPIN),
+    // THE LONGER TIMEOUT IS REACHED BY POLLING AGAIN
+    let deadline = Instant::now().checked_add(timeout);
+
+    loop {
+        let remaining = match deadline {
+            Some(deadline) => deadline.saturating_duration_since(Instant::now()),
+            None => POLL_INTERVAL,
+        };
+        let wait = remaining.clamp(Duration::from_millis(1), POLL_INTERVAL);
+        let wait = libc::c_int::try_from(wait.as_millis()).unwrap_or(libc::c_int::MAX);
+
+        match unsafe { libc::poll(&mut poll, 1, wait) } {
+            -1 => return Err(io::Error::last_os_error()),
+            0 if deadline.is_some_and(|deadline| Instant::now() >= deadline) => {
+                return Err(io::Error::from(io::ErrorKind::TimedOut));
+            },
+            0  => (),
+            _  => break,
+        }
+    }
+
+    // THE CONTROL MESSAGES MUST BE ALIGNED AS cmsghdr
+    let mut control = [0u64; 16];
+    let mut data    = libc::iovec { 
+        iov_base : buffer[VLAN_TAG_LENGTH..].as_mut_ptr() as *mut libc::c_void,
+        iov_len  : buffer.len() - VLAN_TAG_LENGTH,
+    };
+
+    let mut message : libc::msghdr = unsafe { std::mem::zeroed() };
+    message.msg_iov        = &mut data;
+    message.msg_iovlen     = 1;
+    message.msg_control    = control.as_mut_ptr() as *mut libc::c_void;
+    message.msg_controllen = std::mem::size_of_val(&control) as _;
+
+    // WITH MSG_TRUNC THE KERNEL TELLS THE WHOLE LENGTH, NOT ONLY WHAT FITTED IN THE BUFFER
+    let mut wire_length = match unsafe { libc::recvmsg(socket, &mut message, libc::MSG_TRUNC) } {
+        -1 => return Err(io::Error::last_os_error()),
+        length => length as usize,
+    };
+    let length = wire_length.min(buffer.len() - VLAN_TAG_LENGTH);
+
+    let mut tag    : Option<(u16, u16)>   = None;
+    let mut header : *mut libc::cmsghdr = unsafe { libc::CMSG_FIRSTHDR(&message) };
+
+    while !header.is_null() {
+        let (level, kind) = unsafe { ((*header).cmsg_level, (*header).cmsg_type) };
+
+        if level == libc::SOL_PACKET && kind == libc::PACKET_AUXDATA {
+            let auxdata = unsafe { 
+                std::ptr::read_unaligned(libc::CMSG_DATA(header) as *const libc::tpacket_auxdata)
+            };
+
+            // THE FRAME CUT BY THE KERNEL FILTER (THE SNAPLEN) KEEPS ITS LENGTH ONLY HERE
+            wire_length = auxdata.tp_len as usize;
+
+            if auxdata.tp_status & libc::TP_STATUS_VLAN_VALID != 0 {
+                let tpid = if auxdata.tp_status & libc::TP_STATUS_VLAN_TPID_VALID != 0 {
+                    auxdata.tp_vlan_tpid
+                } else {
+                    ETHERTYPE_VLAN
+                };
+
+                tag = Some((tpid, auxdata.tp_vlan_tci));
+            }
+        }
+
+        header = unsafe { libc::CMSG_NXTHDR(&message, header) };
+    }
+
+    let frame = put_vlan_tag(buffer, length, tag);
+    let tag_length = frame.len() - length;
+
+    Ok((frame, wire_length + tag_length))
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn receive(_socket : i32, _buffer : &mut [u8], _timeout : Duration)
+                                                    -> io::Result<(&[u8], usize)> {
+    Err(io::Error::from(io::ErrorKind::Unsupported))
+}
+
+// THE FILTER IS ATTACHED BEFORE PNET BINDS THE SOCKET TO THE INTERFACE,
+// SO NOT A SINGLE UNFILTERED FRAME GETS IN
+
+#[cfg(target_os = "linux")]
+fn attach_filter(socket : i32, program : &[Instruction]) -> io::Result<()> {
+    let program = libc::sock_fprog {
+        len    : program.len() as libc::c_ushort,
+        filter : program.as_ptr() as *mut libc::sock_filter,
+    };
+
+    let result = unsafe {
+        libc::setsockopt(socket, libc::SOL_SOCKET, libc::SO_ATTACH_FILTER,
+                         &program as *const libc::sock_fprog as *const libc::c_void,
+                         std::mem::size_of::<libc::sock_fprog>() as libc::socklen_t)
+    };
+
+    match result {
+        -1 => Err(io::Error::last_os_error()),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn attach_filter(_socket : i32, _program : &[Instruction]) -> io::Result<()> {
+    Err(io::Error::from(io::ErrorKind::Unsupported))
+}
+
+// THE RECEIVER AND THE SOCKET BEHIND IT (IF IT IS KNOWN)
+// IF THE KERNEL DOES NOT TAKE THE FILTER, THE PROGRAM FILTERS EVERYTHING ITSELF
+
+pub fn open_channel(interface : &NetworkInterface, options : &ChannelOptions, 
+                    filter : Option<&[Instruction]>)
+                                -> io::Result<(Box<dyn DataLinkReceiver>, Option<i32>)> {
+    let socket = open_socket()?;
+
+    if let Some(socket) = socket {
+        if let Err(e) = enable_auxdata(socket) {
+            print_error();
+            println!("CANNOT ASK THE KERNEL FOR THE VLAN TAGS ON {}: {}", interface.name, e);
+        }
+    }
+
+    if let (Some(socket), Some(filter)) = (socket, filter) {
+        if let Err(e) = attach_filter(socket, filter) {
+            print_error();
+            println!("CANNOT ATTACH THE KERNEL FILTER ON {}: {}", interface.name, e);
+        }
+    }
+
+    let config = datalink::Config { socket_fd : socket, ..options.to_config() };
+
+    match datalink::channel(interface, config)? {
+        Channel::Ethernet(_tx, rx) => Ok((rx, socket)),
+        _ => Err(io::Error::new(io::ErrorKind::Unsupported, "UNHANDLED CHANNEL TYPE")),
+    }
+}
+
+
+
+// TESTS
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fanout_and_snaplen() {
+        let fanout = fanout_from_str("7:lb").unwrap();
+        assert_eq!(fanout.group_id, 7);
+        assert_eq!(fanout.fanout_type, FanoutType::LB);
+
+        assert_eq!(fanout_from_str("7").unwrap().fanout_type, FanoutType::HASH);
+        assert!(fanout_from_str("7:nope").is_none());
+
+        let options = ChannelOptions { snaplen : Some(2), promiscuous : false, 
+                                       ..Default::default() };
+        assert_eq!(options.cut(&[1, 2, 3]), &[1, 2]);
+        assert!(!options.to_config().promiscuous);
+        assert_eq!(options.to_config().read_timeout, Some(POLL_INTERVAL));
+
+        let options = ChannelOptions { read_timeout : Some(Duration::from_secs(30 * 24 * 3600)),
+                                       ..Default::default() };
+        assert_eq!(options.poll_timeout(), POLL_INTERVAL);
+
+        let options = ChannelOptions { read_timeout : Some(Duration::from_millis(50)),
+                                       ..Default::default() };
+        assert_eq!(options.to_config().read_timeout, Some(Duration::from_millis(50)));
+    }
+
+    #[test]
+    fn test_put_vlan_tag() {
+        let frame : Vec<u8> = (1..=14).collect();
+        let mut buffer = [vec![0; VLAN_TAG_LENGTH], frame.clone()].concat();
+
+        assert_eq!(put_vlan_tag(&mut buffer.clone(), 14, None), &frame[..]);
+
+        let tagged = put_vlan_tag(&mut buffer, 14, Some((0x8100, 0x2064)));
+        assert_eq!(&tagged[..12], &frame[..12]);
+        assert_eq!(&tagged[12..16], &[0x81, 0x00, 0x20, 0x64]);
+        assert_eq!(&tagged[16..], &frame[12..]);
+    }
+}
@@ -0,0 +1,99 @@
+// THE FILE MADE FOR STOPPING THE CAPTURE:
+// THE PACKET COUNT, THE DURATION AND THE SIGNALS FROM THE SYSTEM
+// (AND FOR SIGUSR1, WHICH ASKS FOR THE STATISTICS WITHOUT STOPPING)
+
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+
+
+// THE SIGNAL HANDLER CAN ONLY TOUCH THE ATOMICS,
+// SO IT JUST RAISES THE FLAG CHECKED BY EVERY THREAD
+
+static STOP_REQUESTED  : AtomicBool = AtomicBool::new(false);
+static STATS_REQUESTED : AtomicBool = AtomicBool::new(false);
+
+extern "C" fn on_signal(signal : libc::c_int) {
+    if signal == libc::SIGUSR1 {
+        STATS_REQUESTED.store(true, Ordering::SeqCst);
+    } else {
+        STOP_REQUESTED.store(true, Ordering::SeqCst);
+    }
+}
+
+pub fn install_signal_handlers() {
+    let handler = on_signal as extern "C" fn(libc::c_int) as libc::sighandler_t;
+
+    unsafe {
+        libc::signal(libc::SIGINT, handler);
+        libc::signal(libc::SIGTERM, handler);
+        libc::signal(libc::SIGUSR1, handler);
+    }
+}
+
+// TRUE ONCE FOR EVERY SIGUSR1 RECEIVED
+
+pub fn take_stats_request() -> bool {
+    STATS_REQUESTED.swap(false, Ordering::SeqCst)
+}
+
+
+
+// THE CONDITIONS GIVEN BY THE USER AND THE STATE SHARED BY ALL THE THREADS
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct StopConditions {
+    pub count    : Option<usize>,
+    pub duration : Option<Duration>,
+}
+
+pub struct Control {
+    count    : Option<usize>,
+    deadline : Option<Instant>,
+    matched  : AtomicUsize,
+}
+
+impl Control {
+    pub fn new(conditions : StopConditions) -> Control {
+        Control {
+            count    : conditions.count,
+            deadline : conditions.duration.map(|duration| Instant::now() + duration),
+            matched  : AtomicUsize::new(0),
+        }
+    }
+
+    pub fn should_stop(&self) -> bool {
+        STOP_REQUESTED.load(Ordering::SeqCst) ||
+        self.deadline.is_some_and(|deadline| Instant::now() >= deadline) ||
+        self.count.is_some_and(|count| self.matched.load(Ordering::SeqCst) >= count)
+    }
+
+    // EVERY MATCHED PACKET TAKES ITS PLACE IN THE COUNT BEFORE IT IS SHOWN,
+    // SO THE THREADS TOGETHER NEVER SHOW MORE THAN ASKED
+
+    pub fn take_matched(&self) -> bool {
+        let taken = self.matched.fetch_add(1, Ordering::SeqCst);
+
+        self.count.is_none_or(|count| taken < count)
+    }
+}
+
+
+
+// TESTS
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_packet_count() {
+        let control = Control::new(StopConditions { count : Some(2), duration : None });
+
+        assert!(!control.should_stop());
+        assert!(control.take_matched());
+        assert!(control.take_matched());
+        assert!(!control.take_matched());
+        assert!(control.should_stop());
+    }
+}
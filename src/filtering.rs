@@ -1,8 +1,486 @@
+pub mod expression;
+pub mod bpf;
+pub mod services;
+pub mod ssh;
+pub mod payload;
+pub mod fields;
+
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+use pnet::util::MacAddr;
+
 use crate::Parameters;
+use crate::protocols::Protocol;
+use crate::filtering::expression::Expression;
+use crate::filtering::payload::PayloadPattern;
+use crate::rules::Rule;
+
+
+
+// ALL THE FILTERS REQUESTED BY THE USER, SORTED OUT FROM THE PARAMETERS
+// TO BE PASSED ONCE INTO THE LAYER CHECKING FOR EVERY PACKET
+
+#[derive(Clone, Debug, Default)]
+pub struct Filters {
+    pub protocols  : Vec<Protocol>,
+    pub ips        : Vec<IpFilter>,
+    pub ports      : Vec<PortRange>,
+    pub arp_op     : Option<u16>,
+    pub macs       : Vec<MacFilter>,
+    pub ethertypes : Vec<u16>,
+
+    // THE TCP OR UDP DATA MUST CARRY ANY OF THEM
+    pub payload    : Vec<PayloadPattern>,
+
+    // THE EXPRESSION GIVEN WITH -f AND THE FLAGS WHICH ARE JUST ITS SHORTER FORM
+    // (LIKE --src-ip), IT MUST HOLD TOGETHER WITH ALL THE FLAGS ABOVE
+    pub expression : Option<Expression>,
+
+    // LOADED WITH --rules, THE PACKET IS WANTED ONLY IF ANY OF THEM MATCHES
+    pub rules      : Vec<Rule>,
+}
+
+impl Filters {
+    // ADDING ONE MORE CONDITION WHICH MUST HOLD
+
+    pub fn require(&mut self, expression : Expression) {
+        self.expression = Some(match self.expression.take() {
+            Some(current) => Expression::And(Box::new(current), Box::new(expression)),
+            None => expression,
+        });
+    }
+}
+
+// THE ADDRESSES THE IP FILTER ACCEPTS: THE SINGLE HOST, THE SUBNET (10.0.0.0/8)
+// OR THE RANGE (10.0.0.10-10.0.0.50), ALL OF THEM ARE JUST THE RANGES INSIDE
+// IPv4-MAPPED IPv6 ADDRESSES (::ffff:10.0.0.1) ARE THE SAME AS THEIR IPv4 ONES
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum IpFilter {
+    Host(IpAddr),
+    Network(IpAddr, u8),
+    Range(IpAddr, IpAddr),
+}
+
+// THE ADDRESS AS THE NUMBER AND THE AMOUNT OF ITS BITS
+
+fn ip_to_number(ip : IpAddr) -> (u128, u8) {
+    match ip.to_canonical() {
+        IpAddr::V4(ip) => (u128::from(u32::from(ip)), 32),
+        IpAddr::V6(ip) => (u128::from(ip), 128),
+    }
+}
+
+fn number_to_ip(number : u128, bits : u8) -> IpAddr {
+    if bits == 32 {
+        IpAddr::V4(Ipv4Addr::from(number as u32))
+    } else {
+        IpAddr::V6(Ipv6Addr::from(number))
+    }
+}
+
+impl IpFilter {
+    pub fn parse(text : &str) -> Option<IpFilter> {
+        if let Some((address, prefix)) = text.split_once('/') {
+            let address = address.parse::<IpAddr>().ok()?.to_canonical();
+            let prefix  = prefix.parse::<u8>().ok()?;
+
+            if prefix > ip_to_number(address).1 {
+                return None;
+            }
+
+            return Some(IpFilter::Network(address, prefix));
+        }
+
+        if let Some((start, end)) = text.split_once('-') {
+            let start = start.parse::<IpAddr>().ok()?.to_canonical();
+            let end   = end.parse::<IpAddr>().ok()?.to_canonical();
+
+            let ((first, start_bits), (last, end_bits)) = (ip_to_number(start), 
+                                                           ip_to_number(end));
+
+            if start_bits != end_bits || first > last {
+                return None;
+            }
+
+            return Some(IpFilter::Range(start, end));
+        }
+
+        Some(IpFilter::Host(text.parse::<IpAddr>().ok()?.to_canonical()))
+    }
+
+    // THE FIRST AND THE LAST ADDRESS ACCEPTED
+
+    pub fn bounds(&self) -> (IpAddr, IpAddr) {
+        match *self {
+            IpFilter::Host(ip) => (ip, ip),
+            IpFilter::Range(start, end) => (start, end),
+            IpFilter::Network(ip, prefix) => {
+                let (number, bits) = ip_to_number(ip);
+                let host_mask = u128::MAX.checked_shr(u32::from(prefix) + 128 - u32::from(bits))
+                                         .unwrap_or(0);
+
+                (number_to_ip(number & !host_mask, bits), number_to_ip(number | host_mask, bits))
+            },
+        }
+    }
+
+    pub fn contains(&self, ip : IpAddr) -> bool {
+        let (start, end) = self.bounds();
+
+        let (number, bits)      = ip_to_number(ip);
+        let (first, first_bits) = ip_to_number(start);
+        let (last, _)           = ip_to_number(end);
+
+        bits == first_bits && first <= number && number <= last
+    }
+}
+
+// THE MAC ADDRESS OR ONLY ITS BEGINNING, LIKE THE VENDOR PREFIX (OUI): 00:1a:2b:*
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct MacFilter {
+    pub prefix : Vec<u8>,
+}
+
+impl MacFilter {
+    pub fn parse(text : &str) -> Option<MacFilter> {
+        let text  = text.replace('-', ":");
+        let mut parts : Vec<&str> = text.split(':').collect();
+
+        let is_prefix = parts.last() == Some(&"*");
+
+        if is_prefix {
+            parts.pop();
+        }
+
+        if (is_prefix && parts.len() >= 6) || (!is_prefix && parts.len() != 6) {
+            return None;
+        }
+
+        let prefix : Option<Vec<u8>> = parts.iter()
+            .map(|part| (part.len() <= 2).then(|| u8::from_str_radix(part, 16).ok())?)
+            .collect();
+
+        Some(MacFilter { prefix : prefix? })
+    }
+
+    pub fn contains(&self, mac : MacAddr) -> bool {
+        mac.octets().starts_with(&self.prefix)
+    }
+}
+
+// THE ETHERTYPE IN HEX (0x88cc OR 88cc) OR ITS NAME
+
+pub fn parse_ethertype(text : &str) -> Option<u16> {
+    let value = match text.to_lowercase().as_str() {
+        "ip" | "ipv4"     => 0x0800,
+        "arp"             => 0x0806,
+        "wol"             => 0x0842,
+        "rarp"            => 0x8035,
+        "vlan"            => 0x8100,
+        "ip6" | "ipv6"    => 0x86dd,
+        "mpls"            => 0x8847,
+        "pppoe-discovery" => 0x8863,
+        "pppoe"           => 0x8864,
+        "eapol"           => 0x888e,
+        "qinq"            => 0x88a8,
+        "lldp"            => 0x88cc,
+        "macsec"          => 0x88e5,
+        "ptp"             => 0x88f7,
+        hex => return u16::from_str_radix(hex.trim_start_matches("0x"), 16).ok(),
+    };
+
+    Some(value)
+}
+
+// THE VLAN IDS THE FILTER ACCEPTS: THE SINGLE ONE (100) OR THE RANGE (100-199)
+
+pub const MAX_VLAN_ID : u16 = 4095;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct VlanFilter {
+    pub low  : u16,
+    pub high : u16,
+}
+
+impl VlanFilter {
+    pub fn parse(text : &str) -> Option<VlanFilter> {
+        let id = |text : &str| text.parse::<u16>().ok().filter(|id| *id <= MAX_VLAN_ID);
+
+        let filter = match text.split_once('-') {
+            Some((low, high)) => VlanFilter { low : id(low)?, high : id(high)? },
+            None => VlanFilter { low : id(text)?, high : id(text)? },
+        };
+
+        (filter.low <= filter.high).then_some(filter)
+    }
+
+    pub fn contains(&self, id : u16) -> bool {
+        self.low <= id && id <= self.high
+    }
+}
+
+// THE OPERATIONS OF ARP (AND RARP, InARP ON THE SAME HEADER) BY THEIR CODES
+
+pub const ARP_OPERATION_NAMES : [(&str, u16); 10] = [
+    ("request", 1), ("reply", 2), ("rarp-request", 3), ("rarp-reply", 4),
+    ("drarp-request", 5), ("drarp-reply", 6), ("drarp-error", 7),
+    ("inarp-request", 8), ("inarp-reply", 9), ("arp-nak", 10),
+];
+
+pub fn parse_arp_operation(text : &str) -> Option<u16> {
+    ARP_OPERATION_NAMES.iter().find(|(name, _)| name.eq_ignore_ascii_case(text))
+        .map(|(_, code)| *code)
+        .or_else(|| text.parse::<u16>().ok())
+}
+
+pub fn arp_operation_name(code : u16) -> Option<&'static str> {
+    ARP_OPERATION_NAMES.iter().find(|(_, known)| *known == code).map(|(name, _)| *name)
+}
+
+// THE MESSAGES OF ICMP BY THEIR TYPES, AND THE CODES OF THE ONES WHICH HAVE MORE OF THEM
+
+pub const ICMP_TYPE_NAMES : [(&str, u8); 15] = [
+    ("echo-reply", 0), ("unreachable", 3), ("source-quench", 4), ("redirect", 5),
+    ("echo-request", 8), ("router-advertisement", 9), ("router-solicitation", 10),
+    ("time-exceeded", 11), ("parameter-problem", 12),
+    ("timestamp-request", 13), ("timestamp-reply", 14), ("info-request", 15), 
+    ("info-reply", 16), ("mask-request", 17), ("mask-reply", 18),
+];
+
+pub const ICMP_CODE_NAMES : [(&str, u8, u8); 25] = [
+    ("net-unreachable", 3, 0), ("host-unreachable", 3, 1), ("protocol-unreachable", 3, 2),
+    ("port-unreachable", 3, 3), ("fragmentation-needed", 3, 4), ("source-route-failed", 3, 5),
+    ("net-unknown", 3, 6), ("host-unknown", 3, 7), ("source-host-isolated", 3, 8),
+    ("net-prohibited", 3, 9), ("host-prohibited", 3, 10), ("net-tos-unreachable", 3, 11),
+    ("host-tos-unreachable", 3, 12), ("communication-prohibited", 3, 13),
+    ("host-precedence-violation", 3, 14), ("precedence-cutoff", 3, 15),
+    ("net-redirect", 5, 0), ("host-redirect", 5, 1), ("tos-net-redirect", 5, 2),
+    ("tos-host-redirect", 5, 3),
+    ("ttl-exceeded", 11, 0), ("reassembly-exceeded", 11, 1),
+    ("pointer-error", 12, 0), ("missing-option", 12, 1), ("bad-length", 12, 2),
+];
+
+// THE SAME FOR ICMPv6, WHICH ALSO CARRIES THE NEIGHBOR DISCOVERY AND MLD
+
+pub const ICMPV6_TYPE_NAMES : [(&str, u8); 15] = [
+    ("unreachable", 1), ("packet-too-big", 2), ("time-exceeded", 3), ("parameter-problem", 4),
+    ("echo-request", 128), ("echo-reply", 129),
+    ("mld-query", 130), ("mld-report", 131), ("mld-done", 132), ("mld2-report", 143),
+    ("router-solicitation", 133), ("router-advertisement", 134),
+    ("neighbor-solicitation", 135), ("neighbor-advertisement", 136), ("redirect", 137),
+];
+
+pub const ICMPV6_CODE_NAMES : [(&str, u8, u8); 12] = [
+    ("no-route", 1, 0), ("admin-prohibited", 1, 1), ("beyond-scope", 1, 2),
+    ("address-unreachable", 1, 3), ("port-unreachable", 1, 4), 
+    ("source-policy-failed", 1, 5), ("reject-route", 1, 6),
+    ("hop-limit-exceeded", 3, 0), ("reassembly-exceeded", 3, 1),
+    ("erroneous-header", 4, 0), ("unknown-next-header", 4, 1), ("unknown-option", 4, 2),
+];
+
+fn type_name(names : &[(&'static str, u8)], icmp_type : u8) -> Option<&'static str> {
+    names.iter().find(|(_, known)| *known == icmp_type).map(|(name, _)| *name)
+}
+
+fn code_name(names : &[(&'static str, u8, u8)], icmp_type : u8, code : u8) 
+                                                            -> Option<&'static str> {
+    names.iter().find(|(_, known_type, known_code)| {
+        *known_type == icmp_type && *known_code == code
+    }).map(|(name, _, _)| *name)
+}
+
+pub fn icmp_type_name(icmp_type : u8) -> Option<&'static str> {
+    type_name(&ICMP_TYPE_NAMES, icmp_type)
+}
+
+pub fn icmp_code_name(icmp_type : u8, code : u8) -> Option<&'static str> {
+    code_name(&ICMP_CODE_NAMES, icmp_type, code)
+}
+
+pub fn icmpv6_type_name(icmp_type : u8) -> Option<&'static str> {
+    type_name(&ICMPV6_TYPE_NAMES, icmp_type)
+}
+
+pub fn icmpv6_code_name(icmp_type : u8, code : u8) -> Option<&'static str> {
+    code_name(&ICMPV6_CODE_NAMES, icmp_type, code)
+}
+
+// THE ICMP MESSAGES THE FILTER ACCEPTS: THE TYPE (echo-request OR 8), THE TYPE WITH 
+// THE CODE (3/4) OR THE NAME OF THE CODE, WHICH ALSO TELLS THE TYPE (port-unreachable)
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct IcmpFilter {
+    pub icmp_type : u8,
+    pub code      : Option<u8>,
+}
+
+impl IcmpFilter {
+    fn parse_names(text : &str, types : &[(&str, u8)], codes : &[(&str, u8, u8)]) 
+                                                                    -> Option<IcmpFilter> {
+        let icmp_type = |text : &str| types.iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(text)).map(|(_, known)| *known)
+            .or_else(|| text.parse::<u8>().ok());
+
+        if let Some((name, code)) = text.split_once('/') {
+            return Some(IcmpFilter { icmp_type : icmp_type(name)?, 
+                                     code : Some(code.parse::<u8>().ok()?) });
+        }
+
+        if let Some((_, known_type, code)) = codes.iter()
+            .find(|(name, _, _)| name.eq_ignore_ascii_case(text)) {
+            return Some(IcmpFilter { icmp_type : *known_type, code : Some(*code) });
+        }
+
+        Some(IcmpFilter { icmp_type : icmp_type(text)?, code : None })
+    }
+
+    pub fn parse(text : &str) -> Option<IcmpFilter> {
+        IcmpFilter::parse_names(text, &ICMP_TYPE_NAMES, &ICMP_CODE_NAMES)
+    }
+
+    pub fn parse_v6(text : &str) -> Option<IcmpFilter> {
+        IcmpFilter::parse_names(text, &ICMPV6_TYPE_NAMES, &ICMPV6_CODE_NAMES)
+    }
+
+    pub fn matches(&self, icmp_type : u8, code : u8) -> bool {
+        self.icmp_type == icmp_type && self.code.is_none_or(|known| known == code)
+    }
+}
+
+// THE TCP FLAGS WHICH MUST BE SET AND THE ONES WHICH MUST NOT: "syn,!ack"
+
+pub const TCP_FLAG_NAMES : [(&str, u8); 8] = [
+    ("FIN", 0x01), ("SYN", 0x02), ("RST", 0x04), ("PSH", 0x08),
+    ("ACK", 0x10), ("URG", 0x20), ("ECE", 0x40), ("CWR", 0x80),
+];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TcpFlagFilter {
+    pub set   : u8,
+    pub unset : u8,
+}
+
+impl TcpFlagFilter {
+    pub fn parse(text : &str) -> Option<TcpFlagFilter> {
+        let mut filter = TcpFlagFilter { set : 0, unset : 0 };
+
+        for name in text.split(',') {
+            let (negated, name) = match name.strip_prefix('!') {
+                Some(name) => (true, name),
+                None => (false, name),
+            };
+
+            let (_, flag) = TCP_FLAG_NAMES.iter()
+                .find(|(flag_name, _)| flag_name.eq_ignore_ascii_case(name))?;
+
+            if negated { filter.unset |= flag } else { filter.set |= flag }
+        }
+
+        (filter.set & filter.unset == 0).then_some(filter)
+    }
+
+    pub fn matches(&self, flags : u8) -> bool {
+        flags & self.set == self.set && flags & self.unset == 0
+    }
+}
+
+// THE PORTS THE FILTER ACCEPTS: THE SINGLE ONE (443 OR https) OR THE RANGE (8000-8100)
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PortRange {
+    pub low  : u16,
+    pub high : u16,
+}
+
+impl PortRange {
+    pub fn single(port : u16) -> PortRange {
+        PortRange { low : port, high : port }
+    }
+
+    // THE NAMES MAY HAVE THE DASH INSIDE (ftp-data), SO THE RANGE IS ONLY MADE OF NUMBERS
+
+    pub fn parse(text : &str) -> Option<PortRange> {
+        if let Ok(port) = text.parse::<u16>() {
+            return Some(PortRange::single(port));
+        }
+
+        if let Some((low, high)) = text.split_once('-') {
+            if let (Ok(low), Ok(high)) = (low.parse::<u16>(), high.parse::<u16>()) {
+                return (low <= high).then_some(PortRange { low, high });
+            }
+        }
+
+        services::port_by_name(text).map(PortRange::single)
+    }
+
+    // THE COMMA SEPARATED LIST: 80,443,8000-8100
+
+    pub fn parse_list(text : &str) -> Option<Vec<PortRange>> {
+        text.split(',').map(PortRange::parse).collect()
+    }
+
+    pub fn contains(&self, port : u16) -> bool {
+        self.low <= port && port <= self.high
+    }
+}
+
+// THE RANGES SORTED AND THE OVERLAPPING OR TOUCHING ONES JOINED,
+// SO THE PORT CAN BE LOOKED FOR WITH THE BINARY SEARCH
+
+pub fn merge_ports(mut ports : Vec<PortRange>) -> Vec<PortRange> {
+    ports.sort_by_key(|range| range.low);
+
+    let mut merged : Vec<PortRange> = vec![];
+
+    for range in ports {
+        match merged.last_mut() {
+            Some(last) if u32::from(range.low) <= u32::from(last.high) + 1 => {
+                last.high = last.high.max(range.high);
+            },
+            _ => merged.push(range),
+        }
+    }
+
+    merged
+}
+
+fn merged_contain(ports : &[PortRange], port : u16) -> bool {
+    let position = ports.partition_point(|range| range.high < port);
+
+    ports.get(position).is_some_and(|range| range.contains(port))
+}
+
+// WHICH END OF THE CONVERSATION THE ADDRESS OR THE PORT MUST BE ON
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Direction {
+    Any,
+    Source,
+    Destination,
+}
+
+impl Direction {
+    pub fn matches<T>(&self, source : T, dest : T, check : impl Fn(T) -> bool) -> bool {
+        match self {
+            Direction::Any         => check(source) || check(dest),
+            Direction::Source      => check(source),
+            Direction::Destination => check(dest),
+        }
+    }
+}
 
 pub trait NetworkLayer {
     // Getting the source and destination of the packet
-    fn get_source_dest(&self) -> (String, String);
+    fn get_source_dest(&self) -> (IpAddr, IpAddr);
+}
+
+pub fn check_ip_direction<T>(packet : &T, ip : &IpFilter, direction : Direction) -> bool 
+where T: NetworkLayer, {
+    let (source, dest) = packet.get_source_dest();
+
+    direction.matches(source, dest, |address| ip.contains(address))
 }
 
 pub fn check_ips<T>(packet : &T, ips : Parameters) -> bool 
@@ -13,8 +491,7 @@ where T: NetworkLayer, {
                 return true;
             }
 
-            let (source, dest) = packet.get_source_dest();
-            if ips.contains(&source) || ips.contains(&dest) {
+            if ips.iter().any(|ip| check_ip_direction(packet, ip, Direction::Any)) {
                 return true;
             }
         },
@@ -24,6 +501,13 @@ where T: NetworkLayer, {
     false
 }
 
+pub fn check_port_direction(source : u16, dest : u16, ports : &PortRange, 
+                            direction : Direction) -> bool {
+    direction.matches(source, dest, |current| ports.contains(current))
+}
+
+// THE PORTS ARE EXPECTED TO BE MERGED ALREADY (SEE merge_ports)
+
 pub fn check_ports(source : u16, dest : u16, p : Parameters) -> bool {
     match p {
         Parameters::Port(needed_ports) => {
@@ -31,15 +515,22 @@ pub fn check_ports(source : u16, dest : u16, p : Parameters) -> bool {
                 return true;
             }
 
-            needed_ports.iter().any(|port| {
-                *port == source || *port == dest
-            });
+            Direction::Any.matches(source, dest, |port| merged_contain(&needed_ports, port))
         },
-        Parameters::NoParameter => return true,
+        Parameters::NoParameter => true,
         _ => panic!("CODE ERROR: PORTS ARE ONLY ALLOWED IN TRANSPORT LAYER"),
     }
+}
 
-    false
+pub fn check_macs(source : MacAddr, dest : MacAddr, p : Parameters) -> bool {
+    match p {
+        Parameters::Mac(macs) => {
+            macs.is_empty() || 
+            macs.iter().any(|mac| Direction::Any.matches(source, dest, |address| mac.contains(address)))
+        },
+        Parameters::NoParameter => true,
+        _ => panic!("CODE ERROR: MACS ARE ONLY ALLOWED IN NETWORK ACCESS LAYER"),
+    }
 }
 
 pub fn check_arp_operation(current_op_code : u16, p : Parameters) -> bool {
@@ -51,7 +542,164 @@ pub fn check_arp_operation(current_op_code : u16, p : Parameters) -> bool {
 
             current_op_code == op.unwrap()
         },
-        Parameters::NoParameter => return true,
+        Parameters::NoParameter => true,
         _ => panic!("CODE ERROR: ARP OPERATION IS ONLY ALLOWED IN NETWORK ACCESS LAYER"),
     }
 }
+
+
+
+// TESTS
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ip(text : &str) -> IpAddr {
+        text.parse::<IpAddr>().unwrap()
+    }
+
+    #[test]
+    fn test_ip_filters() {
+        let subnet = IpFilter::parse("10.0.0.0/8").unwrap();
+        assert!(subnet.contains(ip("10.200.3.4")));
+        assert!(subnet.contains(ip("::ffff:10.0.0.1")));
+        assert!(!subnet.contains(ip("11.0.0.1")));
+        assert!(!subnet.contains(ip("::a00:1")));
+
+        let range = IpFilter::parse("10.0.0.10-10.0.0.50").unwrap();
+        assert!(range.contains(ip("10.0.0.10")) && range.contains(ip("10.0.0.50")));
+        assert!(!range.contains(ip("10.0.0.51")));
+
+        let ipv6 = IpFilter::parse("2001:db8::/32").unwrap();
+        assert!(ipv6.contains(ip("2001:0db8:0000::1")));
+        assert!(!ipv6.contains(ip("2001:db9::1")));
+        assert_eq!(ipv6.bounds().1, ip("2001:db8:ffff:ffff:ffff:ffff:ffff:ffff"));
+
+        assert_eq!(IpFilter::parse("::ffff:192.168.0.1"), 
+                   Some(IpFilter::Host(ip("192.168.0.1"))));
+        assert_eq!(IpFilter::parse("0.0.0.0/0").unwrap().bounds().1, ip("255.255.255.255"));
+
+        assert!(IpFilter::parse("10.0.0.0/33").is_none());
+        assert!(IpFilter::parse("10.0.0.50-10.0.0.10").is_none());
+        assert!(IpFilter::parse("10.0.0.1-::1").is_none());
+        assert!(IpFilter::parse("10.0.0").is_none());
+    }
+
+    #[test]
+    fn test_mac_filters() {
+        let device = MacAddr::new(0x00, 0x1a, 0x2b, 0x3c, 0x4d, 0x5e);
+
+        assert!(MacFilter::parse("00:1a:2b:*").unwrap().contains(device));
+        assert!(MacFilter::parse("00-1A-2B-3C-4D-5E").unwrap().contains(device));
+        assert!(MacFilter::parse("*").unwrap().contains(device));
+        assert!(!MacFilter::parse("00:1a:2c:*").unwrap().contains(device));
+
+        assert!(MacFilter::parse("00:1a:2b").is_none());
+        assert!(MacFilter::parse("00:1a:2b:3c:4d:5e:*").is_none());
+        assert!(MacFilter::parse("00:1a:2b:3c:4d:5g").is_none());
+        assert!(MacFilter::parse("00:1a:2b:3c:4d:05e").is_none());
+
+        let other = MacAddr::broadcast();
+        assert!(check_macs(other, device, Parameters::Mac(vec![MacFilter::parse("00:1a:*")
+                                                                    .unwrap()])));
+        assert!(!check_macs(other, other, Parameters::Mac(vec![MacFilter::parse("00:1a:*")
+                                                                    .unwrap()])));
+
+        assert_eq!(parse_ethertype("lldp"), Some(0x88cc));
+        assert_eq!(parse_ethertype("0x88CC"), Some(0x88cc));
+        assert_eq!(parse_ethertype("8100"), Some(0x8100));
+        assert_eq!(parse_ethertype("ethernet"), None);
+    }
+
+    #[test]
+    fn test_vlan_filters() {
+        assert_eq!(VlanFilter::parse("100"), Some(VlanFilter { low : 100, high : 100 }));
+        assert_eq!(VlanFilter::parse("100-199"), Some(VlanFilter { low : 100, high : 199 }));
+        assert_eq!(VlanFilter::parse("4096"), None);
+        assert_eq!(VlanFilter::parse("200-100"), None);
+        assert_eq!(VlanFilter::parse("trunk"), None);
+
+        assert!(VlanFilter::parse("100-199").unwrap().contains(150));
+        assert!(!VlanFilter::parse("100").unwrap().contains(101));
+    }
+
+    #[test]
+    fn test_icmp_filters() {
+        assert_eq!(IcmpFilter::parse("echo-request"), 
+                   Some(IcmpFilter { icmp_type : 8, code : None }));
+        assert_eq!(IcmpFilter::parse("11"), Some(IcmpFilter { icmp_type : 11, code : None }));
+        assert_eq!(IcmpFilter::parse("unreachable/4"), 
+                   Some(IcmpFilter { icmp_type : 3, code : Some(4) }));
+        assert_eq!(IcmpFilter::parse("Port-Unreachable"), 
+                   Some(IcmpFilter { icmp_type : 3, code : Some(3) }));
+        assert_eq!(IcmpFilter::parse("ping"), None);
+        assert_eq!(IcmpFilter::parse("3/x"), None);
+        assert_eq!(IcmpFilter::parse("256"), None);
+
+        assert!(IcmpFilter::parse("unreachable").unwrap().matches(3, 13));
+        assert!(!IcmpFilter::parse("port-unreachable").unwrap().matches(3, 1));
+
+        assert_eq!(icmp_type_name(11), Some("time-exceeded"));
+        assert_eq!(icmp_code_name(3, 4), Some("fragmentation-needed"));
+        assert_eq!(icmp_code_name(8, 0), None);
+
+        // THE SAME NAMES MEAN THE OTHER TYPES IN ICMPv6
+        assert_eq!(IcmpFilter::parse_v6("port-unreachable"), 
+                   Some(IcmpFilter { icmp_type : 1, code : Some(4) }));
+        assert_eq!(IcmpFilter::parse_v6("neighbor-solicitation"), 
+                   Some(IcmpFilter { icmp_type : 135, code : None }));
+        assert_eq!(IcmpFilter::parse_v6("source-quench"), None);
+        assert_eq!(icmpv6_type_name(134), Some("router-advertisement"));
+        assert_eq!(icmpv6_code_name(3, 0), Some("hop-limit-exceeded"));
+    }
+
+    #[test]
+    fn test_arp_operations() {
+        assert_eq!(parse_arp_operation("request"), Some(1));
+        assert_eq!(parse_arp_operation("REPLY"), Some(2));
+        assert_eq!(parse_arp_operation("inarp-reply"), Some(9));
+        assert_eq!(parse_arp_operation("25"), Some(25));
+        assert_eq!(parse_arp_operation("ask"), None);
+
+        assert_eq!(arp_operation_name(3), Some("rarp-request"));
+        assert_eq!(arp_operation_name(25), None);
+    }
+
+    #[test]
+    fn test_tcp_flags() {
+        let attempts = TcpFlagFilter::parse("SYN,!ack").unwrap();
+        assert_eq!(attempts, TcpFlagFilter { set : 0x02, unset : 0x10 });
+
+        assert!(attempts.matches(0x02));
+        assert!(!attempts.matches(0x12));
+        assert!(TcpFlagFilter::parse("rst").unwrap().matches(0x14));
+
+        assert!(TcpFlagFilter::parse("syn,!syn").is_none());
+        assert!(TcpFlagFilter::parse("syn,,ack").is_none());
+        assert!(TcpFlagFilter::parse("ns").is_none());
+    }
+
+    #[test]
+    fn test_port_ranges() {
+        assert_eq!(PortRange::parse("8000-8100"), Some(PortRange { low : 8000, high : 8100 }));
+        assert_eq!(PortRange::parse("https"), Some(PortRange::single(443)));
+        assert_eq!(PortRange::parse("ftp-data"), Some(PortRange::single(20)));
+        assert_eq!(PortRange::parse("8100-8000"), None);
+        assert_eq!(PortRange::parse("70000"), None);
+        assert_eq!(PortRange::parse_list("80,https,8000-8100").map(|list| list.len()), Some(3));
+        assert_eq!(PortRange::parse_list("80,,443"), None);
+
+        let ports = merge_ports(PortRange::parse_list("9000-9010,443,8000-8100,8101,80,8050-8060")
+                                    .unwrap());
+        assert_eq!(ports, vec![PortRange::single(80), PortRange::single(443),
+                               PortRange { low : 8000, high : 8101 },
+                               PortRange { low : 9000, high : 9010 }]);
+
+        assert!(check_ports(50000, 8101, Parameters::Port(ports.clone())));
+        assert!(check_ports(443, 50000, Parameters::Port(ports.clone())));
+        assert!(!check_ports(50000, 8102, Parameters::Port(ports.clone())));
+        assert!(!check_ports(81, 8999, Parameters::Port(ports)));
+        assert!(check_ports(81, 8999, Parameters::Port(vec![])));
+    }
+}
@@ -0,0 +1,1005 @@
+// COMPILING THE FILTERS INTO THE CLASSIC BPF PROGRAM FOR THE KERNEL,
+// SO THE UNWANTED FRAMES ARE DROPPED BEFORE THEY ARE COPIED TO THE PROGRAM AT ALL
+// THE KERNEL PROGRAM IS ONLY THE FIRST SIEVE: IT MAY LET MORE THROUGH (THE PARTS
+// IT CANNOT EXPRESS ARE LEFT OUT), BUT NEVER LESS, THE LAYERS STILL CHECK EVERYTHING
+
+use std::net::IpAddr;
+
+use crate::capture;
+use crate::filtering::{Direction, Filters, IcmpFilter, IpFilter, MacFilter, PortRange, TcpFlagFilter};
+use crate::filtering::expression::Expression;
+use crate::filtering::fields::{Field, FieldRange};
+use crate::protocols::Protocol;
+use crate::protocols::ethernet::VLAN_ETHERTYPES;
+use crate::protocols::ipv6::EXTENSION_HEADERS;
+
+// THE PARTS OF THE OPCODES (SEE linux/bpf_common.h)
+
+const BPF_LD   : u16 = 0x00;
+const BPF_LDX  : u16 = 0x01;
+const BPF_ALU  : u16 = 0x04;
+const BPF_JMP  : u16 = 0x05;
+const BPF_RET  : u16 = 0x06;
+
+const BPF_W    : u16 = 0x00;
+const BPF_H    : u16 = 0x08;
+const BPF_B    : u16 = 0x10;
+
+const BPF_ABS  : u16 = 0x20;
+const BPF_IND  : u16 = 0x40;
+const BPF_LEN  : u16 = 0x80;
+const BPF_MSH  : u16 = 0xa0;
+
+const BPF_AND  : u16 = 0x50;
+const BPF_JA   : u16 = 0x00;
+const BPF_JEQ  : u16 = 0x10;
+const BPF_JGT  : u16 = 0x20;
+const BPF_JGE  : u16 = 0x30;
+const BPF_K    : u16 = 0x00;
+
+// THE WHOLE FRAME IS KEPT UNLESS THE SNAPLEN IS GIVEN, THEN THE KERNEL COPIES
+// ONLY THE BEGINNING OF THE FRAME (THE PROGRAM RETURNS HOW MANY BYTES TO KEEP)
+
+const ACCEPT : u32 = u32::MAX;
+const DROP   : u32 = 0;
+
+const ETHERTYPE_IPV4 : u16 = 0x0800;
+const ETHERTYPE_IPV6 : u16 = 0x86dd;
+const ETHERTYPE_ARP  : u16 = 0x0806;
+
+// THE KERNEL KEEPS THE OUTER VLAN TAG ASIDE, THE PROGRAM ASKS FOR IT BY THE SPECIAL OFFSET
+// (SKF_AD_OFF + SKF_AD_VLAN_TAG_PRESENT, SEE linux/filter.h)
+
+const VLAN_TAG_PRESENT : u32 = 0xfffff000 + 48;
+
+
+
+// THE SINGLE INSTRUCTION, THE SAME LAYOUT AS struct sock_filter
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Instruction {
+    pub code : u16,
+    pub jt   : u8,
+    pub jf   : u8,
+    pub k    : u32,
+}
+
+// THE SINGLE COMPARISON OF THE FRAME, THE OFFSETS ARE FROM THE START OF THE FRAME
+
+#[derive(Clone, Debug)]
+enum Check {
+    Byte { offset : u32, mask : u32, value : u32 },
+    Half { offset : u32, value : u32 },
+    Word { offset : u32, value : u32 },
+    ByteRange { offset : u32, mask : u32, low : u32, high : u32 },
+    HalfRange { offset : u32, mask : u32, low : u32, high : u32 },
+    WordRange { offset : u32, low : u32, high : u32 },
+
+    // THE 16 BITS AFTER THE IPv4 HEADER (STARTING AT header), MASKED, INSIDE THE RANGE, 
+    // THE LENGTH OF THE HEADER IS ONLY KNOWN FROM THE FRAME
+    AfterIpv4 { header : u32, offset : u32, mask : u32, low : u32, high : u32 },
+
+    // THE LENGTH OF THE WHOLE FRAME
+    Length { low : u32, high : u32 },
+}
+
+#[derive(Clone, Debug)]
+enum Node {
+    True,
+    False,
+    And(Box<Node>, Box<Node>),
+    Or(Box<Node>, Box<Node>),
+    Not(Box<Node>),
+    Check(Check),
+}
+
+fn and(left : Node, right : Node) -> Node {
+    Node::And(Box::new(left), Box::new(right))
+}
+
+fn or(left : Node, right : Node) -> Node {
+    Node::Or(Box::new(left), Box::new(right))
+}
+
+fn any(nodes : Vec<Node>) -> Node {
+    nodes.into_iter().reduce(or).unwrap_or(Node::False)
+}
+
+
+
+// WHERE THE NETWORK LAYER STARTS AND IF THERE IS THE ETHERNET HEADER BEFORE IT
+
+#[derive(Clone, Copy, Debug)]
+struct Link {
+    network  : u32,
+    ethernet : bool,
+}
+
+impl Link {
+    fn new(link_type : u32) -> Option<Link> {
+        match link_type {
+            capture::LINKTYPE_ETHERNET => Some(Link { network : 14, ethernet : true }),
+            capture::LINKTYPE_RAW      => Some(Link { network : 0,  ethernet : false }),
+            _ => None,
+        }
+    }
+
+    // THE RAW IP HAS NO ETHERTYPE, THE VERSION OF THE PACKET TELLS THE SAME
+
+    fn ethertype(&self, ethertype : u16) -> Node {
+        if self.ethernet {
+            return Node::Check(Check::Half { offset : 12, value : u32::from(ethertype) });
+        }
+
+        match ethertype {
+            ETHERTYPE_IPV4 => Node::Check(Check::Byte { offset : 0, mask : 0xf0, value : 0x40 }),
+            ETHERTYPE_IPV6 => Node::Check(Check::Byte { offset : 0, mask : 0xf0, value : 0x60 }),
+            _ => Node::False,
+        }
+    }
+
+    // THE TAG TAKEN OUT BY THE KERNEL OR THE TAG STILL INSIDE THE FRAME
+
+    fn tagged(&self) -> Node {
+        if !self.ethernet {
+            return Node::False;
+        }
+
+        let mut nodes = vec![Node::Check(Check::Byte { offset : VLAN_TAG_PRESENT, mask : 0xff,
+                                                       value : 1 })];
+
+        nodes.extend(VLAN_ETHERTYPES.iter().map(|tpid| {
+            Node::Check(Check::Half { offset : 12, value : u32::from(*tpid) })
+        }));
+
+        any(nodes)
+    }
+
+    // IPv6 WITH THE EXTENSION HEADERS, THE TRANSPORT HEADER IS SOMEWHERE AFTER THEM
+
+    fn extended_ipv6(&self) -> Node {
+        and(self.ethertype(ETHERTYPE_IPV6), any(EXTENSION_HEADERS.iter()
+            .map(|header| self.byte(6, u32::from(*header))).collect()))
+    }
+
+    fn byte(&self, offset : u32, value : u32) -> Node {
+        Node::Check(Check::Byte { offset : self.network + offset, mask : 0xff, value })
+    }
+
+    fn half(&self, offset : u32, value : u32) -> Node {
+        Node::Check(Check::Half { offset : self.network + offset, value })
+    }
+
+    fn word(&self, offset : u32, value : u32) -> Node {
+        Node::Check(Check::Word { offset : self.network + offset, value })
+    }
+
+    // THE PROTOCOL FIELD OF IPv4 AND THE NEXT HEADER OF IPv6
+
+    fn transport(&self, protocol : u8) -> Node {
+        or(and(self.ethertype(ETHERTYPE_IPV4), self.byte(9, u32::from(protocol))),
+           and(self.ethertype(ETHERTYPE_IPV6), self.byte(6, u32::from(protocol))))
+    }
+
+    fn protocol(&self, protocol : &Protocol) -> Option<Node> {
+        match protocol {
+            Protocol::ETHERNET => Some(if self.ethernet { Node::True } else { Node::False }),
+            Protocol::IPv4     => Some(self.ethertype(ETHERTYPE_IPV4)),
+            Protocol::IPv6     => Some(self.ethertype(ETHERTYPE_IPV6)),
+            Protocol::ARP      => Some(self.ethertype(ETHERTYPE_ARP)),
+            Protocol::TCP      => Some(self.transport(6)),
+            Protocol::UDP      => Some(self.transport(17)),
+            Protocol::ICMP     => Some(self.transport(1)),
+            Protocol::ICMPv6   => Some(self.transport(58)),
+            _ => None,
+        }
+    }
+
+    // THE ADDRESS (4 OR 16 BYTES AT THE OFFSET) INSIDE THE RANGE, WORD BY WORD:
+    // THE SAME WORDS AT THE BEGINNING, THE RANGE IN ONE WORD AND ANYTHING AFTER IT
+    // (SO ANY SUBNET FITS, BUT NOT EVERY RANGE OF IPv6)
+
+    fn address(&self, offset : u32, low : &[u32], high : &[u32]) -> Option<Node> {
+        let mut node = Node::True;
+
+        for (i, (first, last)) in low.iter().zip(high.iter()).enumerate() {
+            let word_offset = offset + 4 * i as u32;
+
+            if first == last {
+                node = and(node, self.word(word_offset, *first));
+                continue;
+            }
+
+            let rest_is_any = low[i + 1..].iter().all(|word| *word == 0) &&
+                              high[i + 1..].iter().all(|word| *word == u32::MAX);
+
+            if !rest_is_any {
+                return None;
+            }
+
+            return Some(and(node, Node::Check(Check::WordRange { 
+                offset : self.network + word_offset, low : *first, high : *last })));
+        }
+
+        Some(node)
+    }
+
+    // THE SOURCE OR THE DESTINATION OF IP, THE SENDER OR THE TARGET OF ARP,
+    // IPv4 ALSO MATCHES ITS MAPPED FORM (::ffff:a.b.c.d) INSIDE IPv6
+
+    fn host(&self, host : &IpFilter, direction : Direction) -> Option<Node> {
+        let words = |ip : IpAddr| -> Vec<u32> {
+            match ip {
+                IpAddr::V4(ip) => vec![u32::from(ip)],
+                IpAddr::V6(ip) => ip.segments().chunks(2)
+                    .map(|pair| (u32::from(pair[0]) << 16) | u32::from(pair[1])).collect(),
+            }
+        };
+
+        let (start, end) = host.bounds();
+        let (low, high)  = (words(start), words(end));
+
+        let either = |source : u32, destination : u32, low : &[u32], high : &[u32]| {
+            match direction {
+                Direction::Any => Some(or(self.address(source, low, high)?, 
+                                          self.address(destination, low, high)?)),
+                Direction::Source      => self.address(source, low, high),
+                Direction::Destination => self.address(destination, low, high),
+            }
+        };
+
+        if start.is_ipv6() {
+            return Some(and(self.ethertype(ETHERTYPE_IPV6), either(8, 24, &low, &high)?));
+        }
+
+        let mapped_low  = [0, 0, 0xffff, low[0]];
+        let mapped_high = [0, 0, 0xffff, high[0]];
+
+        Some(any(vec![
+            and(self.ethertype(ETHERTYPE_IPV4), either(12, 16, &low, &high)?),
+            and(self.ethertype(ETHERTYPE_ARP),  either(14, 24, &low, &high)?),
+            and(self.ethertype(ETHERTYPE_IPV6), either(8, 24, &mapped_low, &mapped_high)?),
+        ]))
+    }
+
+    // THE SENDER (AT 14) OR THE TARGET (AT 24) PROTOCOL ADDRESS OF ARP, ONLY IPv4 IS THERE
+
+    fn arp_host(&self, host : &IpFilter, direction : Direction) -> Option<Node> {
+        let (IpAddr::V4(start), IpAddr::V4(end)) = host.bounds() else {
+            return Some(Node::False);
+        };
+
+        let (low, high) = ([u32::from(start)], [u32::from(end)]);
+        let at = |offset : u32| self.address(offset, &low, &high);
+
+        let node = match direction {
+            Direction::Any         => or(at(14)?, at(24)?),
+            Direction::Source      => at(14)?,
+            Direction::Destination => at(24)?,
+        };
+
+        Some(and(self.ethertype(ETHERTYPE_ARP), node))
+    }
+
+    fn port(&self, port : &PortRange, direction : Direction) -> Node {
+        let (low, high) = (u32::from(port.low), u32::from(port.high));
+        let tcp_or_udp  = |offset : u32| or(self.byte(offset, 6), self.byte(offset, 17));
+
+        let after_ipv4 = |offset : u32| Node::Check(Check::AfterIpv4 { 
+            header : self.network, offset, mask : 0xffff, low, high });
+        let after_ipv6 = |offset : u32| Node::Check(Check::HalfRange { 
+            offset : self.network + 40 + offset, mask : 0xffff, low, high });
+
+        // THE SOURCE PORT FIRST, THE DESTINATION ONE RIGHT AFTER IT
+        let either = |at : &dyn Fn(u32) -> Node| match direction {
+            Direction::Any         => or(at(0), at(2)),
+            Direction::Source      => at(0),
+            Direction::Destination => at(2),
+        };
+
+        let ipv4 = and(and(self.ethertype(ETHERTYPE_IPV4), tcp_or_udp(9)), either(&after_ipv4));
+
+        let ipv6 = and(and(self.ethertype(ETHERTYPE_IPV6), tcp_or_udp(6)),
+                       either(&after_ipv6));
+
+        or(ipv4, ipv6)
+    }
+
+    // THE FLAGS ARE THE 14TH BYTE OF THE TCP HEADER, FOR IPv4 THE HALF BEFORE THEM IS LOADED
+
+    fn tcp_flags(&self, flags : &TcpFlagFilter) -> Node {
+        let mask  = u32::from(flags.set | flags.unset);
+        let value = u32::from(flags.set);
+
+        let ipv4 = and(and(self.ethertype(ETHERTYPE_IPV4), self.byte(9, 6)),
+                       Node::Check(Check::AfterIpv4 { header : self.network, offset : 12, 
+                                                      mask, low : value, high : value }));
+
+        let ipv6 = and(and(self.ethertype(ETHERTYPE_IPV6), self.byte(6, 6)),
+                       Node::Check(Check::Byte { offset : self.network + 40 + 13, mask, value }));
+
+        or(ipv4, ipv6)
+    }
+
+    // THE TYPE IS THE FIRST BYTE OF THE ICMP HEADER, THE CODE IS RIGHT AFTER IT
+
+    fn icmp(&self, filter : &IcmpFilter, version : u16) -> Node {
+        let icmp_type = u32::from(filter.icmp_type) << 8;
+
+        let (mask, value) = match filter.code {
+            Some(code) => (0xffff, icmp_type | u32::from(code)),
+            None => (0xff00, icmp_type),
+        };
+
+        if version == ETHERTYPE_IPV6 {
+            return and(and(self.ethertype(ETHERTYPE_IPV6), self.byte(6, 58)),
+                       Node::Check(Check::HalfRange { offset : self.network + 40, mask, 
+                                                      low : value, high : value }));
+        }
+
+        and(and(self.ethertype(ETHERTYPE_IPV4), self.byte(9, 1)),
+            Node::Check(Check::AfterIpv4 { header : self.network, offset : 0, mask, 
+                                           low : value, high : value }))
+    }
+
+    // THE BITS OF THE MASK INSIDE THE BYTE (OR THE HALF IF THE MASK IS WIDER) OF THE HEADER,
+    // THE RANGE IS SHIFTED TO THE PLACE OF THE BITS
+
+    fn bits(&self, offset : u32, mask : u32, range : (u32, u32)) -> Node {
+        let shift = mask.trailing_zeros();
+        let (low, high) = (range.0 << shift, range.1 << shift);
+        let offset = self.network + offset;
+
+        if mask > 0xff {
+            return Node::Check(Check::HalfRange { offset, mask, low, high });
+        }
+
+        Node::Check(Check::ByteRange { offset, mask, low, high })
+    }
+
+    // THE FIELD OF IPv4 AND THE SAME ONE OF IPv6 (IF IT HAS SUCH), THE RANGE IS CUT 
+    // TO WHAT THE FIELD CAN HOLD FIRST
+
+    fn field(&self, field : Field, range : &FieldRange) -> Node {
+        if range.low > field.max() {
+            return Node::False;
+        }
+
+        let range = (range.low, range.high.min(field.max()));
+
+        let (ipv4, ipv6) = match field {
+            Field::FrameLength    => return Node::Check(Check::Length { low : range.0, 
+                                                                        high : range.1 }),
+            Field::Length         => {
+                let ipv6 = range.1.checked_sub(40).map(|high| {
+                    self.bits(4, 0xffff, (range.0.saturating_sub(40), high))
+                });
+
+                (self.bits(2, 0xffff, (range.0, range.1.min(0xffff))), ipv6)
+            },
+            Field::Ttl            => (self.bits(8, 0xff, range), Some(self.bits(7, 0xff, range))),
+            Field::Dscp           => (self.bits(1, 0xfc, range), Some(self.bits(0, 0x0fc0, range))),
+            Field::Ecn            => (self.bits(1, 0x03, range), Some(self.bits(1, 0x30, range))),
+            Field::DontFragment   => (self.bits(6, 0x40, range), None),
+            Field::MoreFragments  => (self.bits(6, 0x20, range), None),
+            Field::FragmentOffset => (self.bits(6, 0x1fff, range), None),
+        };
+
+        or(and(self.ethertype(ETHERTYPE_IPV4), ipv4),
+           and(self.ethertype(ETHERTYPE_IPV6), ipv6.unwrap_or(Node::False)))
+    }
+
+    // THE BEGINNING OF THE MAC ADDRESS AT THE OFFSET OF THE FRAME,
+    // AS FEW LOADS AS POSSIBLE: THE WORDS FIRST, THEN THE HALF AND THE BYTE
+
+    fn mac_at(&self, start : u32, mac : &MacFilter) -> Node {
+        let mut node = Node::True;
+
+        for (position, chunk) in (0..).step_by(4).zip(mac.prefix.chunks(4)) {
+            let offset = start + position;
+            let value  = chunk.iter().fold(0, |value, byte| (value << 8) | u32::from(*byte));
+
+            node = and(node, match chunk.len() {
+                4 => Node::Check(Check::Word { offset, value }),
+                2 => Node::Check(Check::Half { offset, value }),
+                1 => Node::Check(Check::Byte { offset, mask : 0xff, value }),
+                _ => and(Node::Check(Check::Half { offset, value : value >> 8 }),
+                         Node::Check(Check::Byte { offset : offset + 2, mask : 0xff,
+                                                   value : value & 0xff })),
+            });
+        }
+
+        node
+    }
+
+    // THE DESTINATION (AT 0) OR THE SOURCE (AT 6) MAC ADDRESS OF ETHERNET
+
+    fn mac(&self, mac : &MacFilter) -> Node {
+        if !self.ethernet {
+            return Node::False;
+        }
+
+        or(self.mac_at(0, mac), self.mac_at(6, mac))
+    }
+
+    // THE SENDER (AT 8) OR THE TARGET (AT 18) HARDWARE ADDRESS OF ARP
+
+    fn arp_mac(&self, mac : &MacFilter, direction : Direction) -> Node {
+        let at = |offset : u32| self.mac_at(self.network + offset, mac);
+
+        let node = match direction {
+            Direction::Any         => or(at(8), at(18)),
+            Direction::Source      => at(8),
+            Direction::Destination => at(18),
+        };
+
+        and(self.ethertype(ETHERTYPE_ARP), node)
+    }
+
+    fn expression(&self, expression : &Expression) -> Option<Node> {
+        match expression {
+            Expression::And(left, right) => Some(and(self.expression(left)?,
+                                                     self.expression(right)?)),
+            Expression::Or(left, right)  => Some(or(self.expression(left)?,
+                                                    self.expression(right)?)),
+            Expression::Not(inner)       => Some(Node::Not(Box::new(self.expression(inner)?))),
+
+            Expression::Protocol(protocol) => self.protocol(protocol),
+            Expression::Host(direction, host) => self.host(host, *direction),
+            Expression::Port(direction, port) => Some(self.port(port, *direction)),
+            Expression::TcpFlags(flags)  => Some(self.tcp_flags(flags)),
+            Expression::Field(field, range) => Some(self.field(*field, range)),
+            Expression::ArpOperation(op)   => Some(and(self.ethertype(ETHERTYPE_ARP),
+                                                       self.half(6, u32::from(*op)))),
+            Expression::ArpHost(direction, host) => self.arp_host(host, *direction),
+            Expression::ArpMac(direction, mac)   => Some(self.arp_mac(mac, *direction)),
+
+            // THE TAGGED FRAMES ALL PASS (SEE compile), ONLY THE UNTAGGED ONES ARE LEFT TO DROP
+            Expression::Vlan(_) => Some(self.tagged()),
+            Expression::Icmp(filter)  => Some(self.icmp(filter, ETHERTYPE_IPV4)),
+            Expression::Icmp6(filter) => Some(self.icmp(filter, ETHERTYPE_IPV6)),
+        }
+    }
+}
+
+// THE PARTS OF THE EXPRESSION WHICH MUST ALL HOLD, EACH CAN BE LEFT OUT ON ITS OWN
+
+fn conjuncts(expression : &Expression) -> Vec<&Expression> {
+    match expression {
+        Expression::And(left, right) => [conjuncts(left), conjuncts(right)].concat(),
+        expression => vec![expression],
+    }
+}
+
+// THE FLAGS KEEP THEIR OWN MEANING: ARP PASSES THE IP FLAG,
+// THE ARP OPERATION FLAG DOES NOT DROP ANYTHING ON ITS OWN
+
+fn flags(link : &Link, filters : &Filters) -> Vec<Node> {
+    let mut nodes : Vec<Node> = vec![];
+
+    if !filters.protocols.is_empty() {
+        let protocols : Option<Vec<Node>> = filters.protocols.iter()
+            .map(|protocol| link.protocol(protocol)).collect();
+
+        if let Some(protocols) = protocols {
+            nodes.push(any(protocols));
+        }
+    }
+
+    if !filters.ips.is_empty() {
+        let hosts : Option<Vec<Node>> = filters.ips.iter()
+            .map(|ip| link.host(ip, Direction::Any)).collect();
+
+        if let Some(hosts) = hosts {
+            nodes.push(or(link.ethertype(ETHERTYPE_ARP), any(hosts)));
+        }
+    }
+
+    if !filters.macs.is_empty() {
+        nodes.push(any(filters.macs.iter().map(|mac| link.mac(mac)).collect()));
+    }
+
+    if !filters.ethertypes.is_empty() {
+        nodes.push(any(filters.ethertypes.iter()
+            .map(|ethertype| link.ethertype(*ethertype)).collect()));
+    }
+
+    if !filters.ports.is_empty() {
+        nodes.push(any(filters.ports.iter()
+            .map(|port| link.port(port, Direction::Any)).collect()));
+    }
+
+    nodes
+}
+
+
+
+// TURNING THE TREE INTO THE INSTRUCTIONS, THE JUMPS GO TO THE LABELS
+// WHICH ARE ONLY KNOWN ONCE EVERYTHING IS WRITTEN (THE JUMPS ARE ALWAYS FORWARD)
+
+struct Pending {
+    code : u16,
+    jt   : Option<usize>,
+    jf   : Option<usize>,
+    k    : u32,
+}
+
+#[derive(Default)]
+struct Generator {
+    code   : Vec<Pending>,
+    labels : Vec<usize>,
+}
+
+impl Generator {
+    fn label(&mut self) -> usize {
+        self.labels.push(usize::MAX);
+        self.labels.len() - 1
+    }
+
+    fn place(&mut self, label : usize) {
+        self.labels[label] = self.code.len();
+    }
+
+    fn emit(&mut self, code : u16, k : u32) {
+        self.code.push(Pending { code, jt : None, jf : None, k });
+    }
+
+    fn jump(&mut self, code : u16, k : u32, jt : usize, jf : usize) {
+        self.code.push(Pending { code, jt : Some(jt), jf : Some(jf), k });
+    }
+
+    fn check(&mut self, check : &Check, on_true : usize, on_false : usize) {
+        let value = match *check {
+            Check::Byte { offset, mask, value } => {
+                self.emit(BPF_LD | BPF_B | BPF_ABS, offset);
+                if mask != 0xff {
+                    self.emit(BPF_ALU | BPF_AND | BPF_K, mask);
+                }
+                value
+            },
+            Check::Half { offset, value } => {
+                self.emit(BPF_LD | BPF_H | BPF_ABS, offset);
+                value
+            },
+            Check::Word { offset, value } => {
+                self.emit(BPF_LD | BPF_W | BPF_ABS, offset);
+                value
+            },
+            Check::ByteRange { offset, mask, low, high } => {
+                self.emit(BPF_LD | BPF_B | BPF_ABS, offset);
+                if mask != 0xff {
+                    self.emit(BPF_ALU | BPF_AND | BPF_K, mask);
+                }
+                return self.range(low, high, on_true, on_false);
+            },
+            Check::HalfRange { offset, mask, low, high } => {
+                self.emit(BPF_LD | BPF_H | BPF_ABS, offset);
+                if mask != 0xffff {
+                    self.emit(BPF_ALU | BPF_AND | BPF_K, mask);
+                }
+                return self.range(low, high, on_true, on_false);
+            },
+            Check::Length { low, high } => {
+                self.emit(BPF_LD | BPF_W | BPF_LEN, 0);
+                return self.range(low, high, on_true, on_false);
+            },
+            Check::WordRange { offset, low, high } => {
+                self.emit(BPF_LD | BPF_W | BPF_ABS, offset);
+                return self.range(low, high, on_true, on_false);
+            },
+            Check::AfterIpv4 { header, offset, mask, low, high } => {
+                // X = THE LENGTH OF THE IPv4 HEADER, THE LOWER NIBBLE OF ITS FIRST BYTE * 4
+                self.emit(BPF_LDX | BPF_B | BPF_MSH, header);
+                self.emit(BPF_LD | BPF_H | BPF_IND, header + offset);
+                if mask != 0xffff {
+                    self.emit(BPF_ALU | BPF_AND | BPF_K, mask);
+                }
+                return self.range(low, high, on_true, on_false);
+            },
+        };
+
+        self.jump(BPF_JMP | BPF_JEQ | BPF_K, value, on_true, on_false);
+    }
+
+    // THE LOADED VALUE BETWEEN low AND high, THE SINGLE VALUE IS JUST COMPARED
+
+    fn range(&mut self, low : u32, high : u32, on_true : usize, on_false : usize) {
+        if low == high {
+            return self.jump(BPF_JMP | BPF_JEQ | BPF_K, low, on_true, on_false);
+        }
+
+        let inside = self.label();
+
+        self.jump(BPF_JMP | BPF_JGE | BPF_K, low, inside, on_false);
+        self.place(inside);
+        self.jump(BPF_JMP | BPF_JGT | BPF_K, high, on_false, on_true);
+    }
+
+    fn node(&mut self, node : &Node, on_true : usize, on_false : usize) {
+        match node {
+            Node::True  => self.jump(BPF_JMP | BPF_JA, 0, on_true, on_true),
+            Node::False => self.jump(BPF_JMP | BPF_JA, 0, on_false, on_false),
+            Node::And(left, right) => {
+                let middle = self.label();
+                self.node(left, middle, on_false);
+                self.place(middle);
+                self.node(right, on_true, on_false);
+            },
+            Node::Or(left, right) => {
+                let middle = self.label();
+                self.node(left, on_true, middle);
+                self.place(middle);
+                self.node(right, on_true, on_false);
+            },
+            Node::Not(inner) => self.node(inner, on_false, on_true),
+            Node::Check(check) => self.check(check, on_true, on_false),
+        }
+    }
+
+    // THE CONDITIONAL JUMP CAN ONLY SKIP 255 INSTRUCTIONS,
+    // THE PROGRAM IS GIVEN UP IF IT IS LONGER THAN THAT
+
+    fn finish(self) -> Option<Vec<Instruction>> {
+        let mut program = vec![];
+
+        for (position, pending) in self.code.iter().enumerate() {
+            let distance = |label : Option<usize>| label.map(|label| {
+                self.labels[label] - position - 1
+            }).unwrap_or(0);
+
+            let (jt, jf) = (distance(pending.jt), distance(pending.jf));
+
+            program.push(if pending.code == BPF_JMP | BPF_JA {
+                Instruction { code : pending.code, jt : 0, jf : 0, k : jt as u32 }
+            } else {
+                Instruction { code : pending.code, jt : u8::try_from(jt).ok()?,
+                              jf : u8::try_from(jf).ok()?, k : pending.k }
+            });
+        }
+
+        Some(program)
+    }
+}
+
+fn generate(node : &Node, keep : u32) -> Option<Vec<Instruction>> {
+    let mut generator = Generator::default();
+
+    let (accept, drop) = (generator.label(), generator.label());
+
+    generator.node(node, accept, drop);
+
+    generator.place(accept);
+    generator.emit(BPF_RET | BPF_K, keep);
+    generator.place(drop);
+    generator.emit(BPF_RET | BPF_K, DROP);
+
+    generator.finish()
+}
+
+
+
+// NONE IF THERE IS NOTHING THE KERNEL CAN FILTER FOR THE LINK TYPE AND NO SNAPLEN,
+// WITH THE SNAPLEN ONLY, THE PROGRAM JUST CUTS EVERY FRAME
+// THE OFFSETS ARE THE ONES OF THE UNTAGGED FRAME AND OF IPv6 WITHOUT THE EXTENSION HEADERS,
+// SO EVERY TAGGED FRAME (THE TAGS MAY STILL BE IN THE FRAME OR TAKEN OUT BY THE KERNEL)
+// AND EVERY IPv6 PACKET WITH THE EXTENSION HEADERS IS LET THROUGH, THE LAYERS DECIDE
+
+pub fn compile(filters : &Filters, link_type : u32, snaplen : Option<usize>) 
+                                                            -> Option<Vec<Instruction>> {
+    let keep = snaplen.map_or(ACCEPT, |snaplen| u32::try_from(snaplen).unwrap_or(ACCEPT));
+
+    let Some(node) = filter_node(filters, link_type) else {
+        return snaplen.map(|_| vec![Instruction { code : BPF_RET | BPF_K, jt : 0, jf : 0, 
+                                                  k : keep }]);
+    };
+
+    generate(&node, keep)
+}
+
+fn filter_node(filters : &Filters, link_type : u32) -> Option<Node> {
+    let link = Link::new(link_type)?;
+
+    let mut nodes = flags(&link, filters);
+
+    if let Some(expression) = &filters.expression {
+        nodes.extend(conjuncts(expression).into_iter()
+                                          .filter_map(|conjunct| link.expression(conjunct)));
+    }
+
+    let node = nodes.into_iter().reduce(and)?;
+
+    let passed = match link.ethernet {
+        true  => or(link.tagged(), link.extended_ipv6()),
+        false => link.extended_ipv6(),
+    };
+
+    Some(or(passed, node))
+}
+
+
+
+// TESTS
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filtering::expression;
+
+    // THE LITTLE INTERPRETER OF THE INSTRUCTIONS THE COMPILER USES
+
+    fn run(program : &[Instruction], frame : &[u8]) -> u32 {
+        let (mut a, mut x, mut pc) = (0u32, 0u32, 0usize);
+
+        let load = |offset : usize, size : usize| -> Option<u32> {
+            let bytes = frame.get(offset..offset + size)?;
+            Some(bytes.iter().fold(0, |value, byte| (value << 8) | u32::from(*byte)))
+        };
+
+        loop {
+            let instruction = program[pc];
+            pc += 1;
+
+            let k = instruction.k as usize;
+
+            // THE TEST FRAMES NEVER HAVE THE TAG KEPT ASIDE BY THE KERNEL
+            if instruction.k == VLAN_TAG_PRESENT {
+                a = 0;
+                continue;
+            }
+
+            match instruction.code {
+                code if code == BPF_LD | BPF_B | BPF_ABS => match load(k, 1) {
+                    Some(value) => a = value, None => return 0 },
+                code if code == BPF_LD | BPF_H | BPF_ABS => match load(k, 2) {
+                    Some(value) => a = value, None => return 0 },
+                code if code == BPF_LD | BPF_W | BPF_ABS => match load(k, 4) {
+                    Some(value) => a = value, None => return 0 },
+                code if code == BPF_LD | BPF_H | BPF_IND => match load(x as usize + k, 2) {
+                    Some(value) => a = value, None => return 0 },
+                code if code == BPF_LD | BPF_W | BPF_LEN => a = frame.len() as u32,
+                code if code == BPF_LDX | BPF_B | BPF_MSH => match load(k, 1) {
+                    Some(value) => x = (value & 0xf) * 4, None => return 0 },
+                code if code == BPF_ALU | BPF_AND | BPF_K => a &= instruction.k,
+                code if code == BPF_JMP | BPF_JA => pc += k,
+                code if code == BPF_JMP | BPF_JEQ | BPF_K => {
+                    pc += usize::from(if a == instruction.k { instruction.jt }
+                                      else { instruction.jf });
+                },
+                code if code == BPF_JMP | BPF_JGT | BPF_K => {
+                    pc += usize::from(if a > instruction.k { instruction.jt }
+                                      else { instruction.jf });
+                },
+                code if code == BPF_JMP | BPF_JGE | BPF_K => {
+                    pc += usize::from(if a >= instruction.k { instruction.jt }
+                                      else { instruction.jf });
+                },
+                code if code == BPF_RET | BPF_K => return instruction.k,
+                code => panic!("UNKNOWN INSTRUCTION {:#x}", code),
+            }
+        }
+    }
+
+    const ETHERNET_IPV4 : [u8; 14] = [0, 0, 0, 0, 0, 2, 0, 0, 0, 0, 0, 1, 0x08, 0x00];
+
+    // 10.0.0.1:40000 -> 10.0.0.3:443 OVER TCP, WITH 4 BYTES OF THE IPv4 OPTIONS
+    fn tcp_with_options() -> Vec<u8> {
+        [&ETHERNET_IPV4[..],
+         &[0x46, 0x00, 0x00, 0x2c, 0x00, 0x00, 0x40, 0x00, 0x40, 0x06, 0x00, 0x00,
+           0x0a, 0x00, 0x00, 0x01, 0x0a, 0x00, 0x00, 0x03, 0x01, 0x01, 0x01, 0x00],
+         &[0x9c, 0x40, 0x01, 0xbb, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+           0x50, 0x02, 0xff, 0xff, 0x00, 0x00, 0x00, 0x00]].concat()
+    }
+
+    // ::1:1234 -> ::2:53 OVER UDP, DSCP EF WITH ECN 1, THE HOP LIMIT 1
+    fn ipv6_udp() -> Vec<u8> {
+        let mut addresses = [0u8; 32];
+        addresses[15] = 1;
+        addresses[31] = 2;
+
+        [&[0, 0, 0, 0, 0, 2, 0, 0, 0, 0, 0, 1, 0x86, 0xdd][..],
+         &[0x6b, 0x90, 0x00, 0x00, 0x00, 0x08, 0x11, 0x01], &addresses,
+         &[0x04, 0xd2, 0x00, 0x35, 0x00, 0x08, 0x00, 0x00]].concat()
+    }
+
+    // 10.0.0.2 IS AT 00:1a:2b:3c:4d:5e, TOLD TO 10.0.0.1 AT 02:00:00:00:00:01
+    fn arp_reply() -> Vec<u8> {
+        [&[0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 2, 0x08, 0x06][..],
+         &[0x00, 0x01, 0x08, 0x00, 0x06, 0x04, 0x00, 0x02,
+           0x00, 0x1a, 0x2b, 0x3c, 0x4d, 0x5e, 0x0a, 0x00, 0x00, 0x02,
+           0x02, 0x00, 0x00, 0x00, 0x00, 0x01, 0x0a, 0x00, 0x00, 0x01]].concat()
+    }
+
+    // THE PING FROM 10.0.0.1 TO 10.0.0.2, IDENTIFIER 1, SEQUENCE 1
+    fn echo_request() -> Vec<u8> {
+        [&ETHERNET_IPV4[..],
+         &[0x45, 0x00, 0x00, 0x1c, 0x00, 0x00, 0x40, 0x00, 0x40, 0x01, 0x00, 0x00,
+           0x0a, 0x00, 0x00, 0x01, 0x0a, 0x00, 0x00, 0x02],
+         &[0x08, 0x00, 0xf7, 0xfd, 0x00, 0x01, 0x00, 0x01]].concat()
+    }
+
+    // ::1 -> ::2 WITH THE GIVEN NEXT HEADER AND PAYLOAD
+    fn ipv6_frame(next_header : u8, payload : &[u8]) -> Vec<u8> {
+        let mut addresses = [0u8; 32];
+        addresses[15] = 1;
+        addresses[31] = 2;
+
+        [&[0, 0, 0, 0, 0, 2, 0, 0, 0, 0, 0, 1, 0x86, 0xdd][..],
+         &[0x60, 0x00, 0x00, 0x00, 0x00, payload.len() as u8, next_header, 0xff], &addresses,
+         payload].concat()
+    }
+
+    fn accepts(text : &str, link_type : u32, frame : &[u8]) -> bool {
+        let filters = Filters { expression : Some(expression::parse(text).unwrap()),
+                                ..Default::default() };
+
+        run(&compile(&filters, link_type, None).unwrap(), frame) != 0
+    }
+
+    #[test]
+    fn test_compiled_expressions() {
+        let frame = tcp_with_options();
+        let ethernet = capture::LINKTYPE_ETHERNET;
+
+        assert!(accepts("tcp and port 443", ethernet, &frame));
+        assert!(accepts("host 10.0.0.3 and not udp", ethernet, &frame));
+        assert!(accepts("net 10.0.0.0/30 and host 10.0.0.2-10.0.0.9", ethernet, &frame));
+        assert!(!accepts("net 10.0.0.128/25 or net 2001:db8::/32", ethernet, &frame));
+        assert!(accepts("ether and ip", ethernet, &frame));
+        assert!(!accepts("port 80 or ip6", ethernet, &frame));
+        assert!(accepts("src port 40000 and dst host 10.0.0.3", ethernet, &frame));
+        assert!(!accepts("dst port 40000 or src host 10.0.0.3", ethernet, &frame));
+        assert!(!accepts("not host 10.0.0.1", ethernet, &frame));
+        assert!(!accepts("arp or arp op 2", ethernet, &frame));
+        assert!(accepts("src port 39990-40010 and dst port 400-500", ethernet, &frame));
+        assert!(!accepts("port 1-442 or port 444-39999", ethernet, &frame));
+        assert!(accepts("port http,https", ethernet, &frame));
+        assert!(accepts("tcp[syn] and not tcp[ack]", ethernet, &frame));
+        assert!(!accepts("tcp[rst] or tcp[syn,ack]", ethernet, &frame));
+        assert!(accepts("tcp[syn,!fin]", capture::LINKTYPE_RAW, &frame[14..]));
+
+        // THE TOTAL LENGTH IS 44, TTL 64, DSCP AND ECN 0, ONLY DF IS SET
+        assert!(accepts("len 40-50 and ttl >=64 and dscp be and ecn 0", ethernet, &frame));
+        assert!(accepts("df and not mf and frag 0 and framelen 58", ethernet, &frame));
+        assert!(!accepts("len >1400 or ttl <64 or dscp ef or mf or frag >0", ethernet, &frame));
+        assert!(!accepts("framelen <58 or framelen >58", ethernet, &frame));
+        assert!(accepts("len 44 and framelen 44", capture::LINKTYPE_RAW, &frame[14..]));
+
+        let ipv6 = ipv6_udp();
+        assert!(accepts("len 48 and hoplimit 1 and dscp ef and ecn 1", ethernet, &ipv6));
+        assert!(accepts("not df and not frag 0", ethernet, &ipv6));
+        assert!(!accepts("len <48 or ttl >1 or dscp 0-45 or ecn 2-3", ethernet, &ipv6));
+
+        // THE SAME PACKET WITHOUT THE ETHERNET HEADER
+        assert!(accepts("ip and port 40000", capture::LINKTYPE_RAW, &frame[14..]));
+        assert!(!accepts("ether or arp", capture::LINKTYPE_RAW, &frame[14..]));
+    }
+
+    #[test]
+    fn test_compiled_arp() {
+        let frame = arp_reply();
+        let ethernet = capture::LINKTYPE_ETHERNET;
+
+        assert!(accepts("arp op reply and arp sender host 10.0.0.2", ethernet, &frame));
+        assert!(accepts("arp target net 10.0.0.0/24 and arp sender mac 00:1a:2b:*", 
+                        ethernet, &frame));
+        assert!(accepts("arp target mac 02:00:00:00:00:01", ethernet, &frame));
+        assert!(!accepts("arp sender host 10.0.0.1 or arp target mac 00:1a:2b:*", 
+                         ethernet, &frame));
+        assert!(!accepts("arp sender host ::1 or arp op request", ethernet, &frame));
+        assert!(!accepts("arp sender host 10.0.0.2", capture::LINKTYPE_RAW, &frame[14..]));
+    }
+
+    #[test]
+    fn test_compiled_icmp() {
+        let frame = echo_request();
+        let ethernet = capture::LINKTYPE_ETHERNET;
+
+        assert!(accepts("icmp and icmp type echo-request", ethernet, &frame));
+        assert!(accepts("icmp type 8/0 and not icmp type echo-reply", ethernet, &frame));
+        assert!(!accepts("icmp type unreachable or icmp type 8/1", ethernet, &frame));
+        assert!(!accepts("icmp", ethernet, &tcp_with_options()));
+        assert!(accepts("icmp type echo-request", capture::LINKTYPE_RAW, &frame[14..]));
+    }
+
+    #[test]
+    fn test_compiled_icmp6() {
+        let ethernet = capture::LINKTYPE_ETHERNET;
+
+        // THE NEIGHBOR SOLICITATION, THEN THE MLDv2 REPORT BEHIND THE HOP-BY-HOP OPTIONS
+        let solicitation = ipv6_frame(58, &[0x87, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00]);
+        let report = ipv6_frame(0, &[0x3a, 0x00, 0x05, 0x02, 0x00, 0x00, 0x01, 0x00,
+                                     0x8f, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00]);
+
+        assert!(accepts("icmp6 and icmp6 type neighbor-solicitation", ethernet, &solicitation));
+        assert!(accepts("icmp6 type 135/0 and not icmp", ethernet, &solicitation));
+        assert!(!accepts("icmp6 type router-solicitation or udp", ethernet, &solicitation));
+        assert!(!accepts("icmp6", ethernet, &ipv6_udp()));
+        assert!(accepts("icmp6 type 135", capture::LINKTYPE_RAW, &solicitation[14..]));
+
+        // THE OFFSETS ARE WRONG AFTER THE EXTENSION HEADERS, IT IS LEFT TO THE LAYERS
+        assert!(accepts("icmp6 type mld2-report", ethernet, &report));
+        assert!(accepts("tcp", capture::LINKTYPE_RAW, &report[14..]));
+    }
+
+    #[test]
+    fn test_compiled_vlan() {
+        let ethernet = capture::LINKTYPE_ETHERNET;
+
+        // THE SAME TCP PACKET INSIDE VLAN 100
+        let untagged = tcp_with_options();
+        let tagged = [&untagged[..12], &[0x81, 0x00, 0x00, 0x64], &untagged[12..]].concat();
+
+        assert!(accepts("vlan and tcp", ethernet, &tagged));
+        assert!(!accepts("vlan or udp", ethernet, &untagged));
+        assert!(!accepts("vlan 100", ethernet, &untagged));
+        assert!(accepts("not vlan", ethernet, &untagged));
+
+        // THE OFFSETS ARE WRONG FOR THE TAGGED FRAME, IT IS LEFT TO THE LAYERS
+        assert!(accepts("udp and port 53", ethernet, &tagged));
+        assert!(!accepts("vlan", capture::LINKTYPE_RAW, &untagged[14..]));
+    }
+
+    #[test]
+    fn test_compiled_flags() {
+        let frame = tcp_with_options();
+        let ethernet = capture::LINKTYPE_ETHERNET;
+
+        let filters = Filters { protocols : vec![Protocol::UDP], ..Default::default() };
+        assert_eq!(run(&compile(&filters, ethernet, None).unwrap(), &frame), 0);
+
+        let filters = Filters { ips : vec![IpFilter::parse("10.0.0.0/24").unwrap()], 
+                                ports : vec![PortRange { low : 400, high : 500 }],
+                                ..Default::default() };
+        assert_ne!(run(&compile(&filters, ethernet, None).unwrap(), &frame), 0);
+
+        // THE PORTS ARE THE SAME FOR TCP AND UDP
+        let filters = Filters { ports : vec![PortRange::single(53)], ..Default::default() };
+        assert_eq!(run(&compile(&filters, ethernet, None).unwrap(), &frame), 0);
+
+        let mac = |text : &str| MacFilter::parse(text).unwrap();
+
+        let filters = Filters { macs : vec![mac("00:00:00:00:00:01"), mac("aa:bb:*")],
+                                ethertypes : vec![0x0800], ..Default::default() };
+        assert_ne!(run(&compile(&filters, ethernet, None).unwrap(), &frame), 0);
+
+        for prefix in ["00:*", "00:00:00:*", "00:00:00:00:00:*", "00:00:00:00:00:02"] {
+            let filters = Filters { macs : vec![mac(prefix)], ..Default::default() };
+            assert_ne!(run(&compile(&filters, ethernet, None).unwrap(), &frame), 0);
+        }
+
+        let filters = Filters { macs : vec![mac("00:00:00:00:00:03"), mac("00:00:01:*")],
+                                ..Default::default() };
+        assert_eq!(run(&compile(&filters, ethernet, None).unwrap(), &frame), 0);
+        assert_eq!(run(&compile(&filters, capture::LINKTYPE_RAW, None).unwrap(), &frame[14..]), 0);
+
+        let filters = Filters { ethertypes : vec![0x88cc, 0x0806], ..Default::default() };
+        assert_eq!(run(&compile(&filters, ethernet, None).unwrap(), &frame), 0);
+
+        // NOTHING TO FILTER, OR NOTHING THE KERNEL UNDERSTANDS
+        assert!(compile(&Filters::default(), capture::LINKTYPE_ETHERNET, None).is_none());
+
+        let filters = Filters { expression : expression::parse("host ::1-::2:0:0:1").ok(),
+                                ..Default::default() };
+        assert!(compile(&filters, capture::LINKTYPE_ETHERNET, None).is_none());
+        assert!(compile(&filters, capture::LINKTYPE_LINUX_SLL, None).is_none());
+    }
+
+    #[test]
+    fn test_compiled_snaplen() {
+        let frame = tcp_with_options();
+        let ethernet = capture::LINKTYPE_ETHERNET;
+
+        // THE MATCHED FRAME IS CUT BY THE KERNEL, THE OTHERS ARE STILL DROPPED
+        let filters = Filters { protocols : vec![Protocol::TCP], ..Default::default() };
+        assert_eq!(run(&compile(&filters, ethernet, Some(34)).unwrap(), &frame), 34);
+
+        let filters = Filters { protocols : vec![Protocol::UDP], ..Default::default() };
+        assert_eq!(run(&compile(&filters, ethernet, Some(34)).unwrap(), &frame), 0);
+
+        // NOTHING TO FILTER, THE PROGRAM ONLY CUTS, WHATEVER THE LINK TYPE IS
+        let program = compile(&Filters::default(), capture::LINKTYPE_LINUX_SLL, Some(96)).unwrap();
+        assert_eq!(program.len(), 1);
+        assert_eq!(run(&program, &frame), 96);
+    }
+}
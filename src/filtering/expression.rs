@@ -0,0 +1,499 @@
+// THE FILTER EXPRESSION LANGUAGE, SIMILAR TO THE ONE OF TCPDUMP:
+//     tcp and (port 443 or port 8443) and not host 10.0.0.5
+// THE TEXT IS PARSED ONCE INTO THE TREE, WHICH IS THEN CHECKED
+// AGAINST THE DECODED LAYERS OF EVERY PACKET
+
+use pnet::packet::arp::ArpPacket;
+use pnet::packet::ipv4::Ipv4Packet;
+use pnet::packet::ipv6::Ipv6Packet;
+use pnet::packet::tcp::TcpPacket;
+use pnet::packet::udp::UdpPacket;
+
+use crate::Parameters;
+use crate::filtering::{Direction, IcmpFilter, IpFilter, MacFilter, PortRange, TcpFlagFilter, 
+                       VlanFilter, check_ip_direction, check_port_direction, check_arp_operation, 
+                       parse_arp_operation};
+use crate::filtering::fields::{Field, FieldRange};
+use crate::layers::Layer;
+use crate::protocols::Protocol;
+use crate::protocols::ethernet;
+
+
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Expression {
+    And(Box<Expression>, Box<Expression>),
+    Or(Box<Expression>, Box<Expression>),
+    Not(Box<Expression>),
+
+    // THE PRIMITIVES, THE LEAVES OF THE TREE
+    Protocol(Protocol),
+    Host(Direction, IpFilter),
+    Port(Direction, PortRange),
+    ArpOperation(u16),
+
+    // THE ADDRESSES INSIDE ARP: THE SOURCE IS THE SENDER, THE DESTINATION IS THE TARGET
+    ArpHost(Direction, IpFilter),
+    ArpMac(Direction, MacFilter),
+
+    // THE TYPE (AND THE CODE) OF THE ICMP MESSAGE
+    Icmp(IcmpFilter),
+    Icmp6(IcmpFilter),
+
+    // ANY OF THE VLAN TAGS OF THE FRAME HAS THE ID, NONE MEANS ANY TAGGED FRAME
+    Vlan(Option<VlanFilter>),
+
+    TcpFlags(TcpFlagFilter),
+    Field(Field, FieldRange),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Open,
+    Close,
+    Word(String),
+}
+
+
+
+// SPLITTING THE TEXT INTO THE WORDS AND THE PARENTHESES
+// "&&", "||" AND "!" ARE THE SAME AS "and", "or" AND "not"
+
+fn push_word(word : &mut String, tokens : &mut Vec<Token>) {
+    if !word.is_empty() {
+        tokens.push(Token::Word(std::mem::take(word)));
+    }
+}
+
+fn tokenize(text : &str) -> Vec<Token> {
+    let mut tokens : Vec<Token> = vec![];
+    let mut word   : String     = String::new();
+
+    let text = text.replace("&&", " and ").replace("||", " or ");
+
+    for character in text.chars() {
+        match character {
+            '(' | ')' => {
+                push_word(&mut word, &mut tokens);
+                tokens.push(if character == '(' { Token::Open } else { Token::Close });
+            },
+            '!' if word.is_empty() => tokens.push(Token::Word("not".to_string())),
+            character if character.is_whitespace() => push_word(&mut word, &mut tokens),
+            character => word.push(character),
+        }
+    }
+
+    push_word(&mut word, &mut tokens);
+
+    tokens
+}
+
+
+
+// THE TCP FLAGS ARE WRITTEN IN THE BRACKETS: tcp[syn], tcp[syn,!ack]
+
+fn parse_tcp_flags(word : &str) -> Option<Result<Expression, String>> {
+    let flags = word.strip_prefix("tcp[")?.strip_suffix(']')?;
+
+    Some(TcpFlagFilter::parse(flags).map(Expression::TcpFlags)
+            .ok_or_else(|| format!("INVALID TCP FLAGS '{}'", flags)))
+}
+
+
+
+// THE RECURSIVE DESCENT PARSER, FROM THE WEAKEST OPERATOR TO THE STRONGEST:
+//     or  ->  and  ->  not  ->  (...) OR THE PRIMITIVE
+
+struct Parser {
+    tokens   : Vec<Token>,
+    position : usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    fn next_is(&mut self, word : &str) -> bool {
+        if self.peek() == Some(&Token::Word(word.to_string())) {
+            self.position += 1;
+            return true;
+        }
+
+        false
+    }
+
+    fn value(&mut self, name : &str) -> Result<String, String> {
+        match self.next() {
+            Some(Token::Word(value)) => Ok(value),
+            _ => Err(format!("{} NEEDS A VALUE", name.to_uppercase())),
+        }
+    }
+
+    fn parse_or(&mut self) -> Result<Expression, String> {
+        let mut left = self.parse_and()?;
+
+        while self.next_is("or") {
+            left = Expression::Or(Box::new(left), Box::new(self.parse_and()?));
+        }
+
+        Ok(left)
+    }
+
+    fn parse_and(&mut self) -> Result<Expression, String> {
+        let mut left = self.parse_not()?;
+
+        while self.next_is("and") {
+            left = Expression::And(Box::new(left), Box::new(self.parse_not()?));
+        }
+
+        Ok(left)
+    }
+
+    fn parse_not(&mut self) -> Result<Expression, String> {
+        if self.next_is("not") {
+            return Ok(Expression::Not(Box::new(self.parse_not()?)));
+        }
+
+        self.parse_primary()
+    }
+
+    fn parse_primary(&mut self) -> Result<Expression, String> {
+        match self.next() {
+            Some(Token::Open) => {
+                let inner = self.parse_or()?;
+
+                match self.next() {
+                    Some(Token::Close) => Ok(inner),
+                    _ => Err("MISSING ')'".to_string()),
+                }
+            },
+            Some(Token::Close) => Err("UNEXPECTED ')'".to_string()),
+            Some(Token::Word(word)) => self.parse_primitive(&word),
+            None => Err("UNEXPECTED END OF THE FILTER".to_string()),
+        }
+    }
+
+    fn parse_primitive(&mut self, word : &str) -> Result<Expression, String> {
+        match word {
+            "src" | "dst" => {
+                let direction = if word == "src" { Direction::Source } 
+                                else { Direction::Destination };
+
+                match self.next() {
+                    Some(Token::Word(word)) if matches!(word.as_str(), "host" | "net" | "port") => {
+                        self.parse_address(&word, direction)
+                    },
+                    _ => Err(format!("{} NEEDS HOST, NET OR PORT", word.to_uppercase())),
+                }
+            },
+            "host" | "net" | "port" => self.parse_address(word, Direction::Any),
+            "arp" if self.next_is("op") => {
+                let value = self.value("arp op")?;
+
+                parse_arp_operation(&value).map(Expression::ArpOperation)
+                    .ok_or_else(|| format!("INVALID ARP OPERATION '{}'", value))
+            },
+            "arp" if self.next_is("sender") => self.parse_arp_address("arp sender", 
+                                                                     Direction::Source),
+            "arp" if self.next_is("target") => self.parse_arp_address("arp target", 
+                                                                     Direction::Destination),
+            "icmp" if self.next_is("type") => {
+                let value = self.value("icmp type")?;
+
+                IcmpFilter::parse(&value).map(Expression::Icmp)
+                    .ok_or_else(|| format!("INVALID ICMP TYPE '{}'", value))
+            },
+            "icmp6" if self.next_is("type") => {
+                let value = self.value("icmp6 type")?;
+
+                IcmpFilter::parse_v6(&value).map(Expression::Icmp6)
+                    .ok_or_else(|| format!("INVALID ICMPv6 TYPE '{}'", value))
+            },
+            "vlan" => {
+                let Some(Token::Word(value)) = self.peek().cloned() else {
+                    return Ok(Expression::Vlan(None));
+                };
+
+                if !value.starts_with(|character : char| character.is_ascii_digit()) {
+                    return Ok(Expression::Vlan(None));
+                }
+
+                self.position += 1;
+
+                VlanFilter::parse(&value).map(|vlan| Expression::Vlan(Some(vlan)))
+                    .ok_or_else(|| format!("INVALID VLAN '{}'", value))
+            },
+            word if word.starts_with("tcp[") => parse_tcp_flags(word)
+                .unwrap_or_else(|| Err(format!("UNKNOWN WORD '{}'", word))),
+            word if Field::from_name(word).is_some() => {
+                let field = Field::from_name(word).unwrap();
+
+                if field.is_flag() {
+                    return Ok(Expression::Field(field, FieldRange::single(1)));
+                }
+
+                let value = self.value(word)?;
+
+                FieldRange::parse(&value, field).map(|range| Expression::Field(field, range))
+                    .ok_or_else(|| format!("INVALID {} '{}'", word.to_uppercase(), value))
+            },
+            word => match Protocol::from_name(word) {
+                Some(protocol) => Ok(Expression::Protocol(protocol)),
+                None => Err(format!("UNKNOWN WORD '{}'", word)),
+            },
+        }
+    }
+
+    // THE SUBNETS AND THE RANGES ARE ALSO FINE FOR "host", "net" IS JUST CLEARER,
+    // THE PORT IS WRITTEN THE SAME WAY AS IN -pt (8000-8100, https, 80,443)
+
+    fn parse_address(&mut self, word : &str, direction : Direction) 
+                                                        -> Result<Expression, String> {
+        let value = self.value(word)?;
+
+        if word == "port" {
+            return PortRange::parse_list(&value)
+                .and_then(|ports| any_of(ports.into_iter()
+                    .map(|port| Expression::Port(direction, port)).collect()))
+                .ok_or_else(|| format!("INVALID PORT '{}'", value));
+        }
+
+        IpFilter::parse(&value).map(|ip| Expression::Host(direction, ip))
+            .ok_or_else(|| format!("INVALID {} '{}'", word.to_uppercase(), value))
+    }
+
+    // "arp sender host 10.0.0.1", "arp target net 10.0.0.0/24", "arp sender mac 00:1a:2b:*"
+
+    fn parse_arp_address(&mut self, side : &str, direction : Direction) 
+                                                        -> Result<Expression, String> {
+        let kind = match self.next() {
+            Some(Token::Word(kind)) if matches!(kind.as_str(), "host" | "net" | "mac") => kind,
+            _ => return Err(format!("{} NEEDS HOST, NET OR MAC", side.to_uppercase())),
+        };
+
+        let value = self.value(&format!("{} {}", side, kind))?;
+
+        let expression = match kind.as_str() {
+            "mac" => MacFilter::parse(&value).map(|mac| Expression::ArpMac(direction, mac)),
+            _ => IpFilter::parse(&value).map(|ip| Expression::ArpHost(direction, ip)),
+        };
+
+        expression.ok_or_else(|| format!("INVALID {} '{}'", kind.to_uppercase(), value))
+    }
+}
+
+// THE CONDITION HOLDING IF ANY OF THE GIVEN ONES HOLDS, NONE IF NOTHING IS GIVEN
+
+pub fn any_of(expressions : Vec<Expression>) -> Option<Expression> {
+    expressions.into_iter().reduce(|left, right| Expression::Or(Box::new(left), 
+                                                                 Box::new(right)))
+}
+
+pub fn parse(text : &str) -> Result<Expression, String> {
+    let mut parser = Parser { tokens : tokenize(text), position : 0 };
+
+    if parser.tokens.is_empty() {
+        return Err("THE FILTER IS EMPTY".to_string());
+    }
+
+    let expression = parser.parse_or()?;
+
+    match parser.peek() {
+        None => Ok(expression),
+        Some(Token::Close) => Err("UNEXPECTED ')'".to_string()),
+        Some(Token::Open) => Err("UNEXPECTED '('".to_string()),
+        Some(Token::Word(word)) => Err(format!("UNEXPECTED '{}'", word)),
+    }
+}
+
+
+
+// CHECKING THE TREE AGAINST THE LAYERS OF THE PACKET,
+// THE PRIMITIVE IS FALSE IF THE PACKET HAS NO LAYER IT IS ABOUT
+
+fn layer(layers : &[Layer], protocol : Protocol) -> Option<&Layer> {
+    layers.iter().find(|layer| layer.protocol == protocol)
+}
+
+fn has_host(layers : &[Layer], host : &IpFilter, direction : Direction) -> bool {
+    layer(layers, Protocol::IPv4).and_then(|layer| Ipv4Packet::new(&layer.data))
+        .is_some_and(|packet| check_ip_direction(&packet, host, direction)) ||
+    layer(layers, Protocol::IPv6).and_then(|layer| Ipv6Packet::new(&layer.data))
+        .is_some_and(|packet| check_ip_direction(&packet, host, direction)) ||
+    layer(layers, Protocol::ARP).and_then(|layer| ArpPacket::new(&layer.data))
+        .is_some_and(|packet| check_ip_direction(&packet, host, direction))
+}
+
+fn has_port(layers : &[Layer], port : &PortRange, direction : Direction) -> bool {
+    layer(layers, Protocol::TCP).and_then(|layer| TcpPacket::new(&layer.data))
+        .is_some_and(|packet| check_port_direction(packet.get_source(), 
+                                                   packet.get_destination(), port, direction)) ||
+    layer(layers, Protocol::UDP).and_then(|layer| UdpPacket::new(&layer.data))
+        .is_some_and(|packet| check_port_direction(packet.get_source(), 
+                                                   packet.get_destination(), port, direction))
+}
+
+impl Expression {
+    pub fn matches(&self, layers : &[Layer]) -> bool {
+        match self {
+            Expression::And(left, right) => left.matches(layers) && right.matches(layers),
+            Expression::Or(left, right)  => left.matches(layers) || right.matches(layers),
+            Expression::Not(inner)       => !inner.matches(layers),
+
+            Expression::Protocol(protocol) => layer(layers, protocol.clone()).is_some(),
+            Expression::Host(direction, host) => has_host(layers, host, *direction),
+            Expression::Port(direction, port) => has_port(layers, port, *direction),
+            Expression::Field(field, range) => {
+                field.value(layers).is_some_and(|value| range.contains(value))
+            },
+            Expression::TcpFlags(flags) => {
+                layer(layers, Protocol::TCP).and_then(|layer| TcpPacket::new(&layer.data))
+                    .is_some_and(|packet| flags.matches(packet.get_flags()))
+            },
+            Expression::ArpHost(direction, host) => {
+                layer(layers, Protocol::ARP).and_then(|layer| ArpPacket::new(&layer.data))
+                    .is_some_and(|packet| check_ip_direction(&packet, host, *direction))
+            },
+            Expression::ArpMac(direction, mac) => {
+                layer(layers, Protocol::ARP).and_then(|layer| ArpPacket::new(&layer.data))
+                    .is_some_and(|packet| direction.matches(packet.get_sender_hw_addr(),
+                                                            packet.get_target_hw_addr(),
+                                                            |address| mac.contains(address)))
+            },
+            Expression::Icmp(filter) => layer(layers, Protocol::ICMP)
+                .is_some_and(|layer| layer.data.len() >= 2 && 
+                                     filter.matches(layer.data[0], layer.data[1])),
+            Expression::Icmp6(filter) => layer(layers, Protocol::ICMPv6)
+                .is_some_and(|layer| layer.data.len() >= 2 && 
+                                     filter.matches(layer.data[0], layer.data[1])),
+            Expression::Vlan(vlan) => {
+                let tags = layer(layers, Protocol::ETHERNET)
+                    .and_then(|layer| ethernet::split_vlan_tags(&layer.data))
+                    .map(|(tags, _, _)| tags).unwrap_or_default();
+
+                match vlan {
+                    Some(vlan) => tags.iter().any(|tag| vlan.contains(tag.id)),
+                    None => !tags.is_empty(),
+                }
+            },
+            Expression::ArpOperation(op)   => {
+                layer(layers, Protocol::ARP).and_then(|layer| ArpPacket::new(&layer.data))
+                    .is_some_and(|packet| check_arp_operation(packet.get_operation().0,
+                                                    Parameters::ArpOperation(Some(*op))))
+            },
+        }
+    }
+}
+
+
+
+// TESTS
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let expression = parse("tcp and (port 443 or port 8443) and not host 10.0.0.5")
+            .unwrap();
+
+        assert_eq!(expression, Expression::And(
+            Box::new(Expression::And(
+                Box::new(Expression::Protocol(Protocol::TCP)),
+                Box::new(Expression::Or(Box::new(Expression::Port(Direction::Any, PortRange::single(443))),
+                                        Box::new(Expression::Port(Direction::Any, PortRange::single(8443))))))),
+            Box::new(Expression::Not(Box::new(Expression::Host(Direction::Any,
+                IpFilter::parse("10.0.0.5").unwrap()))))));
+
+        assert_eq!(parse("net 10.0.0.0/8").unwrap(), 
+                   Expression::Host(Direction::Any, IpFilter::parse("10.0.0.0/8").unwrap()));
+
+        assert_eq!(parse("src net 10.0.0.0/8 and dst port 53").unwrap(), Expression::And(
+            Box::new(Expression::Host(Direction::Source, IpFilter::parse("10.0.0.0/8").unwrap())),
+            Box::new(Expression::Port(Direction::Destination, PortRange::single(53)))));
+
+        assert_eq!(parse("!(udp||arp op 2)").unwrap(), Expression::Not(Box::new(
+            Expression::Or(Box::new(Expression::Protocol(Protocol::UDP)),
+                           Box::new(Expression::ArpOperation(2))))));
+
+        assert_eq!(parse("tcp[syn] and not tcp[ack]").unwrap(), Expression::And(
+            Box::new(Expression::TcpFlags(TcpFlagFilter { set : 0x02, unset : 0 })),
+            Box::new(Expression::Not(Box::new(
+                Expression::TcpFlags(TcpFlagFilter { set : 0x10, unset : 0 }))))));
+
+        assert_eq!(parse("ttl <5 or df").unwrap(), Expression::Or(
+            Box::new(Expression::Field(Field::Ttl, FieldRange { low : 0, high : 4 })),
+            Box::new(Expression::Field(Field::DontFragment, FieldRange::single(1)))));
+
+        assert_eq!(parse("arp op rarp-reply").unwrap(), Expression::ArpOperation(4));
+
+        assert_eq!(parse("arp sender host 10.0.0.1 and arp target mac 00:1a:2b:*").unwrap(),
+            Expression::And(
+                Box::new(Expression::ArpHost(Direction::Source, 
+                                             IpFilter::parse("10.0.0.1").unwrap())),
+                Box::new(Expression::ArpMac(Direction::Destination, 
+                                            MacFilter::parse("00:1a:2b:*").unwrap()))));
+
+        assert_eq!(parse("icmp type port-unreachable or icmp").unwrap(), Expression::Or(
+            Box::new(Expression::Icmp(IcmpFilter { icmp_type : 3, code : Some(3) })),
+            Box::new(Expression::Protocol(Protocol::ICMP))));
+
+        assert_eq!(parse("icmp6 type router-advertisement and icmp6").unwrap(), Expression::And(
+            Box::new(Expression::Icmp6(IcmpFilter { icmp_type : 134, code : None })),
+            Box::new(Expression::Protocol(Protocol::ICMPv6))));
+
+        assert_eq!(parse("vlan 100-199 and not vlan").unwrap(), Expression::And(
+            Box::new(Expression::Vlan(Some(VlanFilter { low : 100, high : 199 }))),
+            Box::new(Expression::Not(Box::new(Expression::Vlan(None))))));
+        assert_eq!(parse("vlan and ip").unwrap(), Expression::And(
+            Box::new(Expression::Vlan(None)), Box::new(Expression::Protocol(Protocol::IPv4))));
+
+        // "and" IS STRONGER THAN "or"
+        assert_eq!(parse("arp or tcp and port 22").unwrap(), Expression::Or(
+            Box::new(Expression::Protocol(Protocol::ARP)),
+            Box::new(Expression::And(Box::new(Expression::Protocol(Protocol::TCP)),
+                                     Box::new(Expression::Port(Direction::Any, PortRange::single(22)))))));
+    }
+
+    #[test]
+    fn test_parse_ports() {
+        assert_eq!(parse("dst port 8000-8100").unwrap(), 
+                   Expression::Port(Direction::Destination, PortRange { low : 8000, high : 8100 }));
+
+        assert_eq!(parse("port http,https").unwrap(), Expression::Or(
+            Box::new(Expression::Port(Direction::Any, PortRange::single(80))),
+            Box::new(Expression::Port(Direction::Any, PortRange::single(443)))));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(parse("").is_err());
+        assert!(parse("tcp and").is_err());
+        assert!(parse("(tcp").is_err());
+        assert!(parse("tcp)").is_err());
+        assert!(parse("port no-such-service").is_err());
+        assert!(parse("port 8100-8000").is_err());
+        assert!(parse("host 10.0.0.300").is_err());
+        assert!(parse("tcp udp").is_err());
+        assert!(parse("sctp").is_err());
+        assert!(parse("src tcp").is_err());
+        assert!(parse("tcp[syn").is_err());
+        assert!(parse("len").is_err());
+        assert!(parse("dscp af99").is_err());
+        assert!(parse("tcp[fin,bad]").is_err());
+        assert!(parse("arp op ask").is_err());
+        assert!(parse("arp sender port 53").is_err());
+        assert!(parse("arp target mac 00:1a").is_err());
+        assert!(parse("vlan 4096").is_err());
+        assert!(parse("icmp type ping").is_err());
+        assert!(parse("icmp6 type source-quench").is_err());
+    }
+}
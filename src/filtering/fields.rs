@@ -0,0 +1,216 @@
+// THE NUMERIC FIELDS OF THE HEADERS THE FILTERS CAN LOOK AT: THE LENGTHS, TTL,
+// THE QoS MARKING (DSCP AND ECN) AND THE FRAGMENTATION OF IPv4
+
+use pnet::packet::ipv4::Ipv4Packet;
+use pnet::packet::ipv6::Ipv6Packet;
+
+use crate::layers::Layer;
+use crate::protocols::Protocol;
+
+const IPV6_HEADER_LENGTH : u32 = 40;
+
+// THE FLAGS OF IPv4 AS pnet GIVES THEM (THE 3 BITS)
+
+const IPV4_DONT_FRAGMENT  : u8 = 0b010;
+const IPV4_MORE_FRAGMENTS : u8 = 0b001;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Field {
+    // THE WHOLE FRAME, AS IT WAS CAPTURED
+    FrameLength,
+
+    // THE WHOLE IP PACKET (THE TOTAL LENGTH OF IPv4, THE PAYLOAD LENGTH + 40 OF IPv6)
+    Length,
+
+    // TTL OF IPv4 OR THE HOP LIMIT OF IPv6
+    Ttl,
+
+    Dscp,
+    Ecn,
+
+    // ONLY IPv4, 1 IF THE FLAG IS SET
+    DontFragment,
+    MoreFragments,
+
+    // IN THE UNITS OF 8 BYTES, AS PRINTED
+    FragmentOffset,
+}
+
+impl Field {
+    pub fn from_name(name : &str) -> Option<Field> {
+        match name {
+            "framelen"         => Some(Field::FrameLength),
+            "len" | "length"   => Some(Field::Length),
+            "ttl" | "hoplimit" => Some(Field::Ttl),
+            "dscp"             => Some(Field::Dscp),
+            "ecn"              => Some(Field::Ecn),
+            "df"               => Some(Field::DontFragment),
+            "mf"               => Some(Field::MoreFragments),
+            "frag"             => Some(Field::FragmentOffset),
+            _ => None,
+        }
+    }
+
+    // THE FLAGS ARE WRITTEN ALONE ("df"), THE OTHER FIELDS NEED THE VALUE ("ttl 1-5")
+
+    pub fn is_flag(&self) -> bool {
+        matches!(self, Field::DontFragment | Field::MoreFragments)
+    }
+
+    // THE BIGGEST VALUE THE FIELD CAN HOLD
+
+    pub fn max(&self) -> u32 {
+        match self {
+            Field::FrameLength    => u32::MAX,
+            Field::Length         => 0xffff + IPV6_HEADER_LENGTH,
+            Field::Ttl            => 0xff,
+            Field::Dscp           => 0x3f,
+            Field::Ecn            => 0x03,
+            Field::DontFragment   => 1,
+            Field::MoreFragments  => 1,
+            Field::FragmentOffset => 0x1fff,
+        }
+    }
+
+    pub fn parse_value(&self, text : &str) -> Option<u32> {
+        match self {
+            Field::Dscp => parse_dscp(text),
+            _ => text.parse::<u32>().ok(),
+        }
+    }
+
+    pub fn value(&self, layers : &[Layer]) -> Option<u32> {
+        if *self == Field::FrameLength {
+            return layers.first().map(|layer| layer.data.len() as u32);
+        }
+
+        let find = |protocol : Protocol| layers.iter().find(|layer| layer.protocol == protocol);
+
+        if let Some(packet) = find(Protocol::IPv4).and_then(|layer| Ipv4Packet::new(&layer.data)) {
+            let flag = |flag : u8| u32::from(packet.get_flags() & flag > 0);
+
+            return match self {
+                Field::Length         => Some(u32::from(packet.get_total_length())),
+                Field::Ttl            => Some(u32::from(packet.get_ttl())),
+                Field::Dscp           => Some(u32::from(packet.get_dscp())),
+                Field::Ecn            => Some(u32::from(packet.get_ecn())),
+                Field::DontFragment   => Some(flag(IPV4_DONT_FRAGMENT)),
+                Field::MoreFragments  => Some(flag(IPV4_MORE_FRAGMENTS)),
+                Field::FragmentOffset => Some(u32::from(packet.get_fragment_offset())),
+                Field::FrameLength    => None,
+            };
+        }
+
+        let packet = find(Protocol::IPv6).and_then(|layer| Ipv6Packet::new(&layer.data))?;
+
+        match self {
+            Field::Length => Some(u32::from(packet.get_payload_length()) + IPV6_HEADER_LENGTH),
+            Field::Ttl    => Some(u32::from(packet.get_hop_limit())),
+            Field::Dscp   => Some(u32::from(packet.get_traffic_class() >> 2)),
+            Field::Ecn    => Some(u32::from(packet.get_traffic_class() & 0b11)),
+            _ => None,
+        }
+    }
+}
+
+// THE DSCP NUMBER OR THE NAME OF THE CLASS: be, ef, cs0-cs7, af11-af43
+
+pub fn parse_dscp(text : &str) -> Option<u32> {
+    let text = text.to_lowercase();
+
+    let value = match text.as_str() {
+        "be" | "default" => 0,
+        "ef"             => 46,
+        _ => {
+            if let Some(class) = text.strip_prefix("cs") {
+                class.parse::<u32>().ok().filter(|class| *class <= 7)? * 8
+            }
+            else if let Some(class) = text.strip_prefix("af") {
+                let digits : Vec<u32> = class.chars().map(|digit| digit.to_digit(10))
+                                             .collect::<Option<_>>()?;
+
+                match digits[..] {
+                    [class, drop] if (1..=4).contains(&class) && (1..=3).contains(&drop) => {
+                        class * 8 + drop * 2
+                    },
+                    _ => return None,
+                }
+            }
+            else {
+                text.parse::<u32>().ok()?
+            }
+        },
+    };
+
+    (value < 64).then_some(value)
+}
+
+
+
+// THE VALUES THE FIELD MAY HAVE: 64, 1400-1500, >1400, <=5
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FieldRange {
+    pub low  : u32,
+    pub high : u32,
+}
+
+impl FieldRange {
+    pub fn single(value : u32) -> FieldRange {
+        FieldRange { low : value, high : value }
+    }
+
+    pub fn parse(text : &str, field : Field) -> Option<FieldRange> {
+        let value = |text : &str| field.parse_value(text);
+
+        let range = if let Some(rest) = text.strip_prefix(">=") {
+            FieldRange { low : value(rest)?, high : u32::MAX }
+        } else if let Some(rest) = text.strip_prefix("<=") {
+            FieldRange { low : 0, high : value(rest)? }
+        } else if let Some(rest) = text.strip_prefix('>') {
+            FieldRange { low : value(rest)?.checked_add(1)?, high : u32::MAX }
+        } else if let Some(rest) = text.strip_prefix('<') {
+            FieldRange { low : 0, high : value(rest)?.checked_sub(1)? }
+        } else if let Some(single) = value(text) {
+            FieldRange::single(single)
+        } else {
+            let (low, high) = text.split_once('-')?;
+            FieldRange { low : value(low)?, high : value(high)? }
+        };
+
+        (range.low <= range.high).then_some(range)
+    }
+
+    pub fn contains(&self, value : u32) -> bool {
+        self.low <= value && value <= self.high
+    }
+}
+
+
+
+// TESTS
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_field_ranges() {
+        assert_eq!(FieldRange::parse("1400-1500", Field::Length),
+                   Some(FieldRange { low : 1400, high : 1500 }));
+        assert_eq!(FieldRange::parse(">1400", Field::Length),
+                   Some(FieldRange { low : 1401, high : u32::MAX }));
+        assert_eq!(FieldRange::parse("<=5", Field::Ttl), Some(FieldRange { low : 0, high : 5 }));
+        assert_eq!(FieldRange::parse("<0", Field::Ttl), None);
+        assert_eq!(FieldRange::parse("64-1", Field::Ttl), None);
+        assert_eq!(FieldRange::parse("many", Field::Ttl), None);
+
+        assert_eq!(FieldRange::parse("ef", Field::Dscp), Some(FieldRange::single(46)));
+        assert_eq!(FieldRange::parse("af11-AF43", Field::Dscp),
+                   Some(FieldRange { low : 10, high : 38 }));
+        assert_eq!(parse_dscp("cs6"), Some(48));
+        assert_eq!(parse_dscp("cs8"), None);
+        assert_eq!(parse_dscp("af44"), None);
+        assert_eq!(parse_dscp("64"), None);
+    }
+}
@@ -0,0 +1,110 @@
+// LOOKING INSIDE THE DATA CARRIED BY TCP AND UDP, LIKE ngrep DOES:
+// THE EXACT BYTES (--contains) OR THE REGULAR EXPRESSION (--regex)
+
+use regex::bytes::Regex;
+
+#[derive(Clone, Debug)]
+pub enum PayloadPattern {
+    Bytes(Vec<u8>),
+    Regex(Regex),
+}
+
+// THE HEX IS WRITTEN WITH 0x (0x474554 OR 0x47 45 54), ANYTHING ELSE IS THE TEXT ITSELF
+
+pub fn parse_bytes(text : &str) -> Option<Vec<u8>> {
+    let Some(hex) = text.strip_prefix("0x") else {
+        return (!text.is_empty()).then(|| text.as_bytes().to_vec());
+    };
+
+    let hex : String = hex.chars().filter(|character| !character.is_whitespace()).collect();
+
+    if hex.is_empty() || hex.len() % 2 != 0 || !hex.is_ascii() {
+        return None;
+    }
+
+    (0..hex.len()).step_by(2)
+        .map(|position| u8::from_str_radix(&hex[position..position + 2], 16).ok())
+        .collect()
+}
+
+impl PayloadPattern {
+    // WHERE THE PATTERN IS FOUND, AS THE RANGES OF THE PAYLOAD
+
+    pub fn find_all(&self, payload : &[u8]) -> Vec<(usize, usize)> {
+        match self {
+            PayloadPattern::Bytes(bytes) => {
+                let mut found    : Vec<(usize, usize)> = vec![];
+                let mut position : usize               = 0;
+
+                while let Some(offset) = payload.get(position..)
+                    .and_then(|rest| rest.windows(bytes.len()).position(|window| window == bytes)) {
+                    found.push((position + offset, position + offset + bytes.len()));
+                    position += offset + bytes.len();
+                }
+
+                found
+            },
+            PayloadPattern::Regex(regex) => regex.find_iter(payload)
+                .filter(|found| !found.is_empty())
+                .map(|found| (found.start(), found.end())).collect(),
+        }
+    }
+
+    pub fn is_match(&self, payload : &[u8]) -> bool {
+        match self {
+            PayloadPattern::Bytes(bytes) => payload.windows(bytes.len())
+                                                   .any(|window| window == bytes),
+            PayloadPattern::Regex(regex) => regex.is_match(payload),
+        }
+    }
+}
+
+// THE PARTS OF THE PAYLOAD ANY OF THE PATTERNS FOUND, NONE IF NO PATTERN MATCHES
+
+pub fn find_matches(patterns : &[PayloadPattern], payload : &[u8]) -> Option<Vec<(usize, usize)>> {
+    if !patterns.iter().any(|pattern| pattern.is_match(payload)) {
+        return None;
+    }
+
+    let mut found : Vec<(usize, usize)> = patterns.iter()
+        .flat_map(|pattern| pattern.find_all(payload)).collect();
+
+    found.sort();
+
+    Some(found)
+}
+
+
+
+// TESTS
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_payload_patterns() {
+        assert_eq!(parse_bytes("0x474554"), Some(b"GET".to_vec()));
+        assert_eq!(parse_bytes("0x47 45 54"), Some(b"GET".to_vec()));
+        assert_eq!(parse_bytes("Host:"), Some(b"Host:".to_vec()));
+        assert_eq!(parse_bytes("0x4745f"), None);
+        assert_eq!(parse_bytes("0xzz"), None);
+        assert_eq!(parse_bytes(""), None);
+
+        let payload = b"GET /a HTTP/1.1\r\nHost: x\r\n\r\nGET";
+
+        let bytes = PayloadPattern::Bytes(b"GET".to_vec());
+        assert_eq!(bytes.find_all(payload), vec![(0, 3), (28, 31)]);
+
+        let regex = PayloadPattern::Regex(Regex::new(r"HTTP/1\.[01]").unwrap());
+        assert_eq!(regex.find_all(payload), vec![(7, 15)]);
+
+        assert_eq!(find_matches(&[regex.clone(), bytes], payload).unwrap().len(), 3);
+        assert!(find_matches(&[regex], b"SSH-2.0-OpenSSH").is_none());
+
+        // THE BINARY DATA IS FINE TOO
+        let binary = PayloadPattern::Bytes(vec![0x00, 0xff]);
+        assert!(binary.is_match(&[0x10, 0x00, 0xff, 0x00]));
+        assert!(!PayloadPattern::Bytes(b"long".to_vec()).is_match(b"lo"));
+    }
+}
@@ -0,0 +1,72 @@
+// THE NAMES OF THE PORTS (-pt https dns), FIRST FROM THE TABLE BELOW,
+// WHICH IS THE SAME ON EVERY MACHINE, THEN FROM /etc/services OF THIS ONE
+
+use std::fs;
+
+const SERVICES_FILE : &str = "/etc/services";
+
+// THE WELL KNOWN PORTS FROM THE IANA REGISTRY AND A FEW COMMON NICKNAMES
+
+const SERVICES : [(&str, u16); 47] = [
+    ("ftp-data", 20),       ("ftp", 21),            ("ssh", 22),            ("telnet", 23),
+    ("smtp", 25),           ("domain", 53),         ("dns", 53),            ("bootps", 67),
+    ("dhcp", 67),           ("bootpc", 68),         ("tftp", 69),           ("http", 80),
+    ("www", 80),            ("kerberos", 88),       ("pop3", 110),          ("ntp", 123),
+    ("netbios-ns", 137),    ("netbios-ssn", 139),   ("imap", 143),          ("snmp", 161),
+    ("snmp-trap", 162),     ("bgp", 179),           ("ldap", 389),          ("https", 443),
+    ("microsoft-ds", 445),  ("smb", 445),           ("isakmp", 500),        ("syslog", 514),
+    ("submission", 587),    ("ldaps", 636),         ("imaps", 993),         ("pop3s", 995),
+    ("openvpn", 1194),      ("ms-sql-s", 1433),     ("mqtt", 1883),         ("nfs", 2049),
+    ("mysql", 3306),        ("rdp", 3389),          ("ms-wbt-server", 3389), ("ipsec-nat-t", 4500),
+    ("sip", 5060),          ("postgresql", 5432),   ("amqp", 5672),         ("redis", 6379),
+    ("http-alt", 8080),     ("memcache", 11211),    ("mongodb", 27017),
+];
+
+// THE LINE OF /etc/services LOOKS LIKE "https   443/tcp   # comment",
+// THE ALIASES MAY FOLLOW THE PORT
+
+fn find_in_services(content : &str, name : &str) -> Option<u16> {
+    content.lines()
+        .map(|line| line.split('#').next().unwrap_or(""))
+        .find_map(|line| {
+            let mut words = line.split_whitespace();
+            let service   = words.next()?;
+            let port      = words.next()?.split('/').next()?.parse::<u16>().ok()?;
+
+            (service.eq_ignore_ascii_case(name) ||
+             words.any(|alias| alias.eq_ignore_ascii_case(name))).then_some(port)
+        })
+}
+
+pub fn port_by_name(name : &str) -> Option<u16> {
+    if let Some((_, port)) = SERVICES.iter()
+        .find(|(service, _)| service.eq_ignore_ascii_case(name)) {
+        return Some(*port);
+    }
+
+    find_in_services(&fs::read_to_string(SERVICES_FILE).ok()?, name)
+}
+
+
+
+// TESTS
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_port_by_name() {
+        assert_eq!(port_by_name("https"), Some(443));
+        assert_eq!(port_by_name("DNS"), Some(53));
+        assert_eq!(port_by_name("no-such-service"), None);
+
+        let content = "# Network services\n\
+                       ssh\t\t22/tcp\t\t\t\t# SSH Remote Login Protocol\n\
+                       whois\t\t43/tcp\t\tnicname\n";
+
+        assert_eq!(find_in_services(content, "nicname"), Some(43));
+        assert_eq!(find_in_services(content, "SSH"), Some(22));
+        assert_eq!(find_in_services(content, "Remote"), None);
+    }
+}
@@ -0,0 +1,108 @@
+// FINDING THE SSH SESSION OHREST IS RUN FROM, SO ITS OWN TERMINAL TRAFFIC
+// (AND THE ECHO OF EVERY PRINTED PACKET) CAN BE LEFT OUT OF THE CAPTURE
+
+use std::env;
+use std::fs;
+use std::net::IpAddr;
+
+use crate::filtering::{Direction, IpFilter, PortRange};
+use crate::filtering::expression::Expression;
+
+const SSH_CONNECTION : &str = "SSH_CONNECTION";
+
+// HOW FAR UP THE PARENT PROCESSES THE VARIABLE IS LOOKED FOR
+
+const MAX_PARENTS : usize = 16;
+
+fn parent_of(pid : u32) -> Option<u32> {
+    let stat = fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?;
+
+    // THE NAME IN THE BRACKETS MAY HAVE THE SPACES, THE PARENT IS THE SECOND FIELD AFTER IT
+    stat.rsplit_once(')')?.1.split_whitespace().nth(1)?.parse::<u32>().ok()
+}
+
+fn variable_of(pid : u32) -> Option<String> {
+    let environment = fs::read(format!("/proc/{}/environ", pid)).ok()?;
+    let prefix      = format!("{}=", SSH_CONNECTION);
+
+    environment.split(|byte| *byte == 0)
+        .filter_map(|variable| std::str::from_utf8(variable).ok())
+        .find_map(|variable| variable.strip_prefix(&prefix).map(str::to_string))
+}
+
+// "CLIENT_IP CLIENT_PORT SERVER_IP SERVER_PORT" OF THE CURRENT SESSION,
+// sudo USUALLY CLEARS THE VARIABLE, SO THE SHELLS ABOVE ARE ASKED TOO (LINUX)
+
+pub fn find_connection() -> Option<String> {
+    if let Ok(connection) = env::var(SSH_CONNECTION) {
+        return Some(connection);
+    }
+
+    let mut pid = parent_of(std::process::id())?;
+
+    for _ in 0..MAX_PARENTS {
+        if pid <= 1 {
+            break;
+        }
+
+        if let Some(connection) = variable_of(pid) {
+            return Some(connection);
+        }
+
+        pid = parent_of(pid)?;
+    }
+
+    None
+}
+
+// THE PACKETS OF THE SESSION, IN BOTH DIRECTIONS
+
+pub fn flow_expression(connection : &str) -> Option<Expression> {
+    let parts : Vec<&str> = connection.split_whitespace().collect();
+
+    let [client, client_port, server, server_port] = parts[..] else {
+        return None;
+    };
+
+    let end = |ip : &str, port : &str, direction : Direction| -> Option<Expression> {
+        let ip   = IpFilter::Host(ip.parse::<IpAddr>().ok()?.to_canonical());
+        let port = PortRange::single(port.parse::<u16>().ok()?);
+
+        Some(Expression::And(Box::new(Expression::Host(direction, ip)),
+                             Box::new(Expression::Port(direction, port))))
+    };
+
+    let one_way = |from : Direction, to : Direction| -> Option<Expression> {
+        Some(Expression::And(Box::new(end(client, client_port, from)?),
+                             Box::new(end(server, server_port, to)?)))
+    };
+
+    Some(Expression::Or(Box::new(one_way(Direction::Source, Direction::Destination)?),
+                        Box::new(one_way(Direction::Destination, Direction::Source)?)))
+}
+
+
+
+// TESTS
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_flow_expression() {
+        let flow = flow_expression("192.168.1.20 51234 192.168.1.1 22").unwrap();
+
+        let Expression::Or(there, back) = flow else {
+            panic!("THE FLOW MUST GO BOTH WAYS");
+        };
+
+        assert!(matches!(*there, Expression::And(..)));
+        assert!(matches!(*back, Expression::And(..)));
+
+        assert!(flow_expression("fe80::1 51234 fe80::2 22").is_some());
+        assert!(flow_expression("192.168.1.20 51234 192.168.1.1").is_none());
+        assert!(flow_expression("192.168.1.20 ssh 192.168.1.1 22").is_none());
+        assert!(flow_expression("").is_none());
+    }
+}
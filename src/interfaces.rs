@@ -0,0 +1,160 @@
+// THE FILE MADE FOR CHOOSING THE INTERFACES TO LISTEN ON
+// AND FOR SHOWING THE USER WHICH OF THEM EXIST AT ALL
+
+use std::fs;
+
+use pnet::datalink::{self, NetworkInterface};
+
+use crate::{print_program_name, print_error, get_color};
+use crate::capture;
+
+// THE FLAG IS NOT EXPOSED BY PNET, BUT IT IS THE SAME ON LINUX AND BSD
+
+const IFF_PROMISC : u64 = 0x100;
+
+
+
+// THE SIMPLE SHELL-LIKE PATTERN: "*" IS ANY SEQUENCE, "?" IS ANY SINGLE CHARACTER
+
+pub fn matches_pattern(pattern : &str, name : &str) -> bool {
+    let pattern : Vec<char> = pattern.chars().collect();
+    let name    : Vec<char> = name.chars().collect();
+
+    let (mut p, mut n) = (0, 0);
+
+    // THE POSITION OF THE LAST STAR AND THE NAME POSITION IT STARTED FROM,
+    // TO GO BACK THERE IF THE REST DOES NOT MATCH
+    let mut star : Option<(usize, usize)> = None;
+
+    while n < name.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == name[n]) {
+            p += 1;
+            n += 1;
+        }
+        else if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p, n));
+            p += 1;
+        }
+        else if let Some((star_p, star_n)) = star {
+            p = star_p + 1;
+            n = star_n + 1;
+            star = Some((star_p, star_n + 1));
+        }
+        else {
+            return false;
+        }
+    }
+
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+// EVERY INTERFACE MATCHING ANY OF THE PATTERNS, THE USER IS WARNED
+// ABOUT THE PATTERNS WHICH DID NOT MATCH ANYTHING
+
+pub fn select_interfaces(interfaces : &[NetworkInterface], patterns : &[String])
+                                                            -> Vec<NetworkInterface> {
+    for pattern in patterns {
+        if !interfaces.iter().any(|interface| matches_pattern(pattern, &interface.name)) {
+            print_error();
+            println!("NO INTERFACE MATCHES '{}'", pattern);
+        }
+    }
+
+    interfaces.iter()
+        .filter(|interface| patterns.iter().any(|pattern| matches_pattern(pattern,
+                                                                          &interface.name)))
+        .cloned()
+        .collect()
+}
+
+// THE MTU IS NOT KNOWN TO PNET, LINUX TELLS IT THROUGH SYSFS
+
+fn get_mtu(interface : &NetworkInterface) -> Option<u32> {
+    fs::read_to_string(format!("/sys/class/net/{}/mtu", interface.name)).ok()?
+        .trim().parse::<u32>().ok()
+}
+
+// THE HARDWARE TYPE OF THE INTERFACE (ARPHRD_*) TELLS WHAT THE FRAMES LOOK LIKE:
+// THE LOOPBACK ON LINUX STILL HAS THE ETHERNET HEADER, BUT TUN, WIREGUARD, PPP
+// AND THE IP TUNNELS GIVE THE BARE IP PACKETS
+
+fn link_type_from_hardware_type(hardware_type : u32) -> u32 {
+    match hardware_type {
+        // NONE, PPP, IPIP, IP6IP6, SIT, GRE
+        65534 | 512 | 768 | 769 | 776 | 778 => capture::LINKTYPE_RAW,
+        _ => capture::LINKTYPE_ETHERNET,
+    }
+}
+
+// WITHOUT SYSFS (NOT LINUX) THE INTERFACE IS TREATED AS ETHERNET, AS IT ALWAYS WAS
+
+pub fn get_link_type(interface : &NetworkInterface) -> u32 {
+    fs::read_to_string(format!("/sys/class/net/{}/type", interface.name)).ok()
+        .and_then(|hardware_type| hardware_type.trim().parse::<u32>().ok())
+        .map(link_type_from_hardware_type)
+        .unwrap_or(capture::LINKTYPE_ETHERNET)
+}
+
+pub fn list_interfaces() {
+    print_program_name();
+    println!("{}AVAILABLE INTERFACES{}", get_color(1), get_color(0));
+
+    for interface in datalink::interfaces() {
+        let mut flags : Vec<&str> = vec![];
+
+        if interface.is_up()             { flags.push("UP"); }
+        if interface.is_running()        { flags.push("RUNNING"); }
+        if interface.is_loopback()       { flags.push("LOOPBACK"); }
+        if interface.is_point_to_point() { flags.push("POINT-TO-POINT"); }
+
+        if u64::from(interface.flags) & IFF_PROMISC != 0 { 
+            flags.push("PROMISCUOUS"); 
+        }
+
+        if flags.is_empty() {
+            flags.push("DOWN");
+        }
+
+        let mac = interface.mac.map(|mac| mac.to_string()).unwrap_or("-".to_string());
+        let mtu = get_mtu(&interface).map(|mtu| mtu.to_string()).unwrap_or("-".to_string());
+
+        println!("\n{}#{} {}{}   {}MAC:{} {}   {}MTU:{} {}   {}Flags:{} {}",
+                 get_color(1), interface.index, interface.name, get_color(0),
+                 get_color(4), get_color(0), mac,
+                 get_color(5), get_color(0), mtu,
+                 get_color(2), get_color(0), flags.join(", "));
+
+        for ip in &interface.ips {
+            println!("    {}IP:{} {}", get_color(6), get_color(0), ip);
+        }
+    }
+}
+
+
+
+// TESTS
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_patterns() {
+        assert!(matches_pattern("veth*", "veth1a2b"));
+        assert!(matches_pattern("eth?", "eth0"));
+        assert!(matches_pattern("*0", "wlan0"));
+        assert!(matches_pattern("e*h*1", "eth0eth1"));
+        assert!(matches_pattern("lo", "lo"));
+
+        assert!(!matches_pattern("veth*", "eth0"));
+        assert!(!matches_pattern("eth?", "eth10"));
+        assert!(!matches_pattern("lo", "lo0"));
+    }
+
+    #[test]
+    fn test_link_types() {
+        assert_eq!(link_type_from_hardware_type(1), capture::LINKTYPE_ETHERNET);
+        assert_eq!(link_type_from_hardware_type(772), capture::LINKTYPE_ETHERNET);
+        assert_eq!(link_type_from_hardware_type(65534), capture::LINKTYPE_RAW);
+    }
+}
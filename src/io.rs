@@ -1,7 +1,15 @@
 use crate::{Parameters, print_error};
 use crate::protocols::Protocol;
+use crate::channel::fanout_from_str;
+use crate::filtering::{self, payload, Direction, IcmpFilter, IpFilter, MacFilter, PortRange, 
+                       TcpFlagFilter, VlanFilter};
+use crate::filtering::expression::{self, Expression};
+use crate::filtering::fields::{Field, FieldRange};
+use crate::rules::{self, Rule};
 
-use std::net::IpAddr;
+use std::time::Duration;
+
+use regex::bytes::Regex;
 
 fn incr_and_not_exceed(position : &mut usize, args : &[String]) -> bool {
     *position += 1;
@@ -23,13 +31,9 @@ fn protocols_parse(args : &[String], position : &mut usize) -> Vec<Protocol> {
     }
 
     while !args[*position].starts_with("-") {
-        match args[*position].as_str() {
-            "ipv4" => protocols.push(Protocol::IPv4),
-            "ipv6" => protocols.push(Protocol::IPv6),
-            "tcp"  => protocols.push(Protocol::TCP),
-            "udp"  => protocols.push(Protocol::UDP),
-            "arp"  => protocols.push(Protocol::ARP),
-            _ => {
+        match Protocol::from_name(&args[*position]) {
+            Some(protocol) => protocols.push(protocol),
+            None => {
                 print_error(); 
                 println!("INVALID PROTOCOL '{}'", args[*position]);
             }
@@ -43,8 +47,8 @@ fn protocols_parse(args : &[String], position : &mut usize) -> Vec<Protocol> {
     protocols
 }
 
-fn ip_parse(args : &[String], position : &mut usize) -> Vec<String> {
-    let mut ips : Vec<String> = vec![];
+fn ip_parse(args : &[String], position : &mut usize) -> Vec<IpFilter> {
+    let mut ips : Vec<IpFilter> = vec![];
 
     if !incr_and_not_exceed(position, args) {
         print_error();
@@ -53,8 +57,8 @@ fn ip_parse(args : &[String], position : &mut usize) -> Vec<String> {
     }
 
     while !args[*position].starts_with("-") {
-        if let Ok(ip) = args[*position].parse::<IpAddr>() {
-            ips.push(ip.to_string());
+        if let Some(ip) = IpFilter::parse(&args[*position]) {
+            ips.push(ip);
         }
         else {
             print_error();
@@ -69,8 +73,31 @@ fn ip_parse(args : &[String], position : &mut usize) -> Vec<String> {
     ips
 }
 
-fn port_parse(args : &[String], position : &mut usize) -> Vec<u16> {
-    let mut ports : Vec<u16> = vec![];
+fn interface_parse(args : &[String], position : &mut usize) -> Vec<String> {
+    let mut names : Vec<String> = vec![];
+
+    if !incr_and_not_exceed(position, args) {
+        print_error();
+        println!("NO INTERFACES SPECIFIED");
+        return names;
+    }
+
+    while !args[*position].starts_with("-") {
+        names.push(args[*position].clone());
+
+        if !incr_and_not_exceed(position, args) {
+            return names;
+        }
+    }
+
+    names
+}
+
+// EVERY ARGUMENT IS THE PORT, THE RANGE, THE SERVICE NAME OR THE COMMA LIST OF THEM:
+//     -pt 80 8000-8100 https,dns
+
+fn port_parse(args : &[String], position : &mut usize) -> Vec<PortRange> {
+    let mut ports : Vec<PortRange> = vec![];
 
     if !incr_and_not_exceed(position, args) {
         print_error();
@@ -79,12 +106,8 @@ fn port_parse(args : &[String], position : &mut usize) -> Vec<u16> {
     }
 
     while !args[*position].starts_with("-") {
-        if let Ok(port) = args[*position].parse::<u16>() {
-            ports.push(port);
-
-            if !incr_and_not_exceed(position, args) {
-                return ports;
-            }
+        if let Some(ranges) = PortRange::parse_list(&args[*position]) {
+            ports.extend(ranges);
         }
         else {
             print_error();
@@ -106,7 +129,7 @@ fn arp_op_parse(args : &[String], position : &mut usize) -> Option<u16> {
         return None;
     }
 
-    if let Ok(op) = args[*position].parse::<u16>() {
+    if let Some(op) = filtering::parse_arp_operation(&args[*position]) {
         return Some(op);
     }
     
@@ -115,6 +138,183 @@ fn arp_op_parse(args : &[String], position : &mut usize) -> Option<u16> {
     None
 }
 
+fn file_parse(args : &[String], position : &mut usize) -> Option<String> {
+    if !incr_and_not_exceed(position, args) || args[*position].starts_with("-") {
+        print_error();
+        println!("NO FILE SPECIFIED");
+        return None;
+    }
+
+    Some(args[*position].clone())
+}
+
+// SIZES LIKE "100M" OR "512K", THE SUFFIXES ARE THE POWERS OF 1024
+
+pub fn size_from_str(value : &str) -> Option<u64> {
+    let value = value.trim_end_matches(['B', 'b']);
+
+    let (number, multiplier) = match value.chars().last()?.to_ascii_uppercase() {
+        'K' => (&value[..value.len() - 1], 1 << 10),
+        'M' => (&value[..value.len() - 1], 1 << 20),
+        'G' => (&value[..value.len() - 1], 1 << 30),
+        _   => (value, 1),
+    };
+
+    number.parse::<u64>().ok()?.checked_mul(multiplier)
+}
+
+// DURATIONS LIKE "500ms", "30s", "15m", "1h" OR "2d", THE PLAIN NUMBER MEANS SECONDS
+
+pub fn duration_from_str(value : &str) -> Option<Duration> {
+    if let Some(millis) = value.strip_suffix("ms") {
+        return Some(Duration::from_millis(millis.parse::<u64>().ok()?));
+    }
+
+    let (number, multiplier) = match value.chars().last()? {
+        's' => (&value[..value.len() - 1], 1),
+        'm' => (&value[..value.len() - 1], 60),
+        'h' => (&value[..value.len() - 1], 3600),
+        'd' => (&value[..value.len() - 1], 86_400),
+        _   => (value, 1),
+    };
+
+    Some(Duration::from_secs(number.parse::<u64>().ok()?.checked_mul(multiplier)?))
+}
+
+// THE WHOLE FILTER EXPRESSION IS A SINGLE ARGUMENT (IN QUOTES),
+// THE USER IS TOLD WHAT EXACTLY IS WRONG WITH IT
+
+fn filter_parse(args : &[String], position : &mut usize) -> Option<Expression> {
+    if !incr_and_not_exceed(position, args) {
+        print_error();
+        println!("NO FILTER SPECIFIED");
+        return None;
+    }
+
+    match expression::parse(&args[*position]) {
+        Ok(expression) => Some(expression),
+        Err(reason) => {
+            print_error();
+            println!("INVALID FILTER '{}': {}", args[*position], reason);
+            None
+        }
+    }
+}
+
+// THE RULES FILE IS READ RIGHT AWAY, SO THE MISTAKES ARE SHOWN BEFORE THE CAPTURE
+
+fn rules_parse(args : &[String], position : &mut usize) -> Vec<Rule> {
+    let Some(path) = file_parse(args, position) else {
+        return vec![];
+    };
+
+    match rules::load_rules(&path) {
+        Ok(rules) => rules,
+        Err(reason) => {
+            print_error();
+            println!("INVALID RULES FILE '{}': {}", path, reason);
+            vec![]
+        }
+    }
+}
+
+// THE VALUES OF THE HEADER FIELD: --ttl 1-5 64, --dscp ef af41
+
+fn field_parse(args : &[String], position : &mut usize, field : Field) -> Parameters {
+    let mut ranges : Vec<FieldRange> = vec![];
+    let name = args[*position].trim_start_matches('-').to_uppercase();
+
+    if !incr_and_not_exceed(position, args) {
+        print_error();
+        println!("NO {} SPECIFIED", name);
+        return Parameters::HeaderField(field, ranges);
+    }
+
+    // "<64" DOES NOT START WITH "-", THE NEGATIVE NUMBERS ARE NOT NEEDED HERE
+    while !args[*position].starts_with("-") {
+        match FieldRange::parse(&args[*position], field) {
+            Some(range) => ranges.push(range),
+            None => {
+                print_error();
+                println!("INVALID {} '{}'", name, args[*position]);
+            }
+        }
+
+        if !incr_and_not_exceed(position, args) {
+            break;
+        }
+    }
+
+    Parameters::HeaderField(field, ranges)
+}
+
+// THE PATTERN IS THE SINGLE ARGUMENT (IN QUOTES), IT MAY START WITH "-" AS WELL
+
+fn regex_parse(args : &[String], position : &mut usize) -> Option<Regex> {
+    if !incr_and_not_exceed(position, args) {
+        print_error();
+        println!("NO REGULAR EXPRESSION SPECIFIED");
+        return None;
+    }
+
+    match Regex::new(&args[*position]) {
+        Ok(regex) => Some(regex),
+        Err(reason) => {
+            print_error();
+            println!("INVALID REGULAR EXPRESSION '{}': {}", args[*position], reason);
+            None
+        }
+    }
+}
+
+// THE SINGLE VALUE AFTER THE ARGUMENT, PARSED BY THE GIVEN FUNCTION
+
+fn value_parse<T>(args : &[String], position : &mut usize, name : &str, 
+                  parse : fn(&str) -> Option<T>) -> Option<T> {
+    if !incr_and_not_exceed(position, args) {
+        print_error();
+        println!("NO {} SPECIFIED", name);
+        return None;
+    }
+
+    if let Some(value) = parse(&args[*position]) {
+        return Some(value);
+    }
+
+    print_error();
+    println!("INVALID {} '{}'", name, args[*position]);
+    None
+}
+
+// ALL THE VALUES AFTER THE ARGUMENT, EVERY ONE PARSED BY THE GIVEN FUNCTION
+
+fn list_parse<T>(args : &[String], position : &mut usize, name : &str, 
+                 parse : fn(&str) -> Option<T>) -> Vec<T> {
+    let mut values : Vec<T> = vec![];
+
+    if !incr_and_not_exceed(position, args) {
+        print_error();
+        println!("NO {} SPECIFIED", name);
+        return values;
+    }
+
+    while !args[*position].starts_with("-") {
+        match parse(&args[*position]) {
+            Some(value) => values.push(value),
+            None => {
+                print_error();
+                println!("INVALID {} '{}'", name, args[*position]);
+            }
+        }
+
+        if !incr_and_not_exceed(position, args) {
+            return values;
+        }
+    }
+
+    values
+}
+
 pub fn interpret_parameters(args : &[String]) -> Vec<Parameters> {
     let mut parameters : Vec<Parameters> = Vec::new();
 
@@ -128,7 +328,121 @@ pub fn interpret_parameters(args : &[String]) -> Vec<Parameters> {
                                                                 (args, &mut position))),
             "-pt" | "--port"     => parameters.push(Parameters::Port(port_parse
                                                                 (args, &mut position))),
+            "--src-ip"           => parameters.push(Parameters::SourceIp(ip_parse
+                                                                (args, &mut position))),
+            "--dst-ip"           => parameters.push(Parameters::DestinationIp(ip_parse
+                                                                (args, &mut position))),
+            "--src-port"         => parameters.push(Parameters::SourcePort(port_parse
+                                                                (args, &mut position))),
+            "--dst-port"         => parameters.push(Parameters::DestinationPort(port_parse
+                                                                (args, &mut position))),
             "-ao" | "--arp-operation" => parameters.push(Parameters::ArpOperation(arp_op_parse(args, &mut position))),
+            "--arp-sender-ip"    => parameters.push(Parameters::ArpIp(Direction::Source, 
+                                                                ip_parse(args, &mut position))),
+            "--arp-target-ip"    => parameters.push(Parameters::ArpIp(Direction::Destination, 
+                                                                ip_parse(args, &mut position))),
+            "--arp-sender-mac"   => parameters.push(Parameters::ArpMac(Direction::Source, 
+                                            list_parse(args, &mut position, "MAC", 
+                                                       MacFilter::parse))),
+            "--arp-target-mac"   => parameters.push(Parameters::ArpMac(Direction::Destination, 
+                                            list_parse(args, &mut position, "MAC", 
+                                                       MacFilter::parse))),
+            "--icmp-type"        => parameters.push(Parameters::Icmp(list_parse
+                                            (args, &mut position, "ICMP TYPE", 
+                                             IcmpFilter::parse))),
+            "--icmp6-type"       => parameters.push(Parameters::Icmp6(list_parse
+                                            (args, &mut position, "ICMPv6 TYPE", 
+                                             IcmpFilter::parse_v6))),
+            "--vlan"             => parameters.push(Parameters::Vlan(list_parse
+                                            (args, &mut position, "VLAN", 
+                                             VlanFilter::parse))),
+            "--exclude-ip"       => parameters.push(Parameters::ExcludeIp(ip_parse
+                                                                (args, &mut position))),
+            "--exclude-port"     => parameters.push(Parameters::ExcludePort(port_parse
+                                                                (args, &mut position))),
+            "--exclude-protocol" => parameters.push(Parameters::ExcludeProtocol(protocols_parse
+                                                                (args, &mut position))),
+            "--ignore-own-ssh"   => {
+                parameters.push(Parameters::IgnoreOwnSsh);
+                position += 1;
+            },
+            "--tcp-flags"        => parameters.push(Parameters::TcpFlags(list_parse
+                                            (args, &mut position, "TCP FLAGS", 
+                                             TcpFlagFilter::parse))),
+            "--frame-length"     => parameters.push(field_parse(args, &mut position, 
+                                                                Field::FrameLength)),
+            "--length"           => parameters.push(field_parse(args, &mut position, Field::Length)),
+            "--ttl"              => parameters.push(field_parse(args, &mut position, Field::Ttl)),
+            "--dscp"             => parameters.push(field_parse(args, &mut position, Field::Dscp)),
+            "--ecn"              => parameters.push(field_parse(args, &mut position, Field::Ecn)),
+            "--frag-offset"      => parameters.push(field_parse(args, &mut position, 
+                                                                Field::FragmentOffset)),
+            "--df" | "--mf"      => {
+                let field = if args[position] == "--df" { Field::DontFragment } 
+                            else { Field::MoreFragments };
+
+                parameters.push(Parameters::HeaderField(field, vec![FieldRange::single(1)]));
+                position += 1;
+            },
+            "--contains"         => parameters.push(Parameters::Contains(list_parse
+                                            (args, &mut position, "CONTENT", payload::parse_bytes))),
+            "--regex"            => parameters.push(Parameters::Regex(regex_parse
+                                                                (args, &mut position))),
+            "--mac"              => parameters.push(Parameters::Mac(list_parse
+                                            (args, &mut position, "MAC", MacFilter::parse))),
+            "--ethertype"        => parameters.push(Parameters::EtherType(list_parse
+                                            (args, &mut position, "ETHERTYPE", 
+                                             filtering::parse_ethertype))),
+            "-f"  | "--filter"   => parameters.push(Parameters::Filter(filter_parse
+                                                                (args, &mut position))),
+            "--rules"            => parameters.push(Parameters::Rules(rules_parse
+                                                                (args, &mut position))),
+            "-I"  | "--interface" => parameters.push(Parameters::Interface(interface_parse
+                                                                (args, &mut position))),
+            "--list-interfaces"  => {
+                parameters.push(Parameters::ListInterfaces);
+                position += 1;
+            },
+            "-r"  | "--read"     => parameters.push(Parameters::ReadFile(file_parse
+                                                                (args, &mut position))),
+            "-w"  | "--write"    => parameters.push(Parameters::WriteFile(file_parse
+                                                                (args, &mut position))),
+            "--rotate-size"      => parameters.push(Parameters::RotateSize(value_parse
+                                            (args, &mut position, "FILE SIZE", size_from_str))),
+            "--rotate-every"     => parameters.push(Parameters::RotateEvery(value_parse
+                                            (args, &mut position, "DURATION", duration_from_str))),
+            "--max-files"        => parameters.push(Parameters::MaxFiles(value_parse
+                                            (args, &mut position, "FILE COUNT", 
+                                             |value| value.parse::<usize>().ok()))),
+            "-c"  | "--count"    => parameters.push(Parameters::Count(value_parse
+                                            (args, &mut position, "PACKET COUNT", 
+                                             |value| value.parse::<usize>().ok()))),
+            "--duration"         => parameters.push(Parameters::Duration(value_parse
+                                            (args, &mut position, "DURATION", duration_from_str))),
+            "--no-promisc"       => {
+                parameters.push(Parameters::NoPromiscuous);
+                position += 1;
+            },
+            "-s"  | "--snaplen"  => parameters.push(Parameters::Snaplen(value_parse
+                                            (args, &mut position, "SNAPLEN", 
+                                             |value| value.parse::<usize>().ok()
+                                                          .filter(|snaplen| *snaplen > 0)))),
+            "--read-buffer"      => parameters.push(Parameters::ReadBuffer(value_parse
+                                            (args, &mut position, "BUFFER SIZE", 
+                                             |value| size_from_str(value).map(|s| s as usize)))),
+            "--write-buffer"     => parameters.push(Parameters::WriteBuffer(value_parse
+                                            (args, &mut position, "BUFFER SIZE", 
+                                             |value| size_from_str(value).map(|s| s as usize)))),
+            "--timeout"          => parameters.push(Parameters::ReadTimeout(value_parse
+                                            (args, &mut position, "TIMEOUT", 
+                                             |value| duration_from_str(value)
+                                                          .filter(|timeout| *timeout > Duration::ZERO)))),
+            "--fanout"           => parameters.push(Parameters::Fanout(value_parse
+                                            (args, &mut position, "FANOUT", fanout_from_str))),
+            "--print"            => {
+                parameters.push(Parameters::Print);
+                position += 1;
+            },
             _ => position += 1
         }
     }
@@ -154,6 +468,55 @@ mod tests {
         assert_eq!(params.len(), 2);
     }
 
+    #[test]
+    fn test_sizes_and_durations() {
+        assert_eq!(size_from_str("100M"), Some(100 * 1024 * 1024));
+        assert_eq!(size_from_str("512kb"), Some(512 * 1024));
+        assert_eq!(size_from_str("4096"), Some(4096));
+        assert_eq!(size_from_str("M"), None);
+
+        assert_eq!(duration_from_str("1h"), Some(Duration::from_secs(3600)));
+        assert_eq!(duration_from_str("90"), Some(Duration::from_secs(90)));
+        assert_eq!(duration_from_str("250ms"), Some(Duration::from_millis(250)));
+        assert_eq!(duration_from_str("soon"), None);
+    }
+
+    #[test]
+    fn test_interfaces() {
+        let args = vec!["-I".to_string(), "eth0".to_string(), "veth*".to_string(),
+                        "-i".to_string(), "10.0.0.1".to_string()];
+
+        let params = interpret_parameters(&args);
+
+        assert_eq!(params.len(), 2);
+        assert!(matches!(&params[0], Parameters::Interface(names) if names.len() == 2));
+    }
+
+    #[test]
+    fn test_link_filters() {
+        let args : Vec<String> = ["--mac", "00:1a:2b:*", "--ethertype", "lldp", "0x8100", "vlan2"]
+            .iter().map(|arg| arg.to_string()).collect();
+
+        let params = interpret_parameters(&args);
+
+        assert_eq!(params.len(), 2);
+        assert!(matches!(&params[0], Parameters::Mac(macs) if macs[0].prefix == [0x00, 0x1a, 0x2b]));
+        assert!(matches!(&params[1], Parameters::EtherType(types) if *types == [0x88cc, 0x8100]));
+    }
+
+    #[test]
+    fn test_ports() {
+        let args : Vec<String> = ["-pt", "80", "8000-8100", "https,dns", "nonsense", "-p", "tcp"]
+            .iter().map(|arg| arg.to_string()).collect();
+
+        let params = interpret_parameters(&args);
+
+        assert_eq!(params.len(), 2);
+        assert!(matches!(&params[0], Parameters::Port(ports) if ports.len() == 4 &&
+                         ports[1] == PortRange { low : 8000, high : 8100 } &&
+                         ports[3] == PortRange::single(53)));
+    }
+
     #[test]
     fn test_no_input() {
         let args = vec!["-p".to_string(), "-pt".to_string()];
@@ -1,11 +1,21 @@
 // THE FILE MADE FOR DIFFERENT LAYERS OF TCP/IP STACK
 
 use crate::protocols::Protocol;
-use crate::protocols::{ethernet, ipv4, ipv6, tcp, udp, arp};
+use crate::protocols::{ethernet, ipv4, ipv6, tcp, udp, arp, icmp, icmpv6, sll, sll2, loopback, 
+                       raw, payload};
 use crate::Parameters;
+use crate::capture::{self, PacketInfo, format_timestamp};
+use crate::filtering::{Filters, IpFilter, MacFilter, NetworkLayer, PortRange};
+use crate::filtering::payload::find_matches;
 
-use pnet::packet::ethernet::EtherType;
-use pnet::packet::ethernet::EtherTypes;
+use std::net::{IpAddr, SocketAddr};
+
+use pnet::packet::arp::ArpPacket;
+use pnet::packet::ethernet::{EtherType, EtherTypes, EthernetPacket};
+use pnet::packet::ipv4::Ipv4Packet;
+use pnet::packet::ipv6::Ipv6Packet;
+use pnet::packet::tcp::TcpPacket;
+use pnet::packet::udp::UdpPacket;
 
 #[derive(Clone, Debug)]
 pub enum UpperProtocol {
@@ -24,25 +34,87 @@ fn return_print_output(protocol : &Protocol) -> fn(Vec<u8>) {
         Protocol::TCP      => tcp::print_output,
         Protocol::UDP      => udp::print_output,
         Protocol::ARP      => arp::print_output,
+        Protocol::ICMP     => icmp::print_output,
+        Protocol::ICMPv6   => icmpv6::print_output,
+        Protocol::SLL      => sll::print_output,
+        Protocol::SLL2     => sll2::print_output,
+        Protocol::LOOPBACK => loopback::print_output,
+        Protocol::RAW      => raw::print_output,
+        Protocol::PAYLOAD  => payload::print_output,
+    }
+}
+
+// THE ACCESS LAYER PROTOCOL DECODING THE FRAMES OF THE GIVEN LINK TYPE
+
+fn get_access_protocol(link_type : u32) -> Option<Protocol> {
+    match link_type {
+        capture::LINKTYPE_ETHERNET   => Some(Protocol::ETHERNET),
+        capture::LINKTYPE_LINUX_SLL  => Some(Protocol::SLL),
+        capture::LINKTYPE_LINUX_SLL2 => Some(Protocol::SLL2),
+        capture::LINKTYPE_NULL | 
+        capture::LINKTYPE_LOOP       => Some(Protocol::LOOPBACK),
+        capture::LINKTYPE_RAW | capture::LINKTYPE_IPV4 | capture::LINKTYPE_IPV6 | 
+        capture::DLT_RAW | capture::DLT_RAW_OPENBSD => Some(Protocol::RAW),
+        _ => None,
     }
 }
 
-pub fn check_network_access_layer(packet : Vec<u8>) 
+pub fn is_supported_link_type(link_type : u32) -> bool {
+    get_access_protocol(link_type).is_some()
+}
+
+pub fn check_network_access_layer(packet : Vec<u8>, link_type : u32, 
+                                  macs : &[MacFilter], ethertypes : &[u16]) 
                                   -> Option<(Protocol, UpperProtocol, Vec<u8>)> {
 
     let packet_array = packet.as_slice();
 
-    // ETHERNET
-    if let Some(res) = ethernet::check_and_get_next_layer(packet_array, 
-                                                        Parameters::NoParameter) {
-        return Some((Protocol::ETHERNET, res.0, res.1));
+    // THE LINK TYPE OF THE INTERFACE (OR THE CAPTURE FILE)
+    // TELLS HOW THE FRAME SHOULD BE DECODED
+
+    let protocol = get_access_protocol(link_type)?;
+
+    // ONLY ETHERNET HAS BOTH MAC ADDRESSES, THE OTHER FRAMES CANNOT MATCH THEM
+
+    let check_and_get_next_layer = match protocol {
+        Protocol::SLL      => sll::check_and_get_next_layer,
+        Protocol::SLL2     => sll2::check_and_get_next_layer,
+        Protocol::LOOPBACK => loopback::check_and_get_next_layer,
+        Protocol::RAW      => raw::check_and_get_next_layer,
+        _                  => ethernet::check_and_get_next_layer,
+    };
+
+    let macs = match protocol {
+        Protocol::ETHERNET => Parameters::Mac(macs.to_vec()),
+        _ if !macs.is_empty() => return None,
+        _ => Parameters::NoParameter,
+    };
+
+    // THE ETHERTYPE IS ALSO KNOWN FOR THE FRAMES WITHOUT ETHERNET (FROM THE IP VERSION)
+    // THE TAGGED FRAME HAS THE ONE INSIDE THE TAGS, BUT THE TYPES OF THE TAGS MATCH TOO
+
+    if let Some(res) = check_and_get_next_layer(packet_array, macs) {
+        if let UpperProtocol::Layer1(ethertype) = res.0 {
+            let tags = match protocol {
+                Protocol::ETHERNET => ethernet::split_vlan_tags(packet_array)
+                                          .map(|(tags, _, _)| tags).unwrap_or_default(),
+                _ => vec![],
+            };
+
+            if !ethertypes.is_empty() && !ethertypes.contains(&ethertype.0) && 
+               !tags.iter().any(|tag| ethertypes.contains(&tag.tpid)) {
+                return None;
+            }
+        }
+
+        return Some((protocol, res.0, res.1));
     }
 
     None
 }
 
 pub fn check_network_layer(packet : Vec<u8>, current_protocol : UpperProtocol, 
-                           ips : &Vec<String>, arp_op : Option<u16>)
+                           ips : &[IpFilter], arp_op : Option<u16>)
                                     -> Option<(Protocol, UpperProtocol, Vec<u8>)> {
     let packet_array = packet.as_slice();
 
@@ -75,11 +147,11 @@ pub fn check_network_layer(packet : Vec<u8>, current_protocol : UpperProtocol,
 }
 
 pub fn check_transport_layer(packet : Vec<u8>, current_protocol : UpperProtocol,
-                             ports : &Vec<u16>) 
+                             ports : &[PortRange]) 
                                       -> Option<(Protocol, UpperProtocol, Vec<u8>)> {
     let packet_array = packet.as_slice();
 
-    // TCP AND UDP ARE SUPPORTED
+    // TCP, UDP, ICMP AND ICMPv6 ARE SUPPORTED
 
     match current_protocol {
         UpperProtocol::Layer2(6) => {
@@ -94,7 +166,19 @@ pub fn check_transport_layer(packet : Vec<u8>, current_protocol : UpperProtocol,
                 return Some((Protocol::UDP, res.0, res.1));
             }
         },
-        _ => println!("NO TRANSPORT LAYER"),
+        UpperProtocol::Layer2(1) => {
+            if let Some(res) = icmp::check_and_get_next_layer(packet_array, 
+                                                    Parameters::Port(ports.to_vec())) {
+                return Some((Protocol::ICMP, res.0, res.1));
+            }
+        },
+        UpperProtocol::Layer2(58) => {
+            if let Some(res) = icmpv6::check_and_get_next_layer(packet_array, 
+                                                    Parameters::Port(ports.to_vec())) {
+                return Some((Protocol::ICMPv6, res.0, res.1));
+            }
+        },
+        _ => (),
     }
     
     None
@@ -115,47 +199,162 @@ pub fn check_application_layer(packet : Vec<u8>, _current_protocol : UpperProtoc
 // TO CORRECTLY LATER CALL ALL THE PRINT FUNCTIONS AFTER CHECKING
 
 #[derive(Debug)]
-struct Layer {
-    protocol : Protocol,
-    data : Vec<u8>,
+pub struct Layer {
+    pub protocol : Protocol,
+    pub data : Vec<u8>,
+
+    // THE PARTS OF THE DATA TO BE SHOWN IN THE OTHER COLOR (WHAT THE PAYLOAD FILTERS FOUND)
+    pub highlights : Vec<(usize, usize)>,
 }
 
 impl Layer {
     fn create(protocol : Protocol, data : Vec<u8>) -> Layer {
-        Layer { protocol, data }
+        Layer { protocol, data, highlights : vec![] }
     }
 }
 
-fn print_needed(layers : &Vec<Layer>) {
+fn print_needed(layers : &[Layer]) {
     for layer in layers {
-        return_print_output(&layer.protocol)(layer.data.clone());
+        match layer.protocol {
+            Protocol::PAYLOAD => payload::print_highlighted(&layer.data, &layer.highlights),
+            _ => return_print_output(&layer.protocol)(layer.data.clone()),
+        }
+    }
+}
+
+// PRINTING THE WHOLE PACKET: THE HEADER WITH ITS METADATA AND EVERY LAYER
+// STDOUT IS HELD FOR THE WHOLE PACKET, SO THE THREADS DO NOT MIX THEIR LINES
+
+pub fn print_packet(info : &PacketInfo, layers : &[Layer]) {
+    let _stdout = std::io::stdout().lock();
+
+    println!("\n\n\x1b[1mPACKET #{}\x1b[0m, INTERFACE: {}, TIME: {}", 
+                    info.id, info.interface, format_timestamp(info.timestamp));
+
+    if let Some(comment) = info.comment {
+        println!("\x1b[1mCOMMENT:\x1b[0m {}", comment);
+    }
+
+    print_needed(layers);
+}
+
+// WHO TALKS TO WHOM: THE IP ADDRESSES (WITH THE PORTS IF THERE ARE ANY)
+// OR THE MAC ADDRESSES IF THE PACKET HAS NO IP
+
+fn find_endpoints(layers : &[Layer]) -> Option<(String, String)> {
+    let find = |protocol : Protocol| layers.iter().find(|layer| layer.protocol == protocol)
+                                               .map(|layer| layer.data.as_slice());
+
+    let ips : Option<(IpAddr, IpAddr)> = 
+        find(Protocol::IPv4).and_then(Ipv4Packet::new).map(|packet| packet.get_source_dest())
+        .or_else(|| find(Protocol::IPv6).and_then(Ipv6Packet::new)
+                                        .map(|packet| packet.get_source_dest()))
+        .or_else(|| find(Protocol::ARP).and_then(ArpPacket::new)
+                                       .map(|packet| packet.get_source_dest()));
+
+    let ports : Option<(u16, u16)> = 
+        find(Protocol::TCP).and_then(TcpPacket::new)
+                           .map(|packet| (packet.get_source(), packet.get_destination()))
+        .or_else(|| find(Protocol::UDP).and_then(UdpPacket::new)
+                           .map(|packet| (packet.get_source(), packet.get_destination())));
+
+    match (ips, ports) {
+        (Some((source, dest)), Some((source_port, dest_port))) => {
+            Some((SocketAddr::new(source, source_port).to_string(), 
+                  SocketAddr::new(dest, dest_port).to_string()))
+        },
+        (Some((source, dest)), None) => Some((source.to_string(), dest.to_string())),
+        _ => find(Protocol::ETHERNET).and_then(EthernetPacket::new)
+                 .map(|frame| (frame.get_source().to_string(), 
+                               frame.get_destination().to_string())),
     }
 }
 
-pub fn check_all_layers(packet_id : usize, int_name : &str, packet : &[u8], 
-                        protocols : Vec<Protocol>, ips : Vec<String>, ports : Vec<u16>, 
-                        arp_op : Option<u16>) {
+// THE PACKET IN ONE LINE: "#7 eth0 2024-02-29 12:00:00.000000 UTC ETHERNET/IPv4/TCP
+// 10.0.0.1:40000 -> 10.0.0.3:443 (54 bytes)"
+
+pub fn summarize_packet(info : &PacketInfo, layers : &[Layer]) -> String {
+    let protocols : Vec<String> = layers.iter()
+        .filter(|layer| layer.protocol != Protocol::PAYLOAD)
+        .map(|layer| format!("{:?}", layer.protocol))
+        .collect();
+
+    let endpoints = find_endpoints(layers)
+        .map(|(source, dest)| format!(" {} -> {}", source, dest))
+        .unwrap_or_default();
+
+    format!("#{} {} {} {}{} ({} bytes)", info.id, info.interface, 
+            format_timestamp(info.timestamp), protocols.join("/"), endpoints, info.original_len)
+}
+
+// ONLY THE PROTOCOLS THE PROGRAM KNOWS CAN BE BROKEN, THE UNKNOWN ONES ARE NOT DECODED
+
+fn is_known_network(protocol : &UpperProtocol) -> bool {
+    matches!(protocol, UpperProtocol::Layer1(EtherTypes::Ipv4) | 
+                       UpperProtocol::Layer1(EtherTypes::Ipv6) | 
+                       UpperProtocol::Layer1(EtherTypes::Arp))
+}
+
+fn is_known_transport(protocol : &UpperProtocol) -> bool {
+    matches!(protocol, UpperProtocol::Layer2(1) | UpperProtocol::Layer2(6) | 
+                       UpperProtocol::Layer2(17) | UpperProtocol::Layer2(58))
+}
+
+// THE PACKET WHICH PASSED ALL THE FILTERS: ITS LAYERS
+// AND THE RULES IT MATCHED (THEIR POSITIONS IN THE FILTERS)
+
+pub struct CheckedPacket {
+    pub layers  : Vec<Layer>,
+    pub rules   : Vec<usize>,
+
+    // THE LAYER ON WHICH THE PACKET TURNED OUT TO BE BROKEN (TOO SHORT OR NOT WHAT
+    // THE LAYER BELOW PROMISED), THE BROKEN PACKET CAN STILL MATCH WITH ITS UPPER LAYERS
+    pub failure : Option<&'static str>,
+}
+
+// RETURNS THE LAYERS OF THE PACKET IF IT PASSED ALL THE FILTERS
+// WITH THE RULES, THE PACKET MUST ALSO MATCH AT LEAST ONE OF THEM
+// THE PACKET WHICH DID NOT PASS STILL TELLS THE LAYER IT WAS BROKEN ON
+
+pub fn check_all_layers(packet : &[u8], link_type : u32, filters : &Filters) 
+                                        -> Result<CheckedPacket, Option<&'static str>> {
+    let (protocols, ips, ports) = (&filters.protocols, &filters.ips, &filters.ports);
 
     let packet            : Vec<u8>    = packet.to_vec();
     let mut passed_layers : Vec<Layer> = Vec::new();
 
+    // THE LAYER WHICH DID NOT PASS IS BROKEN ONLY IF IT CANNOT BE DECODED WITHOUT ITS FILTERS,
+    // SO THE FILTERED OUT PACKETS ARE NOT COUNTED AS FAILURES
+    // IT IS DECODED AGAIN ONLY WHEN IT HAD ANY FILTERS, THE LAYERS ABOVE ARE NEVER TRIED
+    let mut failure : Option<&'static str> = None;
+
     // IF THE ACCESS LAYER EXISTS AND IS VALID
     let packet_cl = packet.clone();
-    if let Some(res_access) = check_network_access_layer(packet) {
+    if let Some(res_access) = check_network_access_layer(packet, link_type, &filters.macs, 
+                                                         &filters.ethertypes) {
         passed_layers.push(Layer::create(res_access.0, packet_cl.clone()));
 
         // IF THE ACCESS LAYER WAS VALID AND THE NEXT LAYER EXISTS
         if let Some(res_network) = check_network_layer(res_access.2.clone(), 
-                                                    res_access.1.clone(), &ips, arp_op) {
+                                                    res_access.1.clone(), ips, filters.arp_op) {
 
             passed_layers.push(Layer::create(res_network.0, res_access.2.clone()));
             
             // IF THE NETWORK LAYER WAS VALID AND THE NEXT LAYER EXISTS
             if let Some(res_transport) = check_transport_layer(res_network.2.clone(), 
-                                                    res_network.1.clone(), &ports) {
+                                                    res_network.1.clone(), ports) {
 
                 passed_layers.push(Layer::create(res_transport.0, res_network.2.clone()));
 
+                // THE PAYLOAD IS ONLY A LAYER WHEN IT WAS SEARCHED, TO SHOW WHAT WAS FOUND
+                if !filters.payload.is_empty() {
+                    let highlights = find_matches(&filters.payload, &res_transport.2)
+                        .ok_or(failure)?;
+
+                    passed_layers.push(Layer { protocol : Protocol::PAYLOAD, 
+                                               data : res_transport.2.clone(), highlights });
+                }
+
                 // IF THE TRANSPORT LAYER WAS VALID AND THE NEXT LAYER EXISTS
                 if let Some(res_app) = check_application_layer(res_transport.2.clone(), 
                                                         res_transport.1.clone()) {
@@ -163,25 +362,56 @@ pub fn check_all_layers(packet_id : usize, int_name : &str, packet : &[u8],
                                        res_transport.2.clone()));
 
                 }
+            } else if is_known_transport(&res_network.1) && 
+                      (ports.is_empty() || 
+                       check_transport_layer(res_network.2, res_network.1, &[]).is_none()) {
+                failure = Some("TRANSPORT");
             }
+        } else if is_known_network(&res_access.1) && 
+                  ((ips.is_empty() && filters.arp_op.is_none()) || 
+                   check_network_layer(res_access.2, res_access.1, &[], None).is_none()) {
+            failure = Some("NETWORK");
         }
+    } else if is_supported_link_type(link_type) && 
+              ((filters.macs.is_empty() && filters.ethertypes.is_empty()) || 
+               check_network_access_layer(packet_cl, link_type, &[], &[]).is_none()) {
+        failure = Some("ACCESS");
     }
 
 
     if (passed_layers.len() < 2 && !ips.is_empty()) || // IF THERE IS NO NETWORK LAYER, BUT IPs
                                                            // ARE SPECIFIED
-       (passed_layers.len() < 3 && !ports.is_empty())  // IF THERE IS NO TRANSPORT LAYER, BUT PORTS
+       (passed_layers.len() < 3 && !ports.is_empty()) || // IF THERE IS NO TRANSPORT LAYER, BUT PORTS
                                                            // ARE SPECIFIED
+       (passed_layers.len() < 4 && !filters.payload.is_empty()) // OR NO PAYLOAD, BUT IT WAS SEARCHED
     {
-            return;
+            return Err(failure);
     }
 
-    for layer in &passed_layers {
-        if protocols.contains(&layer.protocol) || protocols.is_empty() {
-            println!("\n\n\x1b[1mPACKET #{}\x1b[0m, INTERFACE: {}", 
-                            packet_id, int_name);
-            print_needed(&passed_layers);
-            break;
-        }
+    // THE EXPRESSION IS CHECKED ON THE LAYERS WHICH PASSED THE FLAGS ABOVE
+
+    if filters.expression.as_ref()
+              .is_some_and(|expression| !expression.matches(&passed_layers)) {
+        return Err(failure);
+    }
+
+    let wanted = passed_layers.iter().any(|layer| protocols.contains(&layer.protocol)) || 
+                 (protocols.is_empty() && !passed_layers.is_empty());
+
+    if !wanted {
+        return Err(failure);
     }
+
+    // EVERY RULE IS ASKED, AS ALL OF THE MATCHING ONES DO THEIR ACTION
+
+    let rules : Vec<usize> = filters.rules.iter().enumerate()
+        .filter(|(_, rule)| rule.matches(&passed_layers))
+        .map(|(position, _)| position)
+        .collect();
+
+    if !filters.rules.is_empty() && rules.is_empty() {
+        return Err(failure);
+    }
+
+    Ok(CheckedPacket { layers : passed_layers, rules, failure })
 }
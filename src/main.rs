@@ -2,6 +2,12 @@ pub mod protocols;
 pub mod layers;
 pub mod filtering;
 pub mod io;
+pub mod capture;
+pub mod control;
+pub mod statistics;
+pub mod interfaces;
+pub mod channel;
+pub mod rules;
 
 use crate::io::interpret_parameters;
 use crate::protocols::find_packets;
@@ -13,11 +19,47 @@ use crate::protocols::find_packets;
 
 #[derive(Debug)]
 pub enum Parameters { 
-    IpAddress(Vec<String>),
-    Port(Vec<u16>),
+    IpAddress(Vec<filtering::IpFilter>),
+    SourceIp(Vec<filtering::IpFilter>),
+    DestinationIp(Vec<filtering::IpFilter>),
+    Port(Vec<filtering::PortRange>),
+    SourcePort(Vec<filtering::PortRange>),
+    DestinationPort(Vec<filtering::PortRange>),
     Interface(Vec<String>),
+    ListInterfaces,
     Protocol(Vec<protocols::Protocol>),
     ArpOperation(Option<u16>),
+    ArpIp(filtering::Direction, Vec<filtering::IpFilter>),
+    ArpMac(filtering::Direction, Vec<filtering::MacFilter>),
+    Mac(Vec<filtering::MacFilter>),
+    EtherType(Vec<u16>),
+    Vlan(Vec<filtering::VlanFilter>),
+    Icmp(Vec<filtering::IcmpFilter>),
+    Icmp6(Vec<filtering::IcmpFilter>),
+    Filter(Option<filtering::expression::Expression>),
+    Rules(Vec<rules::Rule>),
+    ExcludeIp(Vec<filtering::IpFilter>),
+    ExcludePort(Vec<filtering::PortRange>),
+    ExcludeProtocol(Vec<protocols::Protocol>),
+    IgnoreOwnSsh,
+    TcpFlags(Vec<filtering::TcpFlagFilter>),
+    HeaderField(filtering::fields::Field, Vec<filtering::fields::FieldRange>),
+    Contains(Vec<Vec<u8>>),
+    Regex(Option<regex::bytes::Regex>),
+    ReadFile(Option<String>),
+    WriteFile(Option<String>),
+    RotateSize(Option<u64>),
+    RotateEvery(Option<std::time::Duration>),
+    MaxFiles(Option<usize>),
+    Count(Option<usize>),
+    Duration(Option<std::time::Duration>),
+    NoPromiscuous,
+    Snaplen(Option<usize>),
+    ReadBuffer(Option<usize>),
+    WriteBuffer(Option<usize>),
+    ReadTimeout(Option<std::time::Duration>),
+    Fanout(Option<pnet::datalink::FanoutOption>),
+    Print,
     NoParameter,
 }
 
@@ -52,5 +94,10 @@ fn main() {
     let args : Vec<String> = std::env::args().collect();
     let parameters = interpret_parameters(&args);
 
+    if parameters.iter().any(|param| matches!(param, Parameters::ListInterfaces)) {
+        interfaces::list_interfaces();
+        return;
+    }
+
     find_packets(parameters);
 }
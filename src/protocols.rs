@@ -4,14 +4,33 @@ pub mod ethernet;
 pub mod ipv4;
 pub mod ipv6;
 pub mod arp;
-
-use std::sync::Arc;
-use pnet::datalink::{self, Channel};
+pub mod icmp;
+pub mod icmpv6;
+pub mod sll;
+pub mod sll2;
+pub mod loopback;
+pub mod raw;
+pub mod payload;
+
+use std::sync::{Arc, Mutex};
+use pnet::datalink;
 use std::thread;
+use std::time::{Duration, Instant};
 use pnet::datalink::NetworkInterface;
 
 use crate::{Parameters, print_program_name, print_error};
 use crate::layers;
+use crate::interfaces::select_interfaces;
+use crate::capture::{self, CaptureWriter, Output, PacketInfo};
+use crate::capture::source::{self, FileSource, LiveSource, PacketSource};
+use crate::capture::rotation::{Rotation, RotatingWriter};
+use crate::filtering::{self, ssh, Direction, Filters};
+use crate::filtering::payload::PayloadPattern;
+use crate::filtering::expression::{self, Expression};
+use crate::control::{self, Control, StopConditions};
+use crate::channel::{self, ChannelOptions};
+use crate::rules::{Action, RuleOutput};
+use crate::statistics::{self, InterfaceStats};
 
 
 
@@ -26,29 +45,101 @@ pub enum Protocol {
     TCP,
     UDP,
     ARP,
-    //ICMP,
+    SLL,
+    SLL2,
+    LOOPBACK,
+    RAW,
+    PAYLOAD,
+    ICMP,
+    ICMPv6,
     //HTTP,
     //HTTPS,
 }
 
+impl Protocol {
+    // THE NAMES THE USER CAN GIVE THE PROTOCOLS (IN -p AND IN THE FILTER EXPRESSION)
+
+    pub fn from_name(name : &str) -> Option<Protocol> {
+        match name {
+            "ether" | "ethernet" => Some(Protocol::ETHERNET),
+            "ip"    | "ipv4"     => Some(Protocol::IPv4),
+            "ip6"   | "ipv6"     => Some(Protocol::IPv6),
+            "tcp"                => Some(Protocol::TCP),
+            "udp"                => Some(Protocol::UDP),
+            "arp"                => Some(Protocol::ARP),
+            "icmp"               => Some(Protocol::ICMP),
+            "icmp6" | "icmpv6"   => Some(Protocol::ICMPv6),
+            _ => None,
+        }
+    }
+}
+
+
+
+// HOW OFTEN THE KERNEL IS ASKED FOR THE DROPS AND THE MAIN THREAD LOOKS FOR SIGUSR1
+
+const KERNEL_STATS_INTERVAL : Duration = Duration::from_secs(1);
+const STATS_SIGNAL_INTERVAL : Duration = Duration::from_millis(100);
+
+
+
+// EVERYTHING THE CAPTURE NEEDS, SORTED OUT FROM THE PARAMETERS
+
+pub struct CaptureSettings {
+    pub interfaces : Vec<NetworkInterface>,
+    pub filters    : Filters,
+    pub read_file  : Option<String>,
+    pub write_file : Option<String>,
+    pub rotation   : Rotation,
+    pub stop       : StopConditions,
+    pub channel    : ChannelOptions,
+
+    // PRINTING IS ON BY DEFAULT, BUT WRITING TO THE FILE TURNS IT OFF
+    // UNLESS IT IS ASKED FOR EXPLICITLY
+    pub print      : bool,
+}
+
+
+
+// ANY OF THE VALUES OF THE FLAG MUST MATCH (NOTHING IS REQUIRED IF ALL WERE INVALID)
+
+fn require_any(filters : &mut Filters, expressions : Vec<Expression>) {
+    if let Some(expression) = expression::any_of(expressions) {
+        filters.require(expression);
+    }
+}
+
+
+
+// NONE OF THE VALUES OF THE FLAG MAY MATCH
+
+fn exclude_any(filters : &mut Filters, expressions : Vec<Expression>) {
+    if let Some(expression) = expression::any_of(expressions) {
+        filters.require(Expression::Not(Box::new(expression)));
+    }
+}
+
 
 
 // FUNCTION FOR BUILDING THE EASY PARSABLE DATA
 // TO LATER BE USED IN THE INDIVIDUAL PROTOCOL FUNCTIONS 
 // FOR THE PACKET FILTERING AND DISPLAYING
 
-fn consider_parameters(interfaces : Vec<NetworkInterface>, params : Vec<Parameters>) ->
-                    (Vec<NetworkInterface>, Vec<u16>, Vec<String>, Vec<Protocol>, Option<u16>) {
+fn consider_parameters(interfaces : Vec<NetworkInterface>, params : Vec<Parameters>) 
+                                                                    -> CaptureSettings {
 
     // THE VARIABLES FOR THE CURRENT DATA
     // REPRESENTED IN VECTORS AS THE DATA IS NOT DRAMATICALLY BIG
     // AND THE OVERHEAD WON'T BREAK THE SPEED AND EFFICIENCY
 
-    let mut working_interfaces  : Vec<NetworkInterface> = Vec::new();
-    let mut working_ports       : Vec<u16>              = Vec::new();
-    let mut working_ips         : Vec<String>           = Vec::new();
-    let mut specified_protocols : Vec<Protocol>         = Vec::new();
-    let mut operation_arp       : Option<u16> = None;
+    let mut working_interfaces  : Option<Vec<NetworkInterface>> = None;
+    let mut filters             : Filters               = Filters::default();
+    let mut read_file           : Option<String>        = None;
+    let mut write_file          : Option<String>        = None;
+    let mut rotation            : Rotation              = Rotation::default();
+    let mut stop                : StopConditions        = StopConditions::default();
+    let mut channel             : ChannelOptions        = ChannelOptions::default();
+    let mut print               : bool                  = false;
 
     // EVERY SINGLE PARAMETER SHOULD BE SEPARATED IN APPROPRIATE VECTOR
     // TO BE LATER USED ON THE FOLLOWING ETAPEE
@@ -56,72 +147,340 @@ fn consider_parameters(interfaces : Vec<NetworkInterface>, params : Vec<Paramete
     for param in params {
         match param {
             Parameters::IpAddress(ips) => {
-                working_ips = ips;
+                filters.ips = ips;
             },
 
             Parameters::Port(ports) => {
-                working_ports = ports;
+                filters.ports = filtering::merge_ports(ports);
             },
 
-            Parameters::Interface(interface_names) => {
-                working_interfaces = interfaces.iter()
-                    .filter(|interface| interface_names.contains(&interface.name))
-                    .map(|interface| interface.clone())
-                    .collect();
+            // THE DIRECTIONAL FLAGS ARE THE SAME AS "src host ... or src host ..." 
+            // AND THE OTHERS IN THE EXPRESSION
+
+            Parameters::SourceIp(ips) => {
+                require_any(&mut filters, ips.into_iter()
+                    .map(|ip| Expression::Host(Direction::Source, ip)).collect());
+            },
+
+            Parameters::DestinationIp(ips) => {
+                require_any(&mut filters, ips.into_iter()
+                    .map(|ip| Expression::Host(Direction::Destination, ip)).collect());
+            },
+
+            Parameters::SourcePort(ports) => {
+                require_any(&mut filters, filtering::merge_ports(ports).into_iter()
+                    .map(|port| Expression::Port(Direction::Source, port)).collect());
+            },
+
+            Parameters::DestinationPort(ports) => {
+                require_any(&mut filters, filtering::merge_ports(ports).into_iter()
+                    .map(|port| Expression::Port(Direction::Destination, port)).collect());
+            },
+
+            // THE ADDRESSES INSIDE ARP, THE SOURCE IS THE SENDER, THE DESTINATION IS THE TARGET
+
+            Parameters::ArpIp(direction, ips) => {
+                require_any(&mut filters, ips.into_iter()
+                    .map(|ip| Expression::ArpHost(direction, ip)).collect());
+            },
+
+            Parameters::ArpMac(direction, macs) => {
+                require_any(&mut filters, macs.into_iter()
+                    .map(|mac| Expression::ArpMac(direction, mac)).collect());
+            },
+
+            Parameters::Icmp(messages) => {
+                require_any(&mut filters, messages.into_iter().map(Expression::Icmp).collect());
+            },
+
+            Parameters::Icmp6(messages) => {
+                require_any(&mut filters, messages.into_iter().map(Expression::Icmp6).collect());
+            },
+
+            // ANY OF THE TAGS (THE OUTER OR THE INNER ONE) CAN HAVE THE ID
+
+            Parameters::Vlan(vlans) => {
+                require_any(&mut filters, vlans.into_iter()
+                    .map(|vlan| Expression::Vlan(Some(vlan))).collect());
+            },
+
+            Parameters::TcpFlags(flags) => {
+                require_any(&mut filters, flags.into_iter().map(Expression::TcpFlags).collect());
+            },
+
+            Parameters::HeaderField(field, ranges) => {
+                require_any(&mut filters, ranges.into_iter()
+                    .map(|range| Expression::Field(field, range)).collect());
+            },
+
+            // THE EXCLUSIONS ARE "not (host ... or host ...)" AND SO ON
+
+            Parameters::ExcludeIp(ips) => {
+                exclude_any(&mut filters, ips.into_iter()
+                    .map(|ip| Expression::Host(Direction::Any, ip)).collect());
+            },
+
+            Parameters::ExcludePort(ports) => {
+                exclude_any(&mut filters, filtering::merge_ports(ports).into_iter()
+                    .map(|port| Expression::Port(Direction::Any, port)).collect());
+            },
+
+            Parameters::ExcludeProtocol(protocols) => {
+                exclude_any(&mut filters, protocols.into_iter()
+                    .map(Expression::Protocol).collect());
+            },
+
+            Parameters::IgnoreOwnSsh => {
+                match ssh::find_connection().as_deref().and_then(ssh::flow_expression) {
+                    Some(flow) => exclude_any(&mut filters, vec![flow]),
+                    None => {
+                        print_error();
+                        println!("NO SSH SESSION FOUND, NOTHING TO IGNORE");
+                    }
+                }
+            },
+
+            Parameters::Interface(patterns) => {
+                working_interfaces = Some(select_interfaces(&interfaces, &patterns));
             },
 
             Parameters::Protocol(protocols) => {
-                specified_protocols = protocols;
+                filters.protocols = protocols;
             },
 
             Parameters::ArpOperation(op) => {
-                operation_arp = op;
+                filters.arp_op = op;
+            },
+
+            Parameters::Contains(contents) => {
+                filters.payload.extend(contents.into_iter().map(PayloadPattern::Bytes));
+            },
+
+            Parameters::Regex(regex) => {
+                filters.payload.extend(regex.map(PayloadPattern::Regex));
+            },
+
+            Parameters::Mac(macs) => {
+                filters.macs = macs;
+            },
+
+            Parameters::EtherType(ethertypes) => {
+                filters.ethertypes = ethertypes;
+            },
+
+            Parameters::Filter(expression) => {
+                if let Some(expression) = expression {
+                    filters.require(expression);
+                }
             },
 
-            Parameters::NoParameter => (),
+            Parameters::Rules(rules) => {
+                filters.rules.extend(rules);
+            },
+
+            Parameters::ReadFile(file) => {
+                read_file = file;
+            },
+
+            Parameters::WriteFile(file) => {
+                write_file = file;
+            },
+
+            Parameters::RotateSize(size) => {
+                rotation.max_size = size;
+            },
+
+            Parameters::RotateEvery(every) => {
+                rotation.every = every;
+            },
+
+            Parameters::MaxFiles(count) => {
+                rotation.max_files = count;
+            },
+
+            Parameters::Count(count) => {
+                stop.count = count;
+            },
+
+            Parameters::Duration(duration) => {
+                stop.duration = duration;
+            },
+
+            Parameters::NoPromiscuous => {
+                channel.promiscuous = false;
+            },
+
+            Parameters::Snaplen(snaplen) => {
+                channel.snaplen = snaplen;
+            },
+
+            Parameters::ReadBuffer(size) => {
+                channel.read_buffer = size;
+            },
+
+            Parameters::WriteBuffer(size) => {
+                channel.write_buffer = size;
+            },
+
+            Parameters::ReadTimeout(timeout) => {
+                channel.read_timeout = timeout;
+            },
+
+            Parameters::Fanout(fanout) => {
+                channel.fanout = fanout;
+            },
+
+            Parameters::Print => {
+                print = true;
+            },
+
+            Parameters::NoParameter | Parameters::ListInterfaces => (),
         }
     }
 
     // If no interfaces are specified, use all interfaces
+    // (BUT NOT IF THE SPECIFIED ONES DO NOT EXIST, THE USER WAS ALREADY TOLD ABOUT IT)
+
+    let working_interfaces = working_interfaces.unwrap_or(interfaces);
+
+    // THE RULES SAY THEMSELVES WHAT IS PRINTED
 
-    if working_interfaces.is_empty() {
-        working_interfaces = interfaces;
+    let print = print || (write_file.is_none() && filters.rules.is_empty());
+
+    if rotation.is_enabled() && write_file.is_none() {
+        print_error();
+        println!("ROTATION NEEDS THE OUTPUT FILE (-w), IGNORING IT");
     }
 
-    (working_interfaces, working_ports, working_ips, specified_protocols, 
-     operation_arp)
+    CaptureSettings { interfaces : working_interfaces, filters, read_file, write_file, 
+                      rotation, stop, channel, print }
 }
 
 
 
-pub fn find_packets(params: Vec<Parameters>) {
-    // GETTING THE DEVICES AVAILABLE FOR THE PROGRAM
-    let interfaces = datalink::interfaces();
+// THE LOOP OVER THE SINGLE SOURCE OF THE PACKETS, RUNNING IN ITS OWN THREAD
 
-    print_program_name();
-    println!("LISTENING ON THE INTERFACES: {:?}", 
-             interfaces.iter().map(|i| i.name.clone()).collect::<Vec<String>>());
+fn find_packets_in_source(source : &mut dyn PacketSource, filters : &Filters, 
+                          output : &Output, control : &Control, stats : &InterfaceStats) {
+    // ONLY THE LINK TYPES UNDERSTOOD BY THE LAYERS CAN BE SHOWN,
+    // THE USER IS TOLD ONCE FOR EVERY INTERFACE
 
-    // TAKING ALL THE PARAMETERS TO WORK WITH INSIDE THE THREAD-LOOP
+    let mut warned_interfaces : Vec<String> = Vec::new();
+
+    // FOR A SAKE OF READABILITY, EACH PACKET GETS A PACKET ID
+
+    let mut packet_id : usize = 0;
+
+    // THE KERNEL IS ASKED ABOUT THE DROPPED PACKETS FROM TIME TO TIME,
+    // SO SIGUSR1 SHOWS THE FRESH NUMBERS
+
+    let mut last_kernel_stats = Instant::now();
+
+
+
+    // STARTING THE LISTENING
+    // IT WILL LISTEN UNTIL ANY OF THE STOP CONDITIONS IS MET OR THE SOURCE IS OVER
+
+    while !control.should_stop() {
+        // THE QUIET INTERFACE IS ASKED AS WELL, ITS DROPS MAY HAVE HAPPENED BEFORE IT WENT QUIET
+
+        if last_kernel_stats.elapsed() >= KERNEL_STATS_INTERVAL {
+            update_kernel_stats(source, stats);
+            last_kernel_stats = Instant::now();
+        }
+
+        // EVERYTIME WE GET A PACKET, PROCEED
+        match source.next_frame() {
+
+            // CASE NO ERROR OF READING
+            Ok(Some(frame)) => {
+                statistics::increment(&stats.seen);
+
+                if !layers::is_supported_link_type(frame.link_type) &&
+                   !warned_interfaces.iter().any(|name| name == frame.interface) {
+                    warned_interfaces.push(frame.interface.to_string());
+
+                    print_error();
+                    println!("UNSUPPORTED LINK TYPE {} ON {}", frame.link_type, frame.interface);
+                }
+
+                // CHECKING THE PACKET
+                // IT IT MATCHES THE PROTOCOLS
+                // PRINT IT OUT
+
+                let info = PacketInfo { id : packet_id, interface : frame.interface,
+                                        timestamp    : frame.timestamp,
+                                        link_type    : frame.link_type,
+                                        original_len : frame.original_len,
+                                        comment      : frame.comment };
+
+                // THE BROKEN LAYER IS FOUND BY THE SAME WALK, MATCHED OR NOT
+
+                match layers::check_all_layers(frame.data, frame.link_type, filters) {
+                    Ok(packet) => {
+                        if let Some(layer) = packet.failure {
+                            stats.decode_failure(layer);
+                        }
+
+                        if !control.take_matched() {
+                            break;
+                        }
+
+                        statistics::increment(&stats.matched);
+                        output.emit(&info, &packet, frame.data);
+                    },
+                    Err(Some(layer)) => stats.decode_failure(layer),
+                    Err(None) => (),
+                }
+
+                packet_id += 1;
+            },
+
+            // THE FILE IS OVER
+            Ok(None) => break,
+
+            // NOTHING CAME IN TIME OR THE SIGNAL INTERRUPTED THE WAITING
+            Err(e) if source::is_idle(&e) => continue,
+
+            // EVERY KIND OF THE ERROR IS SHOWN ONCE, THEN ONLY COUNTED,
+            // THE BROKEN (E.G. DOWN) INTERFACE IS GIVEN SOME TIME NOT TO SPIN ON IT
+            Err(e) => {
+                if stats.receive_error(&e) {
+                    print_error();
+                    println!("CANNOT READ THE PACKET #{} FROM {} ({:?}): {}", 
+                             packet_id, source.name(), e.kind(), e);
+                }
+
+                thread::sleep(channel::POLL_INTERVAL);
+            }
+        }
+    }
+
+    update_kernel_stats(source, stats);
+}
+
+fn update_kernel_stats(source : &mut dyn PacketSource, stats : &InterfaceStats) {
+    if let Some(kernel) = source.kernel_stats() {
+        stats.add_kernel_stats(kernel);
+    }
+}
+
+// EVERY SOURCE GETS ITS OWN THREAD AND ITS OWN STATISTICS
 
-    let (w_interfaces, w_ports, w_ips, w_prot, op) = consider_parameters(interfaces, params);
+pub fn run_sources(sources : Vec<Box<dyn PacketSource>>, filters : Filters, 
+                   output : Arc<Output>, control : Arc<Control>) -> Vec<Arc<InterfaceStats>> {
 
     // WAITING FOR EACH THREAD TO AVOID PREMATURE EXIT AND BUGS
 
     let mut handles : Vec<std::thread::JoinHandle<_>> = vec![];
+    let mut stats   : Vec<Arc<InterfaceStats>>        = vec![];
 
 
 
-    // BECAUSE OF WORKING WITH THREADS, WE NEED TO CLONE THE PROTOCOLS, PORTS AND IP
+    // BECAUSE OF WORKING WITH THREADS, WE NEED TO SHARE THE FILTERS
     // TO ENSURE NO POINTER ISSUES WILL OCCUR DURING THE ANALYSIS
 
-    let w_prot  = Arc::new(w_prot);
-    let w_ports = Arc::new(w_ports);
-    let w_ips   = Arc::new(w_ips);
-    let w_op    = Arc::new(op);
-
-    print_program_name();
-    println!("OH, REST! OHREST IS CATCHING THE PACKETS");
+    let filters = Arc::new(filters);
 
 
 
@@ -129,66 +488,193 @@ pub fn find_packets(params: Vec<Parameters>) {
     // USUALLY, IT IS A GOOD PRACTICE TO USE THREADS FOR EACH INTERFACE
     // AS THEY ARE INDEPENDENT AND CAN BE ANALYZED SEPARATELY
 
-    for interface in w_interfaces {
-        let w_prot  = Arc::clone(&w_prot);
-        let w_ports = Arc::clone(&w_ports);
-        let w_ips   = Arc::clone(&w_ips);
-        let w_op    = Arc::clone(&w_op);
+    for mut source in sources {
+        let filters = Arc::clone(&filters);
+        let output  = Arc::clone(&output);
+        let control = Arc::clone(&control);
+
+        let source_stats = Arc::new(InterfaceStats::new(source.name()));
+        stats.push(Arc::clone(&source_stats));
 
         // CREATING A THREAD FOR EACH INTERFACE
 
         let handle = thread::spawn(move || {
-            // OPENING A CHANNEL FOR THE INTERFACE
-            // IT WILL LISTEN FOR 16-BIT DATA
-            // WHICH LATER WOULD BE CONVERTED TO PACKETS
-            
-            let (_tx, mut rx) = match datalink::channel(&interface, Default::default()) {
-                Ok(Channel::Ethernet(tx, rx)) => (tx, rx),
-                Ok(_) => panic!("Unhandled channel type"),
-                Err(e) => panic!("Error: {}", e),
-            };
+            find_packets_in_source(source.as_mut(), &filters, &output, &control, 
+                                   &source_stats);
+        });
 
-            // FOR A SAKE OF READABILITY, EACH PACKET GETS A PACKET ID
+        handles.push(handle);
+    }
 
-            let mut packet_id : usize = 0;
+    // SIGUSR1 SHOWS THE STATISTICS WHILE THE THREADS ARE STILL WORKING
 
+    while !handles.iter().all(|handle| handle.is_finished()) {
+        if control::take_stats_request() {
+            statistics::print_statistics("STATISTICS", &stats);
+            output.rules.print_counters();
+        }
 
+        thread::sleep(STATS_SIGNAL_INTERVAL);
+    }
 
-            // STARTING THE LISTENING
-            // NO PACKET LIMITATION, IT WILL LISTEN UNTIL THE PROGRAM IS STOPPED
+    for handle in handles {
+        if handle.join().is_err() {
+            print_error();
+            println!("ONE OF THE CAPTURE THREADS PANICKED");
+        }
+    }
 
-            loop {
-                // EVERYTIME WE GET A PACKET, PROCEED
-                match rx.next() {
+    stats
+}
 
-                    // CASE NO ERROR OF READING
-                    Ok(packet) => {
-                        // CHECKING THE PACKET
-                        // IT IT MATCHES THE PROTOCOLS
-                        // PRINT IT OUT
 
-                        layers::check_all_layers(packet_id, &interface.name, packet, w_prot.to_vec(), 
-                                                w_ips.to_vec(), w_ports.to_vec(), *w_op);
-                    },
 
-                    Err(..) => {
-                        print_error();
-                        println!("SOME PACKET GOT CORRUPTED EITHER THE PROGRAM TREATS IT AS SUCH");
-                    }
-                }
+// OFFLINE MODE: THE PACKETS ARE TAKEN FROM THE CAPTURE FILE
 
-                packet_id += 1;
+fn open_file_source(path : &str) -> Vec<Box<dyn PacketSource>> {
+    match FileSource::open(path) {
+        Ok(source) => {
+            print_program_name();
+            println!("OH, REST! OHREST IS READING THE PACKETS FROM {}", source.name());
+
+            vec![Box::new(source)]
+        },
+        Err(e) => {
+            print_error();
+            println!("CANNOT READ THE FILE '{}': {}", path, e);
+            vec![]
+        }
+    }
+}
+
+// OPENING A CHANNEL FOR EVERY INTERFACE
+// THE ONES WHICH CANNOT BE OPENED ARE SKIPPED
+
+fn open_live_sources(interfaces : &[NetworkInterface], options : &ChannelOptions,
+                     filters : &Filters) -> Vec<Box<dyn PacketSource>> {
+    if interfaces.is_empty() {
+        print_error();
+        println!("NO INTERFACES TO LISTEN ON, SEE --list-interfaces");
+        return vec![];
+    }
+
+    let mut sources : Vec<Box<dyn PacketSource>> = vec![];
+
+    for interface in interfaces {
+        match LiveSource::open(interface, options, filters) {
+            Ok(source) => sources.push(Box::new(source)),
+            Err(e) => {
+                print_error();
+                println!("CANNOT LISTEN ON {}: {}", interface.name, e);
             }
-        });
+        }
+    }
 
-        handles.push(handle);
+    print_program_name();
+    println!("LISTENING ON THE INTERFACES: {:?}", 
+             sources.iter().map(|source| source.name().to_string()).collect::<Vec<String>>());
+
+    print_program_name();
+    println!("OH, REST! OHREST IS CATCHING THE PACKETS");
+
+    sources
+}
+
+
+
+// THE LINK TYPE FOR THE HEADER OF THE CLASSIC PCAP FILES, THE ONE OF THE OPENED SOURCES
+// (ETHERNET IF NONE IS KNOWN YET), NONE IF THE SOURCES DIFFER AND A CLASSIC PCAP FILE
+// WOULD HAVE TO HOLD THEM ALL: IT HAS ONLY ONE LINK TYPE, UNLIKE PCAPNG
+
+fn output_link_type(settings : &CaptureSettings, link_types : &[u32]) -> Option<u32> {
+    let link_type = link_types.first().copied().unwrap_or(capture::LINKTYPE_ETHERNET);
+
+    if link_types.iter().all(|known| *known == link_type) {
+        return Some(link_type);
     }
 
-    for handle in handles {
-        handle.join().unwrap();
+    let saved = settings.filters.rules.iter().filter_map(|rule| match &rule.action {
+        Action::Save(path) => Some(path),
+        _ => None,
+    });
+
+    match settings.write_file.iter().chain(saved).find(|path| !capture::is_pcapng(path)) {
+        Some(path) => {
+            print_error();
+            println!("THE SOURCES HAVE DIFFERENT LINK TYPES, '{}' CANNOT HOLD THEM ALL, \
+                      USE .pcapng", path);
+            None
+        },
+        None => Some(link_type),
     }
 }
 
+pub fn find_packets(params: Vec<Parameters>) {
+    // GETTING THE DEVICES AVAILABLE FOR THE PROGRAM
+    let interfaces = datalink::interfaces();
+
+    // TAKING ALL THE PARAMETERS TO WORK WITH INSIDE THE THREAD-LOOP
+
+    let settings = consider_parameters(interfaces, params);
+
+    let sources = match &settings.read_file {
+        Some(path) => open_file_source(path),
+        None => open_live_sources(&settings.interfaces, &settings.channel,
+                                   &settings.filters),
+    };
+
+    if sources.is_empty() {
+        return;
+    }
+
+    // OPENING THE OUTPUT FILE BEFORE ANY PACKET IS CAUGHT
+
+    let link_types : Vec<u32> = sources.iter().flat_map(|source| source.link_types()).collect();
+
+    let Some(link_type) = output_link_type(&settings, &link_types) else {
+        return;
+    };
+
+    let writer = match &settings.write_file {
+        Some(path) if settings.rotation.is_enabled() => {
+            let writer : Box<dyn CaptureWriter> = Box::new(RotatingWriter::new(path, 
+                                                            settings.rotation, link_type));
+            Some(Mutex::new(writer))
+        },
+        Some(path) => match capture::create_writer(path, link_type) {
+            Ok(writer) => Some(Mutex::new(writer)),
+            Err(e) => {
+                print_error();
+                println!("CANNOT CREATE THE FILE '{}': {}", path, e);
+                return;
+            }
+        },
+        None => None,
+    };
+
+    let rules = match RuleOutput::open(&settings.filters.rules, link_type) {
+        Ok(rules) => rules,
+        Err(e) => {
+            print_error();
+            println!("CANNOT CREATE THE FILE {}", e);
+            return;
+        }
+    };
+
+    let output  = Arc::new(Output { print : settings.print, writer, rules });
+    let control = Arc::new(Control::new(settings.stop));
+
+    // CTRL+C OR SYSTEMD STOPPING THE SERVICE ENDS THE CAPTURE GRACEFULLY
+
+    control::install_signal_handlers();
+
+    let stats = run_sources(sources, settings.filters, Arc::clone(&output), control);
+
+    output.finish();
+    statistics::print_summary(&stats);
+    output.rules.print_counters();
+}
+
 
 
 
@@ -197,30 +683,389 @@ pub fn find_packets(params: Vec<Parameters>) {
 // TESTS
 
 #[cfg(test)]
-
 mod tests {
     use super::*;
+    use std::sync::atomic::Ordering;
+    use crate::capture::pcap::{PcapReader, PcapWriter};
+    use crate::capture::source::MemorySource;
+    use crate::rules;
+    use crate::filtering::{IpFilter, MacFilter, PortRange};
+    use crate::filtering::fields::{Field, FieldRange};
 
     #[test]
     fn test_consider_parameters() {
         let interfaces = datalink::interfaces();
         let params = vec![Parameters::Interface(vec!["lo".to_string()]),
-                          Parameters::Port(vec![80, 32, 1]),
-                          Parameters::IpAddress(vec!["127.0.0.1".to_string()])];
-        let (w_ints, w_ports, w_ips, w_protocols) = consider_parameters(interfaces, params);
+                          Parameters::Port(vec![PortRange::single(80), PortRange::single(32),
+                                               PortRange::single(1)]),
+                          Parameters::IpAddress(vec![IpFilter::parse("127.0.0.1").unwrap()])];
+        let settings = consider_parameters(interfaces, params);
+
+        assert_eq!(settings.interfaces.len(), 1);
+        assert_eq!(settings.filters.ports.len(), 3);
+        assert_eq!(settings.filters.ips.len(), 1);
+        assert_eq!(settings.filters.protocols.len(), 0);
+        assert!(settings.read_file.is_none());
+        assert!(settings.print);
+    }
+
+    #[test]
+    fn test_output_link_type() {
+        let settings = |path : &str| {
+            consider_parameters(vec![], vec![Parameters::WriteFile(Some(path.to_string()))])
+        };
+
+        // ETH0 AND TUN0 TOGETHER FIT ONLY INTO PCAPNG
+        let link_types = [capture::LINKTYPE_ETHERNET, capture::LINKTYPE_RAW];
+
+        assert_eq!(output_link_type(&settings("out.pcapng"), &link_types), 
+                   Some(capture::LINKTYPE_ETHERNET));
+        assert_eq!(output_link_type(&settings("out.pcap"), &link_types), None);
+        assert_eq!(output_link_type(&settings("out.pcap"), &link_types[1..]), 
+                   Some(capture::LINKTYPE_RAW));
+        assert_eq!(output_link_type(&settings("out.pcap"), &[]), 
+                   Some(capture::LINKTYPE_ETHERNET));
+    }
+
+    // END-TO-END TESTS WITHOUT ROOT: THE SYNTHETIC FRAMES GO THROUGH THE SAME LOOP
+    // THE FRAMES AND THE COUNTING ARE SHARED WITH THE TESTS OF THE PROTOCOLS
+
+    const ETHERNET_IPV4 : [u8; 14] = [0x02, 0x00, 0x00, 0x00, 0x00, 0x02, 0x02, 0x00, 
+                                      0x00, 0x00, 0x00, 0x01, 0x08, 0x00];
+    const ETHERNET_IPV6 : [u8; 14] = [0x02, 0x00, 0x00, 0x00, 0x00, 0x02, 0x02, 0x00, 
+                                      0x00, 0x00, 0x00, 0x01, 0x86, 0xdd];
+    const ETHERNET_ARP  : [u8; 14] = [0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x02, 0x00, 
+                                      0x00, 0x00, 0x00, 0x01, 0x08, 0x06];
+
+    // 10.0.0.1:1234 -> 10.0.0.2:53 OVER UDP
+    pub(super) fn ipv4_udp_frame() -> Vec<u8> {
+        [&ETHERNET_IPV4[..],
+         &[0x45, 0x00, 0x00, 0x1c, 0x00, 0x00, 0x40, 0x00, 0x40, 0x11, 0x00, 0x00,
+           0x0a, 0x00, 0x00, 0x01, 0x0a, 0x00, 0x00, 0x02],
+         &[0x04, 0xd2, 0x00, 0x35, 0x00, 0x08, 0x00, 0x00]].concat()
+    }
+
+    // 10.0.0.1:40000 -> 10.0.0.3:443 OVER TCP, SYN
+    pub(super) fn ipv4_tcp_frame() -> Vec<u8> {
+        [&ETHERNET_IPV4[..],
+         &[0x45, 0x00, 0x00, 0x28, 0x00, 0x00, 0x40, 0x00, 0x40, 0x06, 0x00, 0x00,
+           0x0a, 0x00, 0x00, 0x01, 0x0a, 0x00, 0x00, 0x03],
+         &[0x9c, 0x40, 0x01, 0xbb, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+           0x50, 0x02, 0xff, 0xff, 0x00, 0x00, 0x00, 0x00]].concat()
+    }
+
+    // ::1:1234 -> ::2:53 OVER UDP
+    pub(super) fn ipv6_udp_frame() -> Vec<u8> {
+        let mut source      = [0u8; 16];
+        let mut destination = [0u8; 16];
+        source[15] = 1;
+        destination[15] = 2;
+
+        [&ETHERNET_IPV6[..],
+         &[0x60, 0x00, 0x00, 0x00, 0x00, 0x08, 0x11, 0x40],
+         &source, &destination,
+         &[0x04, 0xd2, 0x00, 0x35, 0x00, 0x08, 0x00, 0x00]].concat()
+    }
+
+    // WHO HAS 10.0.0.2? TELL 10.0.0.1
+    pub(super) fn arp_request_frame() -> Vec<u8> {
+        [&ETHERNET_ARP[..],
+         &[0x00, 0x01, 0x08, 0x00, 0x06, 0x04, 0x00, 0x01,
+           0x02, 0x00, 0x00, 0x00, 0x00, 0x01, 0x0a, 0x00, 0x00, 0x01,
+           0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x0a, 0x00, 0x00, 0x02]].concat()
+    }
+
+    pub(super) fn all_frames() -> Vec<Vec<u8>> {
+        vec![ipv4_udp_frame(), ipv4_tcp_frame(), ipv6_udp_frame(), arp_request_frame()]
+    }
+
+    // THE WRITER WHICH LETS THE TEST LOOK INTO THE SAVED FILE
+
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl std::io::Write for SharedBuffer {
+        fn write(&mut self, buf : &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn count_matched(frames : Vec<Vec<u8>>, filters : Filters, 
+                     stop : StopConditions) -> (u64, u64) {
+        count_matched_on(capture::LINKTYPE_ETHERNET, frames, filters, stop)
+    }
+
+    // THE FILTERS MADE OF THE FLAGS THE SAME WAY AS FROM THE COMMAND LINE
 
-        assert_eq!(w_ints.len(), 1);
-        assert_eq!(w_ports.len(), 3);
-        assert_eq!(w_ips.len(), 1);
-        assert_eq!(w_protocols.len(), 0);
+    fn filters_from(params : Vec<Parameters>) -> Filters {
+        consider_parameters(vec![], params).filters
+    }
+
+    pub(super) fn count_matched_by(frames : Vec<Vec<u8>>, params : Vec<Parameters>) 
+                                                                        -> (u64, u64) {
+        count_matched(frames, filters_from(params), StopConditions::default())
+    }
+
+    fn count_matched_on(link_type : u32, frames : Vec<Vec<u8>>, filters : Filters, 
+                        stop : StopConditions) -> (u64, u64) {
+        let source = MemorySource::new("mem0", link_type, frames);
+        let output = Arc::new(Output { print : false, writer : None, rules : RuleOutput::default() });
+
+        let stats = run_sources(vec![Box::new(source)], filters, output,
+                                Arc::new(Control::new(stop)));
+
+        (stats[0].seen.load(Ordering::Relaxed), stats[0].matched.load(Ordering::Relaxed))
     }
 
-    /*
     #[test]
-    fn test_find_packets_alone() {
-        let params = vec![Parameters::IpAddress(vec!["127.0.0.1".to_string()])];
+    fn test_memory_source_filters() {
+        let stop = StopConditions::default();
+
+        assert_eq!(count_matched(all_frames(), Filters::default(), stop), (4, 4));
+
+        let filters = Filters { protocols : vec![Protocol::UDP], ..Default::default() };
+        assert_eq!(count_matched(all_frames(), filters, stop), (4, 2));
+
+        let filters = Filters { protocols : vec![Protocol::ARP, Protocol::IPv6], 
+                                ..Default::default() };
+        assert_eq!(count_matched(all_frames(), filters, stop), (4, 2));
+
+        let filters = Filters { ips : vec![IpFilter::parse("10.0.0.3").unwrap()], 
+                                protocols : vec![Protocol::IPv4], ..Default::default() };
+        assert_eq!(count_matched(all_frames(), filters, stop), (4, 1));
+
+        let filters = Filters { ips : vec![IpFilter::parse("10.0.0.0/30").unwrap()], 
+                                protocols : vec![Protocol::IPv4], ..Default::default() };
+        assert_eq!(count_matched(all_frames(), filters, stop), (4, 2));
+
+        let filters = Filters { ports : vec![PortRange { low : 50, high : 60 }], 
+                                protocols : vec![Protocol::UDP], ..Default::default() };
+        assert_eq!(count_matched(all_frames(), filters, stop), (4, 2));
+
+        let filters = Filters { macs : vec![MacFilter::parse("02:00:00:00:00:02").unwrap()], 
+                                ..Default::default() };
+        assert_eq!(count_matched(all_frames(), filters, stop), (4, 3));
+
+        let filters = Filters { macs : vec![MacFilter::parse("02:00:*").unwrap()],
+                                ethertypes : vec![0x0806, 0x88cc], ..Default::default() };
+        assert_eq!(count_matched(all_frames(), filters, stop), (4, 1));
+
+        let filters = Filters { arp_op : Some(2), protocols : vec![Protocol::ARP], 
+                                ..Default::default() };
+        assert_eq!(count_matched(all_frames(), filters, stop), (4, 0));
+    }
+
+    #[test]
+    fn test_memory_source_expression() {
+        let stop = StopConditions::default();
+        let with = |text : &str| Filters { expression : Some(expression::parse(text).unwrap()), 
+                                           ..Default::default() };
+
+        assert_eq!(count_matched(all_frames(), with("udp and port 53"), stop), (4, 2));
+        assert_eq!(count_matched(all_frames(), with("not udp"), stop), (4, 2));
+        assert_eq!(count_matched(all_frames(), with("tcp and (port 443 or port 8443) \
+                                                    and not host 10.0.0.5"), stop), (4, 1));
+        assert_eq!(count_matched(all_frames(), with("host 10.0.0.1 or arp op 1"), stop), 
+                   (4, 3));
+        assert_eq!(count_matched(all_frames(), with("ip6 and host ::2"), stop), (4, 1));
+
+        // THE EXPRESSION AND THE FLAGS MUST BOTH HOLD
+        let filters = Filters { protocols : vec![Protocol::IPv4], ..with("udp or arp") };
+        assert_eq!(count_matched(all_frames(), filters, stop), (4, 1));
+    }
+
+    #[test]
+    fn test_memory_source_directions() {
+        let stop = StopConditions::default();
+        let with = |text : &str| Filters { expression : Some(expression::parse(text).unwrap()),
+                                           ..Default::default() };
+
+        assert_eq!(count_matched(all_frames(), with("src host 10.0.0.1"), stop), (4, 3));
+        assert_eq!(count_matched(all_frames(), with("dst host 10.0.0.1"), stop), (4, 0));
+        assert_eq!(count_matched(all_frames(), with("dst port 53"), stop), (4, 2));
+        assert_eq!(count_matched(all_frames(), with("src port 53"), stop), (4, 0));
+
+        // THE FLAGS BECOME THE SAME EXPRESSIONS
+        let params = vec![Parameters::SourceIp(vec![IpFilter::parse("10.0.0.0/24").unwrap()]),
+                          Parameters::DestinationPort(vec![PortRange::single(443),
+                                                          PortRange::single(53)])];
+        let settings = consider_parameters(vec![], params);
+        assert_eq!(count_matched(all_frames(), settings.filters, stop), (4, 2));
+    }
+
+    #[test]
+    fn test_memory_source_header_fields() {
+        let stop = StopConditions::default();
+        let with = |field : Field, values : &[&str]| {
+            let ranges = values.iter().map(|value| FieldRange::parse(value, field).unwrap());
+            consider_parameters(vec![], vec![Parameters::HeaderField(field, ranges.collect())])
+                .filters
+        };
+
+        assert_eq!(count_matched(all_frames(), with(Field::Ttl, &["64"]), stop), (4, 3));
+        assert_eq!(count_matched(all_frames(), with(Field::Length, &["40-48"]), stop), (4, 2));
+        assert_eq!(count_matched(all_frames(), with(Field::Length, &["<30", ">45"]), stop), 
+                   (4, 2));
+        assert_eq!(count_matched(all_frames(), with(Field::DontFragment, &["1"]), stop), (4, 2));
+        assert_eq!(count_matched(all_frames(), with(Field::Dscp, &["ef"]), stop), (4, 0));
+        assert_eq!(count_matched(all_frames(), with(Field::FrameLength, &["42"]), stop), (4, 2));
+    }
+
+    #[test]
+    fn test_memory_source_exclusions() {
+        let stop = StopConditions::default();
+
+        let params = vec![Parameters::ExcludeProtocol(vec![Protocol::ARP, Protocol::IPv6])];
+        assert_eq!(count_matched_by(all_frames(), params), (4, 2));
+
+        let params = vec![Parameters::ExcludePort(vec![PortRange::single(53)])];
+        assert_eq!(count_matched_by(all_frames(), params), (4, 2));
+
+        let params = vec![Parameters::ExcludeIp(vec![IpFilter::parse("10.0.0.3").unwrap()]),
+                          Parameters::Protocol(vec![Protocol::IPv4])];
+        assert_eq!(count_matched_by(all_frames(), params), (4, 1));
+
+        // THE OWN SSH SESSION, SEEN FROM EITHER END
+        let ignore = |connection : &str| {
+            let mut filters = Filters::default();
+            let flow = ssh::flow_expression(connection).unwrap();
+            filters.require(Expression::Not(Box::new(flow)));
+            filters
+        };
+
+        assert_eq!(count_matched(all_frames(), ignore("10.0.0.1 40000 10.0.0.3 443"), stop), 
+                   (4, 3));
+        assert_eq!(count_matched(all_frames(), ignore("10.0.0.3 443 10.0.0.1 40000"), stop), 
+                   (4, 3));
+        assert_eq!(count_matched(all_frames(), ignore("10.0.0.1 40000 10.0.0.2 443"), stop), 
+                   (4, 4));
+    }
+
+    #[test]
+    fn test_memory_source_rules() {
+        let directory = std::env::temp_dir().join(format!("ohrest_rules_{}", 
+                                                           std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+
+        let saved = directory.join("syn.pcap");
+        let text  = format!("udp => count dns\n\
+                             port 53 => count dns\n\
+                             tcp[syn] => save {}\n\
+                             tcp[rst] => alert RESET\n\
+                             => count all\n", saved.display());
+
+        let run = |filters : Filters| {
+            let rules  = RuleOutput::open(&filters.rules, capture::LINKTYPE_ETHERNET).unwrap();
+            let output = Arc::new(Output { print : false, writer : None, rules });
+            let source = MemorySource::new("mem0", capture::LINKTYPE_ETHERNET, all_frames());
+
+            let stats = run_sources(vec![Box::new(source)], filters, Arc::clone(&output),
+                                    Arc::new(Control::new(StopConditions::default())));
+            output.finish();
+
+            (stats[0].matched.load(Ordering::Relaxed), output.rules.counters())
+        };
+
+        // THE PACKET MATCHING TWO RULES COUNTING UNDER ONE NAME IS COUNTED ONCE
+        let filters = Filters { rules : rules::parse_rules(&text).unwrap(), 
+                                ..Default::default() };
+        assert_eq!(run(filters), (4, vec![("dns".to_string(), 2), ("all".to_string(), 4)]));
+
+        let mut reader = PcapReader::new(std::fs::File::open(&saved).unwrap()).unwrap();
+        assert_eq!(reader.next_record().unwrap().unwrap().data, ipv4_tcp_frame());
+        assert!(reader.next_record().unwrap().is_none());
+
+        // THE FLAGS STILL HOLD FOR ALL THE RULES, THE PACKET NO RULE MATCHES IS NOT WANTED
+        let filters = Filters { protocols : vec![Protocol::IPv4],
+                                rules : rules::parse_rules("udp or arp => count x").unwrap(), 
+                                ..Default::default() };
+        assert_eq!(run(filters), (1, vec![("x".to_string(), 1)]));
+
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn test_memory_source_link_types() {
+        let stop = StopConditions::default();
+
+        // THE SAME IP PACKETS WITHOUT THE ETHERNET HEADER (TUN/WIREGUARD)
+        let raw : Vec<Vec<u8>> = vec![ipv4_udp_frame(), ipv4_tcp_frame(), ipv6_udp_frame()]
+            .into_iter().map(|frame| frame[14..].to_vec()).collect();
+
+        let filters = Filters { protocols : vec![Protocol::UDP], ..Default::default() };
+        assert_eq!(count_matched_on(capture::LINKTYPE_RAW, raw.clone(), filters, stop), 
+                   (3, 2));
+
+        // AND BEHIND THE LITTLE ENDIAN BSD LOOPBACK HEADER
+        let null : Vec<Vec<u8>> = raw.into_iter()
+            .map(|packet| [&[0x02, 0x00, 0x00, 0x00][..], &packet[..]].concat()).collect();
+
+        let filters = Filters { ips : vec![IpFilter::parse("10.0.0.2").unwrap()], 
+                                protocols : vec![Protocol::IPv4], ..Default::default() };
+        assert_eq!(count_matched_on(capture::LINKTYPE_NULL, null, filters, stop), (3, 1));
+    }
+
+    #[test]
+    fn test_memory_source_decode_failures() {
+        let frames = vec![ipv4_udp_frame(), vec![0x02, 0x00],
+                          [&ETHERNET_IPV4[..], &[0x45, 0x00]].concat(),
+                          ipv4_tcp_frame()[..40].to_vec()];
+
+        let failures = |filters : Filters| {
+            let source = MemorySource::new("mem0", capture::LINKTYPE_ETHERNET, frames.clone());
+            let output = Arc::new(Output { print : false, writer : None, 
+                                           rules : RuleOutput::default() });
+
+            let stats = run_sources(vec![Box::new(source)], filters, output,
+                                    Arc::new(Control::new(StopConditions::default())));
+
+            assert_eq!(stats[0].seen.load(Ordering::Relaxed), 4);
+            stats[0].decode_failures.iter().map(|count| count.load(Ordering::Relaxed))
+                                           .collect::<Vec<u64>>()
+        };
+
+        assert_eq!(failures(Filters::default()), vec![1, 1, 1]);
+
+        // THE FILTERED OUT PACKETS ARE NOT BROKEN, THE BROKEN ONES ARE FOUND ANYWAY
+        let ports = Filters { ports : vec![PortRange::single(53)], ..Default::default() };
+        assert_eq!(failures(ports), vec![1, 1, 1]);
+
+        let ips = Filters { ips : vec![IpFilter::parse("10.9.9.9").unwrap()], ..Default::default() };
+        assert_eq!(failures(ips), vec![1, 1, 0]);
+    }
+
+    #[test]
+    fn test_memory_source_count() {
+        let stop = StopConditions { count : Some(3), duration : None };
+
+        // THE SOURCE IS NOT READ ANY FURTHER ONCE THE COUNT IS REACHED
+        assert_eq!(count_matched(all_frames(), Filters::default(), stop), (3, 3));
+    }
+
+    #[test]
+    fn test_memory_source_output() {
+        let buffer = SharedBuffer::default();
+        let pcap   = PcapWriter::new(buffer.clone(), capture::LINKTYPE_ETHERNET).unwrap();
+        let writer : Box<dyn CaptureWriter> = Box::new(pcap);
+
+        let output = Arc::new(Output { print : false, writer : Some(Mutex::new(writer)),
+                                         rules : RuleOutput::default() });
+        let source = MemorySource::new("mem0", capture::LINKTYPE_ETHERNET, all_frames());
+        let filters = Filters { protocols : vec![Protocol::TCP], ..Default::default() };
+
+        run_sources(vec![Box::new(source)], filters, Arc::clone(&output),
+                    Arc::new(Control::new(StopConditions::default())));
+        output.finish();
+
+        let saved = buffer.0.lock().unwrap().clone();
+        let mut reader = PcapReader::new(std::io::Cursor::new(saved)).unwrap();
 
-        find_packets(params);
+        assert_eq!(reader.next_record().unwrap().unwrap().data, ipv4_tcp_frame());
+        assert!(reader.next_record().unwrap().is_none());
     }
-    */
 }
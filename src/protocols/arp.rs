@@ -1,9 +1,21 @@
+use std::net::IpAddr;
+
 use crate::Parameters;
-use pnet::packet::arp::ArpPacket;
+use pnet::packet::arp::{ArpOperations, ArpPacket};
+use pnet::util::MacAddr;
 
 use crate::layers::UpperProtocol;
 use crate::{print_program_name, get_color};
-use crate::filtering::check_arp_operation;
+use crate::filtering::{NetworkLayer, arp_operation_name, check_arp_operation};
+
+// THE ADDRESSES ASKED AND TOLD ABOUT ARE THE HOSTS OF THE ARP PACKET
+
+impl NetworkLayer for ArpPacket<'_> {
+    fn get_source_dest(&self) -> (IpAddr, IpAddr) {
+        (IpAddr::V4(self.get_sender_proto_addr()), 
+        IpAddr::V4(self.get_target_proto_addr()))
+    }
+}
 
 pub fn check_and_get_next_layer(packet : &[u8], params : Parameters) 
                                                         -> Option<(UpperProtocol, Vec<u8>)> {
@@ -17,8 +29,35 @@ pub fn check_and_get_next_layer(packet : &[u8], params : Parameters)
     None 
 }
 
+// THE PACKETS WHICH TELL ABOUT THE ADDRESS INSTEAD OF ASKING FOR IT (RFC 5227),
+// THEY ARE WHAT IS SEEN ON THE IP CONFLICTS AND WHEN THE FAILOVER MOVES THE ADDRESS:
+// THE PROBE CHECKS IF THE ADDRESS IS FREE BEFORE TAKING IT (THE SENDER IS 0.0.0.0),
+// THE ANNOUNCEMENT CLAIMS IT (THE SENDER ASKS FOR ITSELF),
+// THE GRATUITOUS REPLY CLAIMS IT WITHOUT BEING ASKED
+
+pub fn classify(packet : &ArpPacket) -> Option<&'static str> {
+    let (sender, target) = (packet.get_sender_proto_addr(), packet.get_target_proto_addr());
+    let operation = packet.get_operation();
+
+    if operation == ArpOperations::Request && sender.is_unspecified() && 
+       packet.get_target_hw_addr() == MacAddr::zero() {
+        return Some("PROBE");
+    }
+
+    if sender != target || sender.is_unspecified() {
+        return None;
+    }
+
+    match operation {
+        ArpOperations::Request => Some("GRATUITOUS ANNOUNCEMENT"),
+        ArpOperations::Reply   => Some("GRATUITOUS REPLY"),
+        _ => None,
+    }
+}
+
 pub fn print_output(packet : Vec<u8>) {
     let packet = ArpPacket::new(&packet).unwrap();
+    let operation = packet.get_operation().0;
 
     print_program_name();
     println!("\x1b[1m> > > ARP PACKET\x1b[0m");
@@ -28,9 +67,10 @@ pub fn print_output(packet : Vec<u8>) {
              get_color(4), get_color(0), packet.get_protocol_type(),
              get_color(5), get_color(0), packet.get_hw_addr_len());
 
-    println!("{}Protocol Len:{} {}   {}Operation:{} {} ", 
-             get_color(2), get_color(0), packet.get_protocol_type().0, 
-             get_color(4), get_color(0), packet.get_operation().0);  
+    println!("{}Protocol Len:{} {}   {}Operation:{} {} ({}) ", 
+             get_color(2), get_color(0), packet.get_proto_addr_len(), 
+             get_color(4), get_color(0), operation, 
+             arp_operation_name(operation).unwrap_or("unknown"));  
 
     println!("{}Sender HW Address:{} {}   {}Sender Protocol Address:{} {}", 
         get_color(2), get_color(0), packet.get_sender_hw_addr(),
@@ -39,20 +79,28 @@ pub fn print_output(packet : Vec<u8>) {
     println!("{}Target HW Address:{} {}   {}Target Protocol Address:{} {}",
         get_color(4), get_color(0), packet.get_target_hw_addr(),
         get_color(5), get_color(0), packet.get_target_proto_addr());
+
+    if let Some(kind) = classify(&packet) {
+        println!("{}Kind:{} {} OF {} BY {}", get_color(3), get_color(0), kind, 
+                 packet.get_target_proto_addr(), packet.get_sender_hw_addr());
+    }
 }
 
 
 
 #[cfg(test)]
-
 mod test {
     use super::*;
+    use crate::filtering::{Direction, IpFilter, MacFilter};
+    use crate::filtering::expression;
+    use crate::protocols::tests::{all_frames, count_matched_by};
 
     #[test]
     fn test_check_and_get_next_layer() {
         let packet = [0x00, 0x01, 0x08, 0x00, 0x06, 0x04, 0x00, 0x01, 
                       0x26, 0x57, 0x8e, 0xd6, 0x4a, 0x7a, 0x91, 0xae,
-                      0xa1, 0xd3];
+                      0xa1, 0xd3, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+                      0xc0, 0xa8, 0x00, 0x01];
 
         let params = Parameters::NoParameter;
 
@@ -60,4 +108,48 @@ mod test {
 
         assert!(res.is_some());
     }
+
+    #[test]
+    fn test_classify() {
+        // WHO HAS 192.168.0.1? TELL 192.168.0.2
+        let mut packet = vec![0x00, 0x01, 0x08, 0x00, 0x06, 0x04, 0x00, 0x01, 
+                              0x26, 0x57, 0x8e, 0xd6, 0x4a, 0x7a, 0xc0, 0xa8,
+                              0x00, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+                              0xc0, 0xa8, 0x00, 0x01];
+        let kind = |packet : &[u8]| classify(&ArpPacket::new(packet).unwrap());
+
+        assert_eq!(kind(&packet), None);
+
+        // THE SENDER ASKS FOR ITS OWN ADDRESS
+        packet[17] = 0x01;
+        assert_eq!(kind(&packet), Some("GRATUITOUS ANNOUNCEMENT"));
+
+        packet[7] = 0x02;
+        assert_eq!(kind(&packet), Some("GRATUITOUS REPLY"));
+
+        // THE SENDER HAS NO ADDRESS YET
+        packet[7] = 0x01;
+        packet[14..18].copy_from_slice(&[0, 0, 0, 0]);
+        assert_eq!(kind(&packet), Some("PROBE"));
+    }
+
+    #[test]
+    fn test_memory_source_arp() {
+        // THE REQUEST FROM 02:00:00:00:00:01 (10.0.0.1) FOR 10.0.0.2
+        let ip = |text : &str| vec![IpFilter::parse(text).unwrap()];
+
+        let params = vec![Parameters::ArpIp(Direction::Destination, ip("10.0.0.2"))];
+        assert_eq!(count_matched_by(all_frames(), params), (4, 1));
+
+        let params = vec![Parameters::ArpIp(Direction::Source, ip("10.0.0.2"))];
+        assert_eq!(count_matched_by(all_frames(), params), (4, 0));
+
+        let params = vec![Parameters::ArpMac(Direction::Source, 
+                                             vec![MacFilter::parse("02:00:*").unwrap()]),
+                          Parameters::ArpOperation(Some(1))];
+        assert_eq!(count_matched_by(all_frames(), params), (4, 1));
+
+        let params = vec![Parameters::Filter(expression::parse("arp op reply").ok())];
+        assert_eq!(count_matched_by(all_frames(), params), (4, 0));
+    }
 }
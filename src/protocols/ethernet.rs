@@ -1,20 +1,76 @@
 use pnet::packet::Packet;
-use pnet::packet::ethernet::EthernetPacket;
+use pnet::packet::ethernet::{EtherType, EthernetPacket};
+use pnet::packet::vlan::VlanPacket;
 
 use crate::layers::UpperProtocol;
 use crate::Parameters;
+use crate::filtering::check_macs;
 
 use crate::{print_program_name, get_color};
 
-pub fn check_and_get_next_layer(packet : &[u8], _p : Parameters) 
+// THE ETHERTYPES SAYING THE VLAN TAG GOES NEXT: 802.1Q, 802.1ad (QinQ)
+// AND THE OLDER QinQ ONE, STILL USED BY SOME SWITCHES
+
+pub const VLAN_ETHERTYPES : [u16; 3] = [0x8100, 0x88a8, 0x9100];
+
+// MORE TAGS THAN THIS ARE NOT A REAL FRAME
+
+const MAX_VLAN_TAGS : usize = 8;
+
+const ETHERNET_HEADER_LENGTH : usize = 14;
+const VLAN_TAG_LENGTH        : usize = 4;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct VlanTag {
+    pub tpid     : u16,
+    pub id       : u16,
+    pub priority : u8,
+    pub drop     : bool,
+}
+
+pub fn is_vlan(ethertype : EtherType) -> bool {
+    VLAN_ETHERTYPES.contains(&ethertype.0)
+}
+
+// THE TAGS OF THE FRAME (THE OUTER ONE FIRST), THE ETHERTYPE AFTER THEM
+// AND WHERE THE DATA STARTS, NONE IF THE TAGS ARE CUT
+
+pub fn split_vlan_tags(packet : &[u8]) -> Option<(Vec<VlanTag>, EtherType, usize)> {
+    let ether = EthernetPacket::new(packet)?;
+
+    let mut tags      : Vec<VlanTag> = vec![];
+    let mut ethertype : EtherType    = ether.get_ethertype();
+    let mut offset    : usize        = ETHERNET_HEADER_LENGTH;
+
+    while is_vlan(ethertype) && tags.len() < MAX_VLAN_TAGS {
+        let tag = VlanPacket::new(&packet[offset..])?;
+
+        tags.push(VlanTag { tpid     : ethertype.0,
+                            id       : tag.get_vlan_identifier(),
+                            priority : tag.get_priority_code_point().0,
+                            drop     : tag.get_drop_eligible_indicator() == 1 });
+
+        ethertype = tag.get_ethertype();
+        offset   += VLAN_TAG_LENGTH;
+    }
+
+    Some((tags, ethertype, offset))
+}
+
+// THE TAGGED FRAMES GO ON WITH THE ETHERTYPE INSIDE THE TAGS
+
+pub fn check_and_get_next_layer(packet : &[u8], macs : Parameters) 
                                             -> Option<(UpperProtocol, Vec<u8>)> {
 
-    if let Some(ether) = EthernetPacket::new(packet) {
-        return Some((UpperProtocol::Layer1(ether.get_ethertype()), 
-                ether.payload().to_vec()));
+    let ether = EthernetPacket::new(packet)?;
+
+    if !check_macs(ether.get_source(), ether.get_destination(), macs) {
+        return None;
     }
 
-    None
+    let (_, ethertype, offset) = split_vlan_tags(packet)?;
+
+    Some((UpperProtocol::Layer1(ethertype), packet[offset..].to_vec()))
 }
 
 pub fn print_output(packet : Vec<u8>) {
@@ -28,4 +84,87 @@ pub fn print_output(packet : Vec<u8>) {
              get_color(2), get_color(0), packet.get_source(), 
              get_color(5), get_color(0), packet.get_ethertype(),
              get_color(2), get_color(0), packet.payload().len());
+
+    let Some((tags, ethertype, _)) = split_vlan_tags(packet.packet()) else {
+        return;
+    };
+
+    for tag in &tags {
+        println!("{}VLAN:{} {}   {}Priority:{} {}   {}Drop Eligible:{} {}   {}TPID:{} 0x{:04x}",
+                 get_color(6), get_color(0), tag.id,
+                 get_color(4), get_color(0), tag.priority,
+                 get_color(2), get_color(0), u8::from(tag.drop),
+                 get_color(5), get_color(0), tag.tpid);
+    }
+
+    if !tags.is_empty() {
+        println!("{}Inner Type:{} {}", get_color(5), get_color(0), ethertype);
+    }
+}
+
+
+
+// TESTS
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pnet::packet::ethernet::EtherTypes;
+    use crate::filtering::{PortRange, VlanFilter};
+    use crate::filtering::expression;
+    use crate::protocols::tests::{arp_request_frame, count_matched_by, ipv4_tcp_frame, 
+                                  ipv4_udp_frame};
+
+    #[test]
+    fn test_vlan_tags() {
+        // QinQ: THE OUTER TAG 100 (PRIORITY 5), THE INNER TAG 200 (DROP ELIGIBLE), THEN IPv4
+        let frame = [&[0x02, 0, 0, 0, 0, 2, 0x02, 0, 0, 0, 0, 1, 0x88, 0xa8][..],
+                     &[0xa0, 0x64, 0x81, 0x00], &[0x10, 0xc8, 0x08, 0x00],
+                     &[0x45, 0x00]].concat();
+
+        let (tags, ethertype, offset) = split_vlan_tags(&frame).unwrap();
+
+        assert_eq!(tags, vec![VlanTag { tpid : 0x88a8, id : 100, priority : 5, drop : false },
+                              VlanTag { tpid : 0x8100, id : 200, priority : 0, drop : true }]);
+        assert_eq!(ethertype, EtherTypes::Ipv4);
+        assert_eq!(offset, 22);
+
+        let (next, data) = check_and_get_next_layer(&frame, Parameters::NoParameter).unwrap();
+        assert!(matches!(next, UpperProtocol::Layer1(EtherTypes::Ipv4)));
+        assert_eq!(data, vec![0x45, 0x00]);
+
+        // THE TAG CUT IN THE MIDDLE
+        assert!(split_vlan_tags(&frame[..16]).is_none());
+    }
+
+    #[test]
+    fn test_memory_source_vlan() {
+        // THE UDP PACKET IN VLAN 100 INSIDE THE SERVICE VLAN 20 (QinQ), THE TCP ONE IN VLAN 7
+        let udp = ipv4_udp_frame();
+        let tcp = ipv4_tcp_frame();
+
+        let frames = vec![[&udp[..12], &[0x88, 0xa8, 0x00, 0x14, 0x81, 0x00, 0x00, 0x64], 
+                           &udp[12..]].concat(),
+                          [&tcp[..12], &[0x81, 0x00, 0x00, 0x07], &tcp[12..]].concat(),
+                          arp_request_frame()];
+
+        let vlans = |text : &str| vec![VlanFilter::parse(text).unwrap()];
+
+        let params = vec![Parameters::Vlan(vlans("100"))];
+        assert_eq!(count_matched_by(frames.clone(), params), (3, 1));
+
+        let params = vec![Parameters::Vlan(vlans("1-20")), 
+                          Parameters::Port(vec![PortRange::single(53)])];
+        assert_eq!(count_matched_by(frames.clone(), params), (3, 1));
+
+        let params = vec![Parameters::Filter(expression::parse("vlan and ip").ok())];
+        assert_eq!(count_matched_by(frames.clone(), params), (3, 2));
+
+        // THE TYPE INSIDE THE TAGS OR THE TYPE OF ANY TAG
+        let params = vec![Parameters::EtherType(vec![0x88a8])];
+        assert_eq!(count_matched_by(frames.clone(), params), (3, 1));
+
+        let params = vec![Parameters::EtherType(vec![0x0800])];
+        assert_eq!(count_matched_by(frames, params), (3, 2));
+    }
 }
@@ -0,0 +1,194 @@
+// ICMP IS CARRIED BY IPv4 LIKE TCP AND UDP, BUT HAS NO PORTS:
+// THE TYPE AND THE CODE TELL WHAT THE MESSAGE IS, THE 4 BYTES AFTER THE CHECKSUM
+// DEPEND ON THE TYPE, THE REST IS THE DATA (OR THE BEGINNING OF THE PACKET WHICH FAILED)
+
+use std::net::Ipv4Addr;
+
+use pnet::packet::icmp::IcmpPacket;
+use pnet::packet::ip::IpNextHeaderProtocols;
+use pnet::packet::ipv4::Ipv4Packet;
+
+use crate::{print_program_name, get_color};
+use crate::layers::UpperProtocol;
+use crate::Parameters;
+
+use crate::filtering::{icmp_type_name, icmp_code_name};
+
+pub const HEADER_LENGTH : usize = 8;
+
+const ECHO_REPLY        : u8 = 0;
+const UNREACHABLE       : u8 = 3;
+const SOURCE_QUENCH     : u8 = 4;
+const REDIRECT          : u8 = 5;
+const ECHO_REQUEST      : u8 = 8;
+const TIME_EXCEEDED     : u8 = 11;
+const PARAMETER_PROBLEM : u8 = 12;
+
+const FRAGMENTATION_NEEDED : u8 = 4;
+
+// THE ERRORS CARRY THE IP HEADER OF THE PACKET WHICH CAUSED THEM AND 8 BYTES AFTER IT
+
+pub fn is_error(icmp_type : u8) -> bool {
+    matches!(icmp_type, UNREACHABLE | SOURCE_QUENCH | REDIRECT | TIME_EXCEEDED |
+                        PARAMETER_PROBLEM)
+}
+
+// THE ECHO, THE TIMESTAMP, THE INFORMATION AND THE MASK MESSAGES HAVE THE IDENTIFIER
+// AND THE SEQUENCE NUMBER, TO PAIR THE REQUEST WITH ITS REPLY
+
+fn has_identifier(icmp_type : u8) -> bool {
+    matches!(icmp_type, ECHO_REPLY | ECHO_REQUEST | 13..=18)
+}
+
+// THE TYPE OF THE MESSAGE IS PASSED ON, THE DATA AFTER THE HEADER IS THE PAYLOAD
+// ICMP HAS NO PORTS, THE PACKET CANNOT MATCH THEM
+
+pub fn check_and_get_next_layer(packet : &[u8], p : Parameters)
+                                            -> Option<(UpperProtocol, Vec<u8>)> {
+    if let Parameters::Port(ports) = p {
+        if !ports.is_empty() {
+            return None;
+        }
+    }
+
+    let icmp = IcmpPacket::new(packet).filter(|_| packet.len() >= HEADER_LENGTH)?;
+
+    Some((UpperProtocol::Layer2(icmp.get_icmp_type().0), packet[HEADER_LENGTH..].to_vec()))
+}
+
+// "3 (unreachable)", ONLY THE NUMBER IF THE NAME IS NOT KNOWN
+
+pub fn describe(value : u8, name : Option<&str>) -> String {
+    match name {
+        Some(name) => format!("{} ({})", value, name),
+        None => value.to_string(),
+    }
+}
+
+// THE PACKET WHICH FAILED IN ONE LINE: "Udp 10.0.0.1:1234 -> 10.0.0.2:53   TTL: 1   ID: 7"
+
+pub fn describe_original(data : &[u8]) -> Option<String> {
+    let ip = Ipv4Packet::new(data).filter(|packet| packet.get_version() == 4)?;
+
+    let after = data.get(usize::from(ip.get_header_length()) * 4..).unwrap_or_default();
+    let half  = |position : usize| u16::from_be_bytes([after[position], after[position + 1]]);
+
+    let (source, destination) = (ip.get_source(), ip.get_destination());
+    let protocol = ip.get_next_level_protocol();
+
+    let endpoints = match protocol {
+        IpNextHeaderProtocols::Tcp | IpNextHeaderProtocols::Udp if after.len() >= 4 => {
+            format!("{}:{} -> {}:{}", source, half(0), destination, half(2))
+        },
+        IpNextHeaderProtocols::Icmp if after.len() >= HEADER_LENGTH => {
+            format!("{} -> {} ({}, ID {}, SEQ {})", source, destination,
+                    describe(after[0], icmp_type_name(after[0])), half(4), half(6))
+        },
+        _ => format!("{} -> {}", source, destination),
+    };
+
+    Some(format!("{} {}   TTL: {}   ID: {}", protocol, endpoints, ip.get_ttl(),
+                 ip.get_identification()))
+}
+
+pub fn print_output(packet : Vec<u8>) {
+    let icmp = IcmpPacket::new(&packet).unwrap();
+
+    let (icmp_type, code) = (icmp.get_icmp_type().0, icmp.get_icmp_code().0);
+    let half = |position : usize| u16::from_be_bytes([packet[position], packet[position + 1]]);
+
+    print_program_name();
+
+    println!("{}> > > > ICMP PACKET{}", get_color(1), get_color(0));
+    println!("Type: {}   Code: {}   Checksum: {}", 
+             describe(icmp_type, icmp_type_name(icmp_type)),
+             describe(code, icmp_code_name(icmp_type, code)), icmp.get_checksum());
+
+    match icmp_type {
+        _ if has_identifier(icmp_type) => {
+            println!("Identifier: {}   Sequence: {}   Data: {} bytes", half(4), half(6),
+                     packet.len() - HEADER_LENGTH);
+        },
+        REDIRECT => {
+            println!("Gateway: {}", Ipv4Addr::new(packet[4], packet[5], packet[6], packet[7]));
+        },
+        UNREACHABLE if code == FRAGMENTATION_NEEDED => println!("Next-Hop MTU: {}", half(6)),
+        PARAMETER_PROBLEM => println!("Pointer: {}", packet[4]),
+        _ => (),
+    }
+
+    if is_error(icmp_type) {
+        match describe_original(&packet[HEADER_LENGTH..]) {
+            Some(original) => println!("{}Original:{} {}", get_color(3), get_color(0), original),
+            None => println!("{}Original:{} {} bytes", get_color(3), get_color(0),
+                             packet.len() - HEADER_LENGTH),
+        }
+    }
+}
+
+
+
+// TESTS
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filtering::{IcmpFilter, PortRange};
+    use crate::protocols::Protocol;
+    use crate::protocols::tests::{count_matched_by, ipv4_udp_frame};
+
+    #[test]
+    fn test_original_packet() {
+        // TIME EXCEEDED FOR 10.0.0.1:1234 -> 10.0.0.2:53 OVER UDP, WITH TTL 1 AND ID 7
+        let message = [&[0x0b, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00][..],
+                       &[0x45, 0x00, 0x00, 0x1c, 0x00, 0x07, 0x00, 0x00, 0x01, 0x11, 0x00, 0x00,
+                         0x0a, 0x00, 0x00, 0x01, 0x0a, 0x00, 0x00, 0x02],
+                       &[0x04, 0xd2, 0x00, 0x35, 0x00, 0x08, 0x00, 0x00]].concat();
+
+        let (next, data) = check_and_get_next_layer(&message, Parameters::NoParameter).unwrap();
+
+        assert!(matches!(next, UpperProtocol::Layer2(TIME_EXCEEDED)));
+        assert_eq!(describe_original(&data).unwrap(),
+                   "Udp 10.0.0.1:1234 -> 10.0.0.2:53   TTL: 1   ID: 7");
+
+        // THE PORTS CANNOT MATCH, THE SHORT HEADER IS BROKEN
+        assert!(check_and_get_next_layer(&message,
+            Parameters::Port(vec![PortRange::single(53)])).is_none());
+        assert!(check_and_get_next_layer(&message[..6], Parameters::NoParameter).is_none());
+
+        assert!(is_error(UNREACHABLE) && !is_error(ECHO_REQUEST));
+        assert_eq!(describe(3, icmp_type_name(3)), "3 (unreachable)");
+        assert_eq!(describe(42, icmp_type_name(42)), "42");
+    }
+
+    #[test]
+    fn test_memory_source_icmp() {
+        // THE PING FROM 10.0.0.1 AND THE PORT UNREACHABLE FOR THE DNS QUERY OF IT
+        let ip = |protocol : u8, length : u8| [0x45, 0x00, 0x00, length, 0x00, 0x00, 0x40, 0x00,
+                                                0x40, protocol, 0x00, 0x00, 0x0a, 0x00, 0x00, 0x01,
+                                                0x0a, 0x00, 0x00, 0x02];
+        let udp = ipv4_udp_frame();
+        let ethernet = &udp[..14];
+
+        let frames = vec![[ethernet, &ip(1, 28), 
+                           &[0x08, 0x00, 0xf7, 0xfd, 0x00, 0x01, 0x00, 0x01]].concat(),
+                          [ethernet, &ip(1, 56), 
+                           &[0x03, 0x03, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], &udp[14..]].concat(),
+                          udp.clone()];
+
+        let icmp = |text : &str| vec![IcmpFilter::parse(text).unwrap()];
+
+        let params = vec![Parameters::Protocol(vec![Protocol::ICMP])];
+        assert_eq!(count_matched_by(frames.clone(), params), (3, 2));
+
+        let params = vec![Parameters::Icmp(icmp("unreachable"))];
+        assert_eq!(count_matched_by(frames.clone(), params), (3, 1));
+
+        let params = vec![Parameters::Icmp(icmp("echo-request")), Parameters::Icmp(icmp("3/3"))];
+        assert_eq!(count_matched_by(frames.clone(), params), (3, 0));
+
+        // ICMP HAS NO PORTS, ONLY THE UDP PACKET MATCHES
+        let params = vec![Parameters::Port(vec![PortRange::single(53)])];
+        assert_eq!(count_matched_by(frames, params), (3, 1));
+    }
+}
@@ -0,0 +1,435 @@
+// ICMPv6 (NEXT HEADER 58) DOES MORE THAN ICMP: BESIDES THE ECHO AND THE ERRORS
+// IT CARRIES THE NEIGHBOR DISCOVERY (THE ROUTERS, THE PREFIXES FOR THE AUTOCONFIGURATION,
+// THE ADDRESS RESOLUTION INSTEAD OF ARP) AND MLD (THE MULTICAST GROUPS)
+
+use std::net::{Ipv6Addr, SocketAddrV6};
+
+use pnet::packet::icmpv6::Icmpv6Packet;
+use pnet::packet::ip::{IpNextHeaderProtocol, IpNextHeaderProtocols};
+use pnet::packet::ipv6::Ipv6Packet;
+
+use crate::{print_program_name, get_color};
+use crate::layers::UpperProtocol;
+use crate::Parameters;
+
+use crate::filtering::{icmpv6_type_name, icmpv6_code_name};
+use crate::protocols::icmp::describe;
+use crate::protocols::ipv6;
+
+pub const HEADER_LENGTH : usize = 8;
+
+const PACKET_TOO_BIG         : u8 = 2;
+const PARAMETER_PROBLEM      : u8 = 4;
+const ECHO_REQUEST           : u8 = 128;
+const ECHO_REPLY             : u8 = 129;
+const MLD_QUERY              : u8 = 130;
+const MLD_REPORT             : u8 = 131;
+const MLD_DONE               : u8 = 132;
+const ROUTER_SOLICITATION    : u8 = 133;
+const ROUTER_ADVERTISEMENT   : u8 = 134;
+const NEIGHBOR_SOLICITATION  : u8 = 135;
+const NEIGHBOR_ADVERTISEMENT : u8 = 136;
+const REDIRECT               : u8 = 137;
+const MLD2_REPORT            : u8 = 143;
+
+// MLDv2 QUERY IS LONGER THAN THE ONE OF MLDv1 (THE SOURCES COME AFTER 28 BYTES)
+
+const MLD2_QUERY_LENGTH : usize = 28;
+
+const RA_FLAGS     : [(&str, u8); 2] = [("Managed", 0x80), ("Other", 0x40)];
+const NA_FLAGS     : [(&str, u8); 3] = [("Router", 0x80), ("Solicited", 0x40), 
+                                        ("Override", 0x20)];
+const PREFIX_FLAGS : [(&str, u8); 2] = [("On-Link", 0x80), ("Autonomous", 0x40)];
+
+const MLD2_RECORD_TYPES : [&str; 6] = ["IS_INCLUDE", "IS_EXCLUDE", "TO_INCLUDE", "TO_EXCLUDE",
+                                       "ALLOW_NEW", "BLOCK_OLD"];
+
+// THE TYPES BELOW 128 ARE THE ERRORS, THEY CARRY AS MUCH OF THE FAILED PACKET AS FITS
+
+pub fn is_error(icmp_type : u8) -> bool {
+    icmp_type < 128
+}
+
+// THE TYPE OF THE MESSAGE IS PASSED ON, THE DATA AFTER THE HEADER IS THE PAYLOAD
+// ICMPv6 HAS NO PORTS, THE PACKET CANNOT MATCH THEM
+
+pub fn check_and_get_next_layer(packet : &[u8], p : Parameters)
+                                            -> Option<(UpperProtocol, Vec<u8>)> {
+    if let Parameters::Port(ports) = p {
+        if !ports.is_empty() {
+            return None;
+        }
+    }
+
+    let icmp = Icmpv6Packet::new(packet).filter(|_| packet.len() >= HEADER_LENGTH)?;
+
+    Some((UpperProtocol::Layer2(icmp.get_icmpv6_type().0), packet[HEADER_LENGTH..].to_vec()))
+}
+
+fn half(data : &[u8], at : usize) -> Option<u16> {
+    Some(u16::from_be_bytes(data.get(at..at + 2)?.try_into().ok()?))
+}
+
+fn word(data : &[u8], at : usize) -> Option<u32> {
+    Some(u32::from_be_bytes(data.get(at..at + 4)?.try_into().ok()?))
+}
+
+fn address(data : &[u8], at : usize) -> Option<Ipv6Addr> {
+    let bytes : [u8; 16] = data.get(at..at + 16)?.try_into().ok()?;
+    Some(Ipv6Addr::from(bytes))
+}
+
+// THE NAMES OF THE FLAGS WHICH ARE SET: "Managed Other", "none" IF THERE ARE NONE
+
+fn flag_names(flags : u8, names : &[(&str, u8)]) -> String {
+    let set : Vec<&str> = names.iter().filter(|(_, flag)| flags & flag != 0)
+                               .map(|(name, _)| *name).collect();
+
+    if set.is_empty() { "none".to_string() } else { set.join(" ") }
+}
+
+// THE PACKET WHICH FAILED IN ONE LINE: "Udp [::1]:1234 -> [::2]:53   Hop Limit: 1"
+
+pub fn describe_original(data : &[u8]) -> Option<String> {
+    let ip = Ipv6Packet::new(data).filter(|packet| packet.get_version() == 6)?;
+
+    // THE ERROR IS CUT, SO IS THE PAYLOAD OF THE FAILED PACKET
+    let payload = data.get(Ipv6Packet::minimum_packet_size()..).unwrap_or_default();
+
+    let (next_header, after) = ipv6::skip_extension_headers(ip.get_next_header().0, payload)
+                                    .unwrap_or((ip.get_next_header().0, &[]));
+
+    let (source, destination) = (ip.get_source(), ip.get_destination());
+    let protocol = IpNextHeaderProtocol(next_header);
+
+    let endpoints = match (protocol, half(after, 0), half(after, 2)) {
+        (IpNextHeaderProtocols::Tcp | IpNextHeaderProtocols::Udp, Some(source_port),
+         Some(destination_port)) => {
+            format!("{} -> {}", SocketAddrV6::new(source, source_port, 0, 0),
+                    SocketAddrV6::new(destination, destination_port, 0, 0))
+        },
+        (IpNextHeaderProtocols::Icmpv6, _, _) if after.len() >= HEADER_LENGTH => {
+            format!("{} -> {} ({}, ID {}, SEQ {})", source, destination,
+                    describe(after[0], icmpv6_type_name(after[0])),
+                    half(after, 4)?, half(after, 6)?)
+        },
+        _ => format!("{} -> {}", source, destination),
+    };
+
+    Some(format!("{} {}   Hop Limit: {}", protocol, endpoints, ip.get_hop_limit()))
+}
+
+
+
+// THE OPTIONS OF THE NEIGHBOR DISCOVERY: THE TYPE, THE LENGTH IN 8 BYTES, THE VALUE
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum NdpOption {
+    SourceLinkAddress(Vec<u8>),
+    TargetLinkAddress(Vec<u8>),
+
+    // THE PREFIX FOR THE AUTOCONFIGURATION (SLAAC) AND THE ON-LINK DETERMINATION
+    Prefix { prefix : Ipv6Addr, length : u8, flags : u8, valid : u32, preferred : u32 },
+
+    // THE BEGINNING OF THE PACKET WHICH WAS REDIRECTED
+    RedirectedHeader(Vec<u8>),
+
+    Mtu(u32),
+
+    // THE DNS SERVERS (RFC 8106)
+    Rdnss { lifetime : u32, servers : Vec<Ipv6Addr> },
+
+    // THE TYPE AND THE LENGTH OF THE OPTION THE PROGRAM DOES NOT KNOW
+    Other(u8, usize),
+}
+
+fn parse_option(option : &[u8]) -> NdpOption {
+    let body = option[2..].to_vec();
+
+    match option[0] {
+        1 => NdpOption::SourceLinkAddress(body),
+        2 => NdpOption::TargetLinkAddress(body),
+        3 if option.len() >= 32 => NdpOption::Prefix {
+            prefix    : address(option, 16).unwrap_or(Ipv6Addr::UNSPECIFIED),
+            length    : option[2],
+            flags     : option[3],
+            valid     : word(option, 4).unwrap_or_default(),
+            preferred : word(option, 8).unwrap_or_default(),
+        },
+        4 => NdpOption::RedirectedHeader(option[8.min(option.len())..].to_vec()),
+        5 => NdpOption::Mtu(word(option, 4).unwrap_or_default()),
+        25 => NdpOption::Rdnss {
+            lifetime : word(option, 4).unwrap_or_default(),
+            servers  : option[8.min(option.len())..].chunks_exact(16)
+                           .filter_map(|server| address(server, 0)).collect(),
+        },
+        kind => NdpOption::Other(kind, option.len()),
+    }
+}
+
+// THE OPTIONS UNTIL THE END OF THE MESSAGE, THE BROKEN ONE (ZERO OR TOO LONG) ENDS THEM
+
+pub fn parse_options(mut data : &[u8]) -> Vec<NdpOption> {
+    let mut options : Vec<NdpOption> = vec![];
+
+    while data.len() >= 2 {
+        let length = usize::from(data[1]) * 8;
+
+        if length == 0 || length > data.len() {
+            break;
+        }
+
+        let (option, rest) = data.split_at(length);
+
+        options.push(parse_option(option));
+        data = rest;
+    }
+
+    options
+}
+
+fn link_address(bytes : &[u8]) -> String {
+    let hex : Vec<String> = bytes.iter().map(|byte| format!("{:02x}", byte)).collect();
+    hex.join(":")
+}
+
+pub fn describe_option(option : &NdpOption) -> String {
+    match option {
+        NdpOption::SourceLinkAddress(bytes) => {
+            format!("Source Link-Layer Address: {}", link_address(bytes))
+        },
+        NdpOption::TargetLinkAddress(bytes) => {
+            format!("Target Link-Layer Address: {}", link_address(bytes))
+        },
+        NdpOption::Prefix { prefix, length, flags, valid, preferred } => {
+            format!("Prefix: {}/{}   Flags: {}   Valid Lifetime: {}   Preferred Lifetime: {}",
+                    prefix, length, flag_names(*flags, &PREFIX_FLAGS), valid, preferred)
+        },
+        NdpOption::RedirectedHeader(data) => {
+            format!("Redirected Header: {}", describe_original(data)
+                        .unwrap_or_else(|| format!("{} bytes", data.len())))
+        },
+        NdpOption::Mtu(mtu) => format!("MTU: {}", mtu),
+        NdpOption::Rdnss { lifetime, servers } => {
+            let servers : Vec<String> = servers.iter().map(|server| server.to_string()).collect();
+            format!("DNS Servers: {}   Lifetime: {}", servers.join(", "), lifetime)
+        },
+        NdpOption::Other(kind, length) => format!("Type {}: {} bytes", kind, length),
+    }
+}
+
+fn print_options(data : &[u8]) {
+    for option in parse_options(data) {
+        println!("{}Option:{} {}", get_color(5), get_color(0), describe_option(&option));
+    }
+}
+
+
+
+// THE GROUP RECORDS OF THE MLDv2 REPORT: "TO_EXCLUDE ff02::fb (0 sources)"
+
+pub fn mld2_records(packet : &[u8]) -> Vec<String> {
+    let count = half(packet, 6).unwrap_or_default();
+
+    let mut records  : Vec<String> = vec![];
+    let mut position : usize       = HEADER_LENGTH;
+
+    for _ in 0..count {
+        let (Some(kind), Some(auxiliary), Some(sources), Some(group)) =
+            (packet.get(position), packet.get(position + 1), half(packet, position + 2),
+             address(packet, position + 4)) else {
+            break;
+        };
+
+        let name = MLD2_RECORD_TYPES.get(usize::from(*kind).wrapping_sub(1))
+                                    .map(|name| name.to_string())
+                                    .unwrap_or_else(|| format!("TYPE {}", kind));
+
+        records.push(format!("{} {} ({} sources)", name, group, sources));
+
+        position += 20 + usize::from(sources) * 16 + usize::from(*auxiliary) * 4;
+    }
+
+    records
+}
+
+fn print_message(icmp_type : u8, packet : &[u8]) {
+    let ip_at   = |at : usize| address(packet, at).map(|address| address.to_string())
+                                                   .unwrap_or_else(|| "?".to_string());
+    let half_at = |at : usize| half(packet, at).unwrap_or_default();
+    let word_at = |at : usize| word(packet, at).unwrap_or_default();
+
+    match icmp_type {
+        ECHO_REQUEST | ECHO_REPLY => {
+            println!("Identifier: {}   Sequence: {}   Data: {} bytes", half_at(4), half_at(6),
+                     packet.len() - HEADER_LENGTH);
+        },
+        PACKET_TOO_BIG => println!("MTU: {}", word_at(4)),
+        PARAMETER_PROBLEM => println!("Pointer: {}", word_at(4)),
+        ROUTER_SOLICITATION => print_options(&packet[HEADER_LENGTH..]),
+        ROUTER_ADVERTISEMENT => {
+            println!("Hop Limit: {}   Flags: {}   Router Lifetime: {}", packet[4],
+                     flag_names(packet[5], &RA_FLAGS), half_at(6));
+            println!("Reachable Time: {}   Retransmit Timer: {}", word_at(8), word_at(12));
+            print_options(packet.get(16..).unwrap_or_default());
+        },
+        NEIGHBOR_SOLICITATION => {
+            println!("{}Target:{} {}", get_color(2), get_color(0), ip_at(8));
+            print_options(packet.get(24..).unwrap_or_default());
+        },
+        NEIGHBOR_ADVERTISEMENT => {
+            println!("{}Target:{} {}   Flags: {}", get_color(2), get_color(0), ip_at(8),
+                     flag_names(packet[4], &NA_FLAGS));
+            print_options(packet.get(24..).unwrap_or_default());
+        },
+        REDIRECT => {
+            println!("{}Target:{} {}   {}Destination:{} {}", get_color(2), get_color(0),
+                     ip_at(8), get_color(4), get_color(0), ip_at(24));
+            print_options(packet.get(40..).unwrap_or_default());
+        },
+        MLD_QUERY | MLD_REPORT | MLD_DONE => {
+            println!("Maximum Response Delay: {}   {}Multicast Address:{} {}", half_at(4),
+                     get_color(2), get_color(0), ip_at(8));
+
+            if icmp_type == MLD_QUERY && packet.len() >= MLD2_QUERY_LENGTH {
+                println!("Robustness: {}   Query Interval: {}   Sources: {}", packet[24] & 0x07,
+                         packet[25], half_at(26));
+            }
+        },
+        MLD2_REPORT => {
+            for record in mld2_records(packet) {
+                println!("{}Record:{} {}", get_color(2), get_color(0), record);
+            }
+        },
+        _ => (),
+    }
+}
+
+pub fn print_output(packet : Vec<u8>) {
+    let icmp = Icmpv6Packet::new(&packet).unwrap();
+
+    let (icmp_type, code) = (icmp.get_icmpv6_type().0, icmp.get_icmpv6_code().0);
+
+    print_program_name();
+
+    println!("{}> > > > ICMPv6 PACKET{}", get_color(1), get_color(0));
+    println!("Type: {}   Code: {}   Checksum: {}",
+             describe(icmp_type, icmpv6_type_name(icmp_type)),
+             describe(code, icmpv6_code_name(icmp_type, code)), icmp.get_checksum());
+
+    print_message(icmp_type, &packet);
+
+    if is_error(icmp_type) {
+        match describe_original(&packet[HEADER_LENGTH..]) {
+            Some(original) => println!("{}Original:{} {}", get_color(3), get_color(0), original),
+            None => println!("{}Original:{} {} bytes", get_color(3), get_color(0),
+                             packet.len() - HEADER_LENGTH),
+        }
+    }
+}
+
+
+
+// TESTS
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filtering::IcmpFilter;
+    use crate::protocols::Protocol;
+    use crate::protocols::tests::{count_matched_by, ipv6_udp_frame};
+
+    #[test]
+    fn test_router_advertisement() {
+        // THE SOURCE LINK-LAYER ADDRESS, THE PREFIX 2001:db8:1::/64 (ON-LINK, AUTONOMOUS),
+        // THE MTU 1500 AND ONE DNS SERVER 2001:db8::53
+        let mut prefix = [0u8; 32];
+        prefix[..4].copy_from_slice(&[0x03, 0x04, 0x40, 0xc0]);
+        prefix[4..8].copy_from_slice(&2592000u32.to_be_bytes());
+        prefix[8..12].copy_from_slice(&604800u32.to_be_bytes());
+        prefix[16..22].copy_from_slice(&[0x20, 0x01, 0x0d, 0xb8, 0x00, 0x01]);
+
+        let mut dns = [0u8; 24];
+        dns[..8].copy_from_slice(&[0x19, 0x03, 0x00, 0x00, 0x00, 0x00, 0x02, 0x58]);
+        dns[8..12].copy_from_slice(&[0x20, 0x01, 0x0d, 0xb8]);
+        dns[23] = 0x53;
+
+        let options = [&[0x01, 0x01, 0x02, 0x00, 0x00, 0x00, 0x00, 0x01][..], &prefix,
+                       &[0x05, 0x01, 0x00, 0x00, 0x00, 0x00, 0x05, 0xdc], &dns,
+                       &[0x0e, 0x00, 0xff]].concat();
+
+        let parsed = parse_options(&options);
+
+        assert_eq!(parsed.len(), 4);
+        assert_eq!(describe_option(&parsed[0]), "Source Link-Layer Address: 02:00:00:00:00:01");
+        assert_eq!(describe_option(&parsed[1]),
+                   "Prefix: 2001:db8:1::/64   Flags: On-Link Autonomous   \
+                    Valid Lifetime: 2592000   Preferred Lifetime: 604800");
+        assert_eq!(parsed[2], NdpOption::Mtu(1500));
+        assert_eq!(describe_option(&parsed[3]), "DNS Servers: 2001:db8::53   Lifetime: 600");
+    }
+
+    #[test]
+    fn test_errors_and_mld() {
+        // THE HOP LIMIT EXCEEDED FOR [::1]:1234 -> [::2]:53 OVER UDP
+        let mut failed = [0u8; 48];
+        failed[..8].copy_from_slice(&[0x60, 0x00, 0x00, 0x00, 0x00, 0x08, 0x11, 0x01]);
+        failed[23] = 1;
+        failed[39] = 2;
+        failed[40..44].copy_from_slice(&[0x04, 0xd2, 0x00, 0x35]);
+
+        let message = [&[0x03, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00][..], &failed].concat();
+        let (next, data) = check_and_get_next_layer(&message, Parameters::NoParameter).unwrap();
+
+        assert!(matches!(next, UpperProtocol::Layer2(3)));
+        assert_eq!(describe_original(&data).unwrap(),
+                   "Udp [::1]:1234 -> [::2]:53   Hop Limit: 1");
+        assert!(is_error(3) && !is_error(ECHO_REQUEST));
+
+        // THE MLDv2 REPORT JOINING ff02::fb WITH ONE RECORD (TO_EXCLUDE, NO SOURCES)
+        let mut report = vec![0x8f, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01,
+                              0x04, 0x00, 0x00, 0x00, 0xff, 0x02];
+        report.extend_from_slice(&[0; 13]);
+        report.push(0xfb);
+
+        assert_eq!(mld2_records(&report), vec!["TO_EXCLUDE ff02::fb (0 sources)"]);
+        assert_eq!(flag_names(0xa0, &NA_FLAGS), "Router Override");
+        assert_eq!(flag_names(0, &RA_FLAGS), "none");
+    }
+
+    #[test]
+    fn test_memory_source_icmp6() {
+        // ::1 -> ::2 WITH THE GIVEN NEXT HEADER AND PAYLOAD
+        let ipv6 = |next_header : u8, payload : &[u8]| {
+            let mut frame = ipv6_udp_frame()[..54].to_vec();
+            frame[19] = payload.len() as u8;
+            frame[20] = next_header;
+            [&frame[..], payload].concat()
+        };
+
+        // THE ROUTER SOLICITATION, THE MLDv2 REPORT BEHIND THE HOP-BY-HOP OPTIONS
+        // AND THE PORT UNREACHABLE FOR THE UDP PACKET
+        let frames = vec![ipv6(58, &[0x85, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00]),
+                          ipv6(0, &[0x3a, 0x00, 0x05, 0x02, 0x00, 0x00, 0x01, 0x00,
+                                    0x8f, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00]),
+                          ipv6(58, &[&[0x01, 0x04, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00][..], 
+                                     &ipv6_udp_frame()[14..]].concat()),
+                          ipv6_udp_frame()];
+
+        let icmp6 = |text : &str| vec![IcmpFilter::parse_v6(text).unwrap()];
+
+        let params = vec![Parameters::Protocol(vec![Protocol::ICMPv6])];
+        assert_eq!(count_matched_by(frames.clone(), params), (4, 3));
+
+        let params = vec![Parameters::Icmp6(icmp6("mld2-report"))];
+        assert_eq!(count_matched_by(frames.clone(), params), (4, 1));
+
+        let params = vec![Parameters::Icmp6(icmp6("port-unreachable")),
+                          Parameters::Protocol(vec![Protocol::ICMPv6])];
+        assert_eq!(count_matched_by(frames.clone(), params), (4, 1));
+
+        // THE ICMP TYPES ARE NOT THE ICMPv6 ONES
+        let params = vec![Parameters::Icmp(vec![IcmpFilter::parse("3").unwrap()])];
+        assert_eq!(count_matched_by(frames, params), (4, 0));
+    }
+}
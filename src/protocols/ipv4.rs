@@ -1,6 +1,8 @@
 // IPv4 Packet demands a valid Ethernet Frame
 // Ethernet module already checks it, so we getting there after this check
 
+use std::net::IpAddr;
+
 use pnet::packet::Packet;
 use pnet::packet::ipv4::Ipv4Packet;
 use crate::{print_program_name, get_color};
@@ -11,9 +13,9 @@ use crate::Parameters;
 use crate::filtering::{NetworkLayer, check_ips};
 
 impl NetworkLayer for Ipv4Packet<'_> {
-    fn get_source_dest(&self) -> (String, String) {
-        (self.get_source().to_string(), 
-        self.get_destination().to_string())
+    fn get_source_dest(&self) -> (IpAddr, IpAddr) {
+        (IpAddr::V4(self.get_source()), 
+        IpAddr::V4(self.get_destination()))
     }
 }
 
@@ -21,7 +23,7 @@ impl NetworkLayer for Ipv4Packet<'_> {
 
 pub fn check_and_get_next_layer(packet : &[u8], ips : Parameters) 
                                         -> Option<(UpperProtocol, Vec<u8>)> {
-    if packet[0] >> 4 != 4 {
+    if packet.first()? >> 4 != 4 {
         return None;
     }
 
@@ -63,7 +65,6 @@ pub fn print_output(packet : Vec<u8>) {
 // TESTS:
 
 #[cfg(test)]
-
 mod test {
     use super::*;
 
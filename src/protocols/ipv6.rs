@@ -1,3 +1,5 @@
+use std::net::IpAddr;
+
 use pnet::packet::Packet;
 use pnet::packet::ipv6::Ipv6Packet;
 use crate::{print_program_name, get_color};
@@ -7,22 +9,47 @@ use crate::Parameters;
 use crate::filtering::{NetworkLayer, check_ips};
 
 impl NetworkLayer for Ipv6Packet<'_> {
-    fn get_source_dest(&self) -> (String, String) {
-        (self.get_source().to_string(), 
-        self.get_destination().to_string())
+    fn get_source_dest(&self) -> (IpAddr, IpAddr) {
+        (IpAddr::V6(self.get_source()), 
+        IpAddr::V6(self.get_destination()))
     }
 }
 
+// THE EXTENSION HEADERS WHICH MAY COME BEFORE THE TRANSPORT ONE (HOP-BY-HOP, ROUTING,
+// DESTINATION OPTIONS), EACH STARTS WITH THE NEXT HEADER AND ITS LENGTH IN 8 BYTES
+// (NOT COUNTING THE FIRST 8), THE FRAGMENTS ARE LEFT AS THEY ARE
+
+pub const EXTENSION_HEADERS : [u8; 3] = [0, 43, 60];
+
+const MAX_EXTENSION_HEADERS : usize = 8;
+
+pub fn skip_extension_headers(mut next_header : u8, mut data : &[u8]) -> Option<(u8, &[u8])> {
+    for _ in 0..MAX_EXTENSION_HEADERS {
+        if !EXTENSION_HEADERS.contains(&next_header) {
+            return Some((next_header, data));
+        }
+
+        let length = (usize::from(*data.get(1)?) + 1) * 8;
+
+        next_header = data[0];
+        data = data.get(length..)?;
+    }
+
+    None
+}
+
 pub fn check_and_get_next_layer(packet : &[u8], ips: Parameters) 
                                             -> Option<(UpperProtocol, Vec<u8>)> {
-    if packet[0] >> 4 != 6 {
+    if packet.first()? >> 4 != 6 {
         return None
     }
 
     if let Some(ipv6_data) = Ipv6Packet::new(packet) {
         if check_ips(&ipv6_data, ips) {
-            return Some((UpperProtocol::Layer2(ipv6_data.get_next_header().0), 
-                ipv6_data.payload().to_vec()));
+            let (next_header, payload) = skip_extension_headers(ipv6_data.get_next_header().0,
+                                                                ipv6_data.payload())?;
+
+            return Some((UpperProtocol::Layer2(next_header), payload.to_vec()));
         }
     }
 
@@ -50,3 +77,24 @@ pub fn print_output(packet : Vec<u8>) {
              get_color(2), get_color(0), packet.get_source(),
              get_color(4), get_color(0), packet.get_destination()); 
 }
+
+
+
+// TESTS
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extension_headers() {
+        // THE HOP-BY-HOP OPTIONS (THE ROUTER ALERT), THEN THE DESTINATION OPTIONS OF 16 BYTES
+        let mut data = vec![60, 0, 0x05, 0x02, 0x00, 0x00, 0x01, 0x00, 58, 1];
+        data.extend_from_slice(&[0; 14]);
+        data.extend_from_slice(&[0x8f, 0x00]);
+
+        assert_eq!(skip_extension_headers(0, &data), Some((58, &data[24..])));
+        assert_eq!(skip_extension_headers(17, &data), Some((17, &data[..])));
+        assert_eq!(skip_extension_headers(0, &data[..4]), None);
+    }
+}
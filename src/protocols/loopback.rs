@@ -0,0 +1,92 @@
+use pnet::packet::ethernet::{EtherType, EtherTypes};
+
+use crate::layers::UpperProtocol;
+use crate::Parameters;
+
+use crate::{print_program_name, get_color};
+
+// THE BSD LOOPBACK (NULL) HEADER IS JUST THE 4 BYTE ADDRESS FAMILY
+// IN THE BYTE ORDER OF THE MACHINE WHICH WROTE IT, SO BOTH ORDERS ARE TRIED:
+// THE FAMILY IS ALWAYS A SMALL NUMBER, THE WRONG ORDER GIVES A HUGE ONE
+// (LINKTYPE_LOOP IS THE SAME HEADER, BUT ALWAYS IN THE NETWORK ORDER)
+
+const HEADER_LENGTH : usize = 4;
+
+const AF_INET : u32 = 2;
+
+// AF_INET6 IS DIFFERENT ON EVERY SYSTEM: LINUX, NETBSD/OPENBSD, FREEBSD, MACOS
+
+const AF_INET6 : [u32; 4] = [10, 24, 28, 30];
+
+fn get_family(packet : &[u8]) -> Option<u32> {
+    let header : [u8; HEADER_LENGTH] = packet.get(..HEADER_LENGTH)?.try_into().ok()?;
+
+    let big    = u32::from_be_bytes(header);
+    let little = u32::from_le_bytes(header);
+
+    Some(big.min(little))
+}
+
+fn get_ethertype(family : u32) -> Option<EtherType> {
+    match family {
+        AF_INET => Some(EtherTypes::Ipv4),
+        family if AF_INET6.contains(&family) => Some(EtherTypes::Ipv6),
+        _ => None,
+    }
+}
+
+fn get_family_name(family : u32) -> &'static str {
+    match get_ethertype(family) {
+        Some(EtherTypes::Ipv4) => "AF_INET",
+        Some(_) => "AF_INET6",
+        None => "UNKNOWN",
+    }
+}
+
+pub fn check_and_get_next_layer(packet : &[u8], _p : Parameters) 
+                                            -> Option<(UpperProtocol, Vec<u8>)> {
+
+    let ethertype = get_ethertype(get_family(packet)?)?;
+
+    Some((UpperProtocol::Layer1(ethertype), packet[HEADER_LENGTH..].to_vec()))
+}
+
+pub fn print_output(packet : Vec<u8>) {
+    let family = get_family(&packet).unwrap_or(0);
+
+    print_program_name();
+    println!("{}> LOOPBACK INFORMATION{}", get_color(1), get_color(0)); 
+
+    println!("{}Family:{} {} ({})   {}Data Length:{} {}", 
+             get_color(4), get_color(0), get_family_name(family), family,
+             get_color(2), get_color(0), packet.len().saturating_sub(HEADER_LENGTH));
+}
+
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_check_and_get_next_layer() {
+        // THE SAME IPv4 FAMILY WRITTEN BY THE LITTLE AND THE BIG ENDIAN MACHINE
+        for header in [[0x02, 0x00, 0x00, 0x00], [0x00, 0x00, 0x00, 0x02]] {
+            let mut packet = header.to_vec();
+            packet.push(0x45);
+
+            match check_and_get_next_layer(&packet, Parameters::NoParameter) {
+                Some((UpperProtocol::Layer1(ethertype), payload)) => {
+                    assert_eq!(ethertype, EtherTypes::Ipv4);
+                    assert_eq!(payload, vec![0x45]);
+                },
+                _ => panic!("LOOPBACK HEADER WAS NOT RECOGNISED"),
+            }
+        }
+
+        // MACOS AF_INET6
+        assert!(check_and_get_next_layer(&[0x1e, 0, 0, 0], Parameters::NoParameter).is_some());
+        assert!(check_and_get_next_layer(&[0x07, 0, 0, 0], Parameters::NoParameter).is_none());
+        assert!(check_and_get_next_layer(&[0x02, 0], Parameters::NoParameter).is_none());
+    }
+}
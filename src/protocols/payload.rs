@@ -0,0 +1,85 @@
+// THE DATA CARRIED BY TCP OR UDP, SHOWN ONLY WHEN THE PAYLOAD WAS SEARCHED:
+// THE HEX AND THE TEXT SIDE BY SIDE, WHAT WAS FOUND IS HIGHLIGHTED IN BOTH
+
+use crate::{print_program_name, get_color};
+
+const BYTES_PER_LINE : usize = 16;
+
+fn is_highlighted(position : usize, highlights : &[(usize, usize)]) -> bool {
+    highlights.iter().any(|(start, end)| *start <= position && position < *end)
+}
+
+fn format_line(line : &[u8], start : usize, highlights : &[(usize, usize)]) -> String {
+    let mut hex  = String::new();
+    let mut text = String::new();
+
+    for (i, byte) in line.iter().enumerate() {
+        let (on, off) = if is_highlighted(start + i, highlights) { (get_color(3), get_color(0)) }
+                        else { ("", "") };
+
+        let character = if byte.is_ascii_graphic() || *byte == b' ' { *byte as char } else { '.' };
+
+        hex.push_str(&format!("{}{:02x}{} ", on, byte, off));
+        text.push_str(&format!("{}{}{}", on, character, off));
+    }
+
+    // THE SHORT LAST LINE KEEPS THE TEXT COLUMN IN PLACE
+    let padding = "   ".repeat(BYTES_PER_LINE - line.len());
+
+    format!("{:04x}  {}{}  {}", start, hex, padding, text)
+}
+
+pub fn print_highlighted(packet : &[u8], highlights : &[(usize, usize)]) {
+    print_program_name();
+
+    println!("{}> > > > > PAYLOAD{} ({} bytes, {} found)", get_color(1), get_color(0),
+             packet.len(), highlights.len());
+
+    for (number, line) in packet.chunks(BYTES_PER_LINE).enumerate() {
+        println!("{}", format_line(line, number * BYTES_PER_LINE, highlights));
+    }
+}
+
+pub fn print_output(packet : Vec<u8>) {
+    print_highlighted(&packet, &[]);
+}
+
+
+
+// TESTS
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Parameters;
+    use crate::protocols::udp;
+    use crate::filtering::payload::{find_matches, PayloadPattern};
+
+    #[test]
+    fn test_format_line() {
+        let line = format_line(b"GET /\r\n", 16, &[]);
+
+        assert!(line.starts_with("0010  47 45 54 20 2f 0d 0a "));
+        assert!(line.ends_with("  GET /.."));
+
+        let highlighted = format_line(b"GET", 0, &[(1, 2)]);
+        assert!(highlighted.contains(&format!("{}45{}", get_color(3), get_color(0))));
+        assert!(highlighted.ends_with(&format!("G{}E{}T", get_color(3), get_color(0))));
+    }
+
+    #[test]
+    fn test_found_in_udp_payload() {
+        // 10.0.0.1:40000 -> 10.0.0.2:8080 CARRYING THE REQUEST LINE
+        let request  = b"GET /health HTTP/1.1\r\n";
+        let datagram = [&[0x9c, 0x40, 0x1f, 0x90, 0x00, 0x1e, 0x00, 0x00][..], request].concat();
+
+        let (_, payload) = udp::check_and_get_next_layer(&datagram, Parameters::NoParameter)
+            .unwrap();
+        assert_eq!(payload, request);
+
+        let slash = PayloadPattern::Bytes(b"/".to_vec());
+        assert_eq!(slash.find_all(&payload), vec![(4, 5), (16, 17)]);
+        assert_eq!(find_matches(&[slash], &payload), Some(vec![(4, 5), (16, 17)]));
+        assert!(find_matches(&[PayloadPattern::Bytes(b"ERROR".to_vec())], &payload).is_none());
+    }
+}
@@ -0,0 +1,60 @@
+use pnet::packet::ethernet::{EtherType, EtherTypes};
+
+use crate::layers::UpperProtocol;
+use crate::Parameters;
+
+use crate::{print_program_name, get_color};
+
+// THE RAW IP HAS NO LINK HEADER AT ALL (TUN, WIREGUARD AND OTHER L3 DEVICES),
+// THE VERSION IN THE FIRST NIBBLE IS THE ONLY THING TELLING WHAT IS INSIDE
+
+fn get_ethertype(packet : &[u8]) -> Option<EtherType> {
+    match packet.first()? >> 4 {
+        4 => Some(EtherTypes::Ipv4),
+        6 => Some(EtherTypes::Ipv6),
+        _ => None,
+    }
+}
+
+pub fn check_and_get_next_layer(packet : &[u8], _p : Parameters) 
+                                            -> Option<(UpperProtocol, Vec<u8>)> {
+
+    let ethertype = get_ethertype(packet)?;
+
+    Some((UpperProtocol::Layer1(ethertype), packet.to_vec()))
+}
+
+pub fn print_output(packet : Vec<u8>) {
+    let version = packet.first().map(|byte| byte >> 4).unwrap_or(0);
+
+    print_program_name();
+    println!("{}> RAW IP INFORMATION{}", get_color(1), get_color(0)); 
+
+    println!("{}Link Header:{} none   {}IP Version:{} {}   {}Data Length:{} {}", 
+             get_color(4), get_color(0),
+             get_color(5), get_color(0), version,
+             get_color(2), get_color(0), packet.len());
+}
+
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_check_and_get_next_layer() {
+        let ipv6 = [0x60, 0x00, 0x00, 0x00];
+
+        match check_and_get_next_layer(&ipv6, Parameters::NoParameter) {
+            Some((UpperProtocol::Layer1(ethertype), payload)) => {
+                assert_eq!(ethertype, EtherTypes::Ipv6);
+                assert_eq!(payload, ipv6.to_vec());
+            },
+            _ => panic!("RAW IPv6 WAS NOT RECOGNISED"),
+        }
+
+        assert!(check_and_get_next_layer(&[0x10, 0x00], Parameters::NoParameter).is_none());
+        assert!(check_and_get_next_layer(&[], Parameters::NoParameter).is_none());
+    }
+}
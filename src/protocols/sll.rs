@@ -0,0 +1,85 @@
+use pnet::packet::Packet;
+use pnet::packet::sll::SLLPacket;
+
+use crate::layers::UpperProtocol;
+use crate::Parameters;
+
+use crate::{print_program_name, get_color};
+
+// THE LINUX "COOKED" HEADER, WHICH THE KERNEL MAKES UP INSTEAD OF THE REAL ONE
+// (THE "any" CAPTURES AND THE DEVICES WITHOUT THE LINK HEADER OF THEIR OWN)
+
+pub fn get_packet_type_name(packet_type : u16) -> &'static str {
+    match packet_type {
+        0 => "TO US",
+        1 => "BROADCAST",
+        2 => "MULTICAST",
+        3 => "TO OTHER HOST",
+        4 => "SENT BY US",
+        _ => "UNKNOWN",
+    }
+}
+
+// ONLY THE FIRST BYTES OF THE 8 BYTE FIELD ARE THE REAL ADDRESS
+
+pub fn format_address(address : &[u8], length : usize) -> String {
+    if length == 0 {
+        return "-".to_string();
+    }
+
+    address[..length.min(address.len())].iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect::<Vec<String>>()
+        .join(":")
+}
+
+pub fn check_and_get_next_layer(packet : &[u8], _p : Parameters) 
+                                            -> Option<(UpperProtocol, Vec<u8>)> {
+
+    if let Some(sll) = SLLPacket::new(packet) {
+        return Some((UpperProtocol::Layer1(sll.get_protocol()), 
+                sll.payload().to_vec()));
+    }
+
+    None
+}
+
+pub fn print_output(packet : Vec<u8>) {
+    let packet = SLLPacket::new(&packet).unwrap();
+
+    print_program_name();
+    println!("{}> LINUX COOKED INFORMATION{}", get_color(1), get_color(0)); 
+
+    println!("{}Packet Type:{} {}   {}Address:{} {}   {}Type:{} {}   {}Data Length:{} {}", 
+             get_color(4), get_color(0), get_packet_type_name(packet.get_packet_type()), 
+             get_color(2), get_color(0), 
+             format_address(&packet.get_link_layer_address(),
+                            packet.get_link_layer_address_len() as usize), 
+             get_color(5), get_color(0), packet.get_protocol(),
+             get_color(2), get_color(0), packet.payload().len());
+}
+
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pnet::packet::ethernet::EtherTypes;
+
+    #[test]
+    fn test_check_and_get_next_layer() {
+        let packet = [0x00, 0x04, 0x00, 0x01, 0x00, 0x06, 
+                      0x26, 0x57, 0x8e, 0xd6, 0x4a, 0x7a, 0x00, 0x00,
+                      0x86, 0xdd, 0x60];
+
+        match check_and_get_next_layer(&packet, Parameters::NoParameter) {
+            Some((UpperProtocol::Layer1(ethertype), payload)) => {
+                assert_eq!(ethertype, EtherTypes::Ipv6);
+                assert_eq!(payload, vec![0x60]);
+            },
+            _ => panic!("SLL HEADER WAS NOT RECOGNISED"),
+        }
+
+        assert_eq!(format_address(&packet[6..14], 6), "26:57:8e:d6:4a:7a");
+    }
+}
@@ -0,0 +1,66 @@
+use pnet::packet::Packet;
+use pnet::packet::sll2::SLL2Packet;
+
+use crate::layers::UpperProtocol;
+use crate::Parameters;
+use crate::protocols::sll::{get_packet_type_name, format_address};
+
+use crate::{print_program_name, get_color};
+
+// THE SECOND VERSION OF THE COOKED HEADER, IT ALSO KNOWS THE INTERFACE
+// THE PACKET CAME FROM AND ITS REAL HARDWARE TYPE
+
+pub fn check_and_get_next_layer(packet : &[u8], _p : Parameters) 
+                                            -> Option<(UpperProtocol, Vec<u8>)> {
+
+    if let Some(sll) = SLL2Packet::new(packet) {
+        return Some((UpperProtocol::Layer1(sll.get_protocol_type()), 
+                sll.payload().to_vec()));
+    }
+
+    None
+}
+
+pub fn print_output(packet : Vec<u8>) {
+    let packet = SLL2Packet::new(&packet).unwrap();
+
+    print_program_name();
+    println!("{}> LINUX COOKED V2 INFORMATION{}", get_color(1), get_color(0)); 
+
+    println!("{}Packet Type:{} {}   {}Address:{} {}   {}Type:{} {}", 
+             get_color(4), get_color(0), 
+             get_packet_type_name(u16::from(packet.get_packet_type())), 
+             get_color(2), get_color(0), 
+             format_address(&packet.get_link_layer_address(),
+                            packet.get_link_layer_address_length() as usize), 
+             get_color(5), get_color(0), packet.get_protocol_type());
+
+    println!("{}Interface Index:{} {}   {}Hardware Type:{} {}   {}Data Length:{} {}", 
+             get_color(4), get_color(0), packet.get_interface_index(), 
+             get_color(5), get_color(0), packet.get_arphrd_type(),
+             get_color(2), get_color(0), packet.payload().len());
+}
+
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pnet::packet::ethernet::EtherTypes;
+
+    #[test]
+    fn test_check_and_get_next_layer() {
+        let packet = [0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x03, 
+                      0xff, 0xfe, 0x04, 0x00, 
+                      0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+                      0x45];
+
+        match check_and_get_next_layer(&packet, Parameters::NoParameter) {
+            Some((UpperProtocol::Layer1(ethertype), payload)) => {
+                assert_eq!(ethertype, EtherTypes::Ipv4);
+                assert_eq!(payload, vec![0x45]);
+            },
+            _ => panic!("SLL2 HEADER WAS NOT RECOGNISED"),
+        }
+    }
+}
@@ -6,24 +6,32 @@ use crate::layers::UpperProtocol;
 
 use crate::Parameters;
 
-use crate::filtering::check_ports;
+use crate::filtering::{check_ports, TCP_FLAG_NAMES};
 
 
 
 pub fn check_and_get_next_layer(packet : &[u8], p : Parameters) 
                                             -> Option<(UpperProtocol, Vec<u8>)> {
     if let Some(tcp) = TcpPacket::new(packet) {
-        if check_ports(tcp.get_source(), tcp.get_source(), p) {
+        if check_ports(tcp.get_source(), tcp.get_destination(), p) {
             return Some((UpperProtocol::Layer3((tcp.get_destination(), tcp.get_source())),
                 tcp.payload().to_vec()))
         }
-        return Some((UpperProtocol::Layer3((tcp.get_destination(), tcp.get_source())),
-                tcp.payload().to_vec()))
     }
 
     None
 }
 
+// THE NAMES OF THE FLAGS WHICH ARE SET: "SYN, ACK"
+
+pub fn flag_names(flags : u8) -> String {
+    let names : Vec<&str> = TCP_FLAG_NAMES.iter()
+        .filter(|(_, flag)| flags & flag != 0)
+        .map(|(name, _)| *name).collect();
+
+    names.join(", ")
+}
+
 pub fn print_output(packet : Vec<u8>) {
     let packet = TcpPacket::new(&packet).unwrap();
     print_program_name();
@@ -31,7 +39,8 @@ pub fn print_output(packet : Vec<u8>) {
     println!("{}> > > > TCP PACKET{}", get_color(1), get_color(0));
     println!("Source port: {}   Destination port: {}", packet.get_source(), packet.get_destination());
     println!("Sequence number: {}   Ack number: {}", packet.get_sequence(), packet.get_acknowledgement());
-    println!("Flags: {}   Window size: {}   Checksum: {}", packet.get_flags(), 
+    println!("Flags: {} ({})   Window size: {}   Checksum: {}", packet.get_flags(), 
+             flag_names(packet.get_flags()), 
              packet.get_window(), packet.get_checksum());
 
     println!("Urgent pointer: {}", packet.get_urgent_ptr());
@@ -44,5 +53,21 @@ pub fn print_output(packet : Vec<u8>) {
 
 #[cfg(test)]
 mod tests {
-    //use super::*;
+    use super::*;
+    use crate::filtering::PortRange;
+
+    #[test]
+    fn test_ports_and_flags() {
+        // 10.0.0.1:40000 -> 10.0.0.3:443, SYN AND ACK
+        let segment = [0x9c, 0x40, 0x01, 0xbb, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+                       0x50, 0x12, 0xff, 0xff, 0x00, 0x00, 0x00, 0x00];
+
+        assert!(check_and_get_next_layer(&segment, Parameters::NoParameter).is_some());
+        assert!(check_and_get_next_layer(&segment, 
+            Parameters::Port(vec![PortRange::single(443)])).is_some());
+        assert!(check_and_get_next_layer(&segment, 
+            Parameters::Port(vec![PortRange::single(80)])).is_none());
+
+        assert_eq!(flag_names(TcpPacket::new(&segment).unwrap().get_flags()), "SYN, ACK");
+    }
 }
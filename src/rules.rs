@@ -0,0 +1,265 @@
+// THE FILE MADE FOR THE RULES: MANY QUESTIONS ASKED IN ONE CAPTURE
+// EVERY RULE IS THE FILTER AND WHAT TO DO WITH THE PACKETS IT MATCHES,
+// ONE RULE PER LINE OF THE RULES FILE:
+//
+//     # EMPTY LINES AND THE LINES STARTING WITH # ARE SKIPPED
+//     tcp[rst]           => count resets
+//     udp and port 53    => save dns.pcapng
+//     port 23            => alert TELNET IS STILL USED
+//     host 10.0.0.5      => summary
+//     arp                => print
+//                        => count everything
+
+use std::fs;
+use std::io;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::{print_program_name, print_error, get_color};
+use crate::capture::{self, CaptureWriter, PacketInfo};
+use crate::filtering::expression::{self, Expression};
+use crate::layers::{self, Layer};
+
+const SEPARATOR : &str = "=>";
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Action {
+    // THE WHOLE DECODE, LIKE WITHOUT THE RULES
+    Print,
+
+    // ONE LINE FOR THE PACKET
+    Summary,
+
+    // INTO THE NAMED PCAP (OR PCAPNG) FILE
+    Save(String),
+
+    // ONLY COUNTED UNDER THE NAME, SHOWN WITH THE STATISTICS
+    Count(String),
+
+    // THE MESSAGE, WHICH STANDS OUT FROM THE REST OF THE OUTPUT
+    Alert(String),
+}
+
+#[derive(Clone, Debug)]
+pub struct Rule {
+    // THE RULE WITHOUT THE FILTER MATCHES EVERY PACKET
+    pub filter : Option<Expression>,
+    pub action : Action,
+}
+
+impl Rule {
+    pub fn matches(&self, layers : &[Layer]) -> bool {
+        self.filter.as_ref().is_none_or(|filter| filter.matches(layers))
+    }
+}
+
+fn parse_action(text : &str) -> Result<Action, String> {
+    let (name, argument) = match text.split_once(char::is_whitespace) {
+        Some((name, argument)) => (name, argument.trim()),
+        None => (text, ""),
+    };
+
+    match (name, argument) {
+        ("print", "")   => Ok(Action::Print),
+        ("summary", "") => Ok(Action::Summary),
+        ("print" | "summary", _) => Err(format!("{} TAKES NO ARGUMENT", name.to_uppercase())),
+
+        ("save" | "count", "") => Err(format!("{} NEEDS A NAME", name.to_uppercase())),
+        ("save", file)  => Ok(Action::Save(file.to_string())),
+        ("count", name) => Ok(Action::Count(name.to_string())),
+
+        ("alert", message) => Ok(Action::Alert(message.to_string())),
+
+        ("", _) => Err("NO ACTION AFTER '=>'".to_string()),
+        _ => Err(format!("UNKNOWN ACTION '{}'", name)),
+    }
+}
+
+// "FILTER => ACTION [ARGUMENT]"
+
+pub fn parse_rule(line : &str) -> Result<Rule, String> {
+    let Some((filter, action)) = line.split_once(SEPARATOR) else {
+        return Err("NO '=>' BETWEEN THE FILTER AND THE ACTION".to_string());
+    };
+
+    let filter = match filter.trim() {
+        "" => None,
+        filter => Some(expression::parse(filter)?),
+    };
+
+    Ok(Rule { filter, action : parse_action(action.trim())? })
+}
+
+pub fn parse_rules(text : &str) -> Result<Vec<Rule>, String> {
+    text.lines().enumerate()
+        .map(|(number, line)| (number + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+        .map(|(number, line)| parse_rule(line)
+                                  .map_err(|reason| format!("LINE {}: {}", number, reason)))
+        .collect()
+}
+
+pub fn load_rules(path : &str) -> Result<Vec<Rule>, String> {
+    let text = fs::read_to_string(path).map_err(|e| e.to_string())?;
+
+    parse_rules(&text)
+}
+
+
+
+// WHAT THE RULES NEED DURING THE CAPTURE: THE FILES AND THE COUNTERS
+// THE RULES SAVING INTO THE SAME FILE (OR COUNTING UNDER THE SAME NAME) SHARE THEM
+
+#[derive(Default)]
+pub struct RuleOutput {
+    actions  : Vec<Action>,
+    files    : Vec<(String, Mutex<Box<dyn CaptureWriter>>)>,
+    counters : Vec<(String, AtomicU64)>,
+}
+
+impl RuleOutput {
+    // ALL THE FILES ARE CREATED BEFORE ANY PACKET IS CAUGHT
+
+    pub fn open(rules : &[Rule], link_type : u32) -> io::Result<RuleOutput> {
+        let mut output = RuleOutput::default();
+
+        for rule in rules {
+            match &rule.action {
+                Action::Save(path) if !output.files.iter().any(|(name, _)| name == path) => {
+                    let writer = capture::create_writer(path, link_type)
+                        .map_err(|e| io::Error::new(e.kind(), format!("'{}': {}", path, e)))?;
+
+                    output.files.push((path.clone(), Mutex::new(writer)));
+                },
+                Action::Count(name) if !output.counters.iter().any(|(known, _)| known == name) => {
+                    output.counters.push((name.clone(), AtomicU64::new(0)));
+                },
+                _ => (),
+            }
+
+            output.actions.push(rule.action.clone());
+        }
+
+        Ok(output)
+    }
+
+    // DOING WHAT THE MATCHED RULES ASK, THE SAME ACTION ONLY ONCE FOR THE PACKET
+    // (TWO RULES SAVING INTO ONE FILE DO NOT SAVE THE PACKET TWICE)
+
+    pub fn emit(&self, info : &PacketInfo, layers : &[Layer], data : &[u8], matched : &[usize]) {
+        let mut done : Vec<&Action> = vec![];
+
+        for action in matched.iter().filter_map(|index| self.actions.get(*index)) {
+            if done.contains(&action) {
+                continue;
+            }
+
+            done.push(action);
+
+            match action {
+                Action::Print   => layers::print_packet(info, layers),
+                Action::Summary => println!("{}", layers::summarize_packet(info, layers)),
+                Action::Alert(message) => {
+                    let _stdout = io::stdout().lock();
+
+                    print!("{}[OHREST ALERT]{} ", get_color(3), get_color(0));
+                    println!("{}{}{} {}", get_color(1), message, get_color(0),
+                             layers::summarize_packet(info, layers));
+                },
+                Action::Count(name) => {
+                    let counter = self.counters.iter().find(|(known, _)| known == name);
+
+                    if let Some((_, counter)) = counter {
+                        counter.fetch_add(1, Ordering::Relaxed);
+                    }
+                },
+                Action::Save(path) => {
+                    let writer = self.files.iter().find(|(known, _)| known == path);
+
+                    let Some((_, writer)) = writer else {
+                        continue;
+                    };
+
+                    let mut writer = writer.lock().unwrap_or_else(|e| e.into_inner());
+
+                    if let Err(e) = writer.write_packet(info, data) {
+                        print_error();
+                        println!("CANNOT SAVE PACKET #{} FROM {} INTO '{}': {}",
+                                 info.id, info.interface, path, e);
+                    }
+                },
+            }
+        }
+    }
+
+    pub fn counters(&self) -> Vec<(String, u64)> {
+        self.counters.iter()
+            .map(|(name, counter)| (name.clone(), counter.load(Ordering::Relaxed)))
+            .collect()
+    }
+
+    pub fn print_counters(&self) {
+        if self.counters.is_empty() {
+            return;
+        }
+
+        let _stdout = io::stdout().lock();
+
+        print_program_name();
+        println!("{}RULE COUNTERS{}", get_color(1), get_color(0));
+
+        for (name, count) in self.counters() {
+            println!("{}{}:{} {}", get_color(4), name, get_color(0), count);
+        }
+    }
+
+    pub fn finish(&self) {
+        for (path, writer) in &self.files {
+            let mut writer = writer.lock().unwrap_or_else(|e| e.into_inner());
+
+            if let Err(e) = writer.flush() {
+                print_error();
+                println!("CANNOT FINISH WRITING THE FILE '{}': {}", path, e);
+            }
+        }
+    }
+}
+
+
+
+// TESTS
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_rules() {
+        let rules = parse_rules("# THE RESETS AND DNS\n\
+                                 tcp[rst] => count resets\n\
+                                 \n\
+                                 udp and port 53 =>   save  dns.pcapng\n\
+                                 port 23 => alert telnet is used\n\
+                                 host ::1 => summary\n\
+                                 => print\n").unwrap();
+
+        let actions : Vec<Action> = rules.iter().map(|rule| rule.action.clone()).collect();
+
+        assert_eq!(actions, vec![Action::Count("resets".to_string()),
+                                 Action::Save("dns.pcapng".to_string()),
+                                 Action::Alert("telnet is used".to_string()),
+                                 Action::Summary, Action::Print]);
+        assert!(rules[3].filter.is_some());
+        assert!(rules[4].filter.is_none());
+
+        assert_eq!(parse_rules("tcp\n").unwrap_err(),
+                   "LINE 1: NO '=>' BETWEEN THE FILTER AND THE ACTION");
+        assert_eq!(parse_rules("# X\ntcp => shout\n").unwrap_err(),
+                   "LINE 2: UNKNOWN ACTION 'shout'");
+        assert_eq!(parse_rules("tcp => count").unwrap_err(), "LINE 1: COUNT NEEDS A NAME");
+        assert_eq!(parse_rules("tcp => print all").unwrap_err(),
+                   "LINE 1: PRINT TAKES NO ARGUMENT");
+        assert!(parse_rules("host => print").is_err());
+        assert!(parse_rules("tcp =>").is_err());
+    }
+}
@@ -0,0 +1,165 @@
+// THE FILE MADE FOR COUNTING WHAT HAPPENED ON EVERY INTERFACE
+// THE COUNTERS ARE ATOMIC AS EVERY INTERFACE THREAD UPDATES ITS OWN ONES
+// WHILE THE MAIN THREAD MIGHT READ THEM AT ANY MOMENT
+
+use std::io;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+use crate::{print_program_name, get_color};
+
+// THE LAYERS THE PACKET CAN BE BROKEN ON
+
+pub const DECODE_LAYERS : [&str; 3] = ["ACCESS", "NETWORK", "TRANSPORT"];
+
+// WHAT THE KERNEL ITSELF KNOWS ABOUT THE SOCKET (PACKET_STATISTICS ON LINUX)
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct KernelStats {
+    pub received : u64,
+    pub dropped  : u64,
+}
+
+// EVERY KIND OF THE RECEIVE ERROR, HOW MANY TIMES IT HAPPENED
+// AND THE MESSAGE OF THE FIRST ONE
+
+#[derive(Debug)]
+pub struct ErrorCount {
+    pub kind    : io::ErrorKind,
+    pub count   : u64,
+    pub message : String,
+}
+
+#[derive(Debug, Default)]
+pub struct InterfaceStats {
+    pub name            : String,
+    pub seen            : AtomicU64,
+    pub matched         : AtomicU64,
+    pub errors          : AtomicU64,
+    pub decode_failures : [AtomicU64; 3],
+    pub kernel_received : AtomicU64,
+    pub kernel_dropped  : AtomicU64,
+
+    // ONLY THE LIVE SOURCES ON LINUX KNOW WHAT THE KERNEL DROPPED
+    pub kernel_known    : AtomicBool,
+
+    pub error_kinds     : Mutex<Vec<ErrorCount>>,
+}
+
+impl InterfaceStats {
+    pub fn new(name : &str) -> InterfaceStats {
+        InterfaceStats { name : name.to_string(), ..Default::default() }
+    }
+
+    pub fn decode_failure(&self, layer : &str) {
+        if let Some(position) = DECODE_LAYERS.iter().position(|name| *name == layer) {
+            increment(&self.decode_failures[position]);
+        }
+    }
+
+    // RETURNS TRUE IF THE KIND OF THE ERROR IS NEW FOR THE INTERFACE,
+    // SO THE USER IS TOLD ABOUT IT ONLY ONCE
+
+    pub fn receive_error(&self, error : &io::Error) -> bool {
+        increment(&self.errors);
+
+        let mut kinds = self.error_kinds.lock().unwrap();
+
+        match kinds.iter_mut().find(|counted| counted.kind == error.kind()) {
+            Some(counted) => {
+                counted.count += 1;
+                false
+            },
+            None => {
+                kinds.push(ErrorCount { kind : error.kind(), count : 1,
+                                        message : error.to_string() });
+                true
+            }
+        }
+    }
+
+    // THE KERNEL GIVES THE NUMBERS SINCE THE PREVIOUS ASKING, SO THEY ARE ADDED UP
+
+    pub fn add_kernel_stats(&self, kernel : KernelStats) {
+        self.kernel_received.fetch_add(kernel.received, Ordering::Relaxed);
+        self.kernel_dropped.fetch_add(kernel.dropped, Ordering::Relaxed);
+        self.kernel_known.store(true, Ordering::Relaxed);
+    }
+}
+
+pub fn increment(counter : &AtomicU64) {
+    counter.fetch_add(1, Ordering::Relaxed);
+}
+
+fn print_interface(interface : &InterfaceStats) {
+    println!("{}{}:{}   {}Received:{} {}   {}Matched:{} {}   {}Errors:{} {}",
+             get_color(1), interface.name, get_color(0),
+             get_color(2), get_color(0), interface.seen.load(Ordering::Relaxed),
+             get_color(4), get_color(0), interface.matched.load(Ordering::Relaxed),
+             get_color(3), get_color(0), interface.errors.load(Ordering::Relaxed));
+
+    if interface.kernel_known.load(Ordering::Relaxed) {
+        println!("    {}Kernel Received:{} {}   {}Kernel Dropped:{} {}",
+                 get_color(2), get_color(0), interface.kernel_received.load(Ordering::Relaxed),
+                 get_color(3), get_color(0), interface.kernel_dropped.load(Ordering::Relaxed));
+    }
+
+    let failures : Vec<String> = DECODE_LAYERS.iter()
+        .zip(interface.decode_failures.iter())
+        .map(|(layer, count)| (layer, count.load(Ordering::Relaxed)))
+        .filter(|(_, count)| *count > 0)
+        .map(|(layer, count)| format!("{} {}", layer, count))
+        .collect();
+
+    if !failures.is_empty() {
+        println!("    {}Decode Failures:{} {}", get_color(5), get_color(0), failures.join(", "));
+    }
+
+    for error in interface.error_kinds.lock().unwrap().iter() {
+        println!("    {}Error {:?}:{} {} ({})",
+                 get_color(3), error.kind, get_color(0), error.count, error.message);
+    }
+}
+
+pub fn print_statistics(title : &str, stats : &[Arc<InterfaceStats>]) {
+    let _stdout = io::stdout().lock();
+
+    print_program_name();
+    println!("{}{}{}", get_color(1), title, get_color(0));
+
+    for interface in stats {
+        print_interface(interface);
+    }
+}
+
+pub fn print_summary(stats : &[Arc<InterfaceStats>]) {
+    print_statistics("END OF THE CAPTURE", stats);
+}
+
+
+
+// TESTS
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error_kinds() {
+        let stats = InterfaceStats::new("eth0");
+
+        assert!(stats.receive_error(&io::Error::other("Unexpected poll event")));
+        assert!(!stats.receive_error(&io::Error::other("Unexpected poll event")));
+        assert!(stats.receive_error(&io::Error::from(io::ErrorKind::PermissionDenied)));
+
+        assert_eq!(stats.errors.load(Ordering::Relaxed), 3);
+        assert_eq!(stats.error_kinds.lock().unwrap()[0].count, 2);
+
+        stats.decode_failure("NETWORK");
+        stats.add_kernel_stats(KernelStats { received : 10, dropped : 2 });
+        stats.add_kernel_stats(KernelStats { received : 5, dropped : 1 });
+
+        assert_eq!(stats.decode_failures[1].load(Ordering::Relaxed), 1);
+        assert_eq!(stats.kernel_dropped.load(Ordering::Relaxed), 3);
+    }
+}